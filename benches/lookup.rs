@@ -0,0 +1,36 @@
+use std::hint::black_box;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use indexable_sorted_map::IndexableSortedMap;
+
+// Large enough that the tree is far out of cache, so lookups are dominated
+// by memory latency. Compare runs with and without `--features perf`.
+const ENTRIES: u64 = 1 << 22;
+
+// A fixed xorshift sequence, so every run probes the same keys.
+fn keys(seed: u64) -> impl Iterator<Item = u64> {
+    let mut state = seed;
+    std::iter::repeat_with(move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    })
+}
+
+fn lookup(c: &mut Criterion) {
+    let mut map = IndexableSortedMap::new();
+    for key in keys(0x9e37_79b9_7f4a_7c15).take(ENTRIES as usize) {
+        map.insert(key % (ENTRIES * 2), key);
+    }
+
+    // About half the probes hit.
+    let mut probes = keys(0x2545_f491_4f6c_dd1d).map(|key| key % (ENTRIES * 2));
+
+    c.bench_function("lookup out of cache", |b| {
+        b.iter(|| black_box(map.lookup(&probes.next().unwrap())))
+    });
+}
+
+criterion_group!(benches, lookup);
+criterion_main!(benches);
@@ -0,0 +1,232 @@
+use std::collections::BTreeMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use indexable_sorted_map::IndexableSortedMap;
+
+// Compares the map with a `BTreeMap` and a sorted `Vec` on each operation
+// at each size. The largest sizes take a while; pick out a subset with a
+// filter, as in `cargo bench --bench operations -- 'rank/'`.
+const SIZES: [usize; 5] = [1_000, 10_000, 100_000, 1_000_000, 10_000_000];
+
+// Each structure starts with the even keys below twice its size, built in
+// bulk. Inserts add odd keys and removals take even ones, visiting slots
+// in a scattered order that repeats only once all of them are used:
+// `SCATTER` is coprime with every size above.
+const SCATTER: usize = 2_654_435_761;
+
+trait Subject: Clone {
+    const NAME: &'static str;
+
+    fn build(entries: Vec<(u64, u64)>) -> Self;
+    fn insert(&mut self, key: u64, value: u64);
+    fn remove(&mut self, key: u64);
+    fn lookup(&self, key: u64) -> Option<&u64>;
+    fn index(&self, i: usize) -> Option<(&u64, &u64)>;
+    fn rank(&self, key: u64) -> Result<usize, usize>;
+    fn sum(&self) -> u64;
+}
+
+impl Subject for IndexableSortedMap<u64, u64> {
+    const NAME: &'static str = "IndexableSortedMap";
+
+    fn build(entries: Vec<(u64, u64)>) -> Self {
+        entries.into_iter().collect()
+    }
+
+    fn insert(&mut self, key: u64, value: u64) {
+        black_box(IndexableSortedMap::insert(self, key, value));
+    }
+
+    fn remove(&mut self, key: u64) {
+        black_box(IndexableSortedMap::remove(self, &key));
+    }
+
+    fn lookup(&self, key: u64) -> Option<&u64> {
+        IndexableSortedMap::lookup(self, &key)
+    }
+
+    fn index(&self, i: usize) -> Option<(&u64, &u64)> {
+        IndexableSortedMap::index(self, i)
+    }
+
+    fn rank(&self, key: u64) -> Result<usize, usize> {
+        IndexableSortedMap::rank(self, &key)
+    }
+
+    fn sum(&self) -> u64 {
+        self.values().sum()
+    }
+}
+
+// A `BTreeMap` has no positional queries, so `index` and `rank` walk the
+// entries up to the position, as a caller without this crate would.
+impl Subject for BTreeMap<u64, u64> {
+    const NAME: &'static str = "BTreeMap";
+
+    fn build(entries: Vec<(u64, u64)>) -> Self {
+        entries.into_iter().collect()
+    }
+
+    fn insert(&mut self, key: u64, value: u64) {
+        black_box(BTreeMap::insert(self, key, value));
+    }
+
+    fn remove(&mut self, key: u64) {
+        black_box(BTreeMap::remove(self, &key));
+    }
+
+    fn lookup(&self, key: u64) -> Option<&u64> {
+        self.get(&key)
+    }
+
+    fn index(&self, i: usize) -> Option<(&u64, &u64)> {
+        self.iter().nth(i)
+    }
+
+    fn rank(&self, key: u64) -> Result<usize, usize> {
+        let below = self.range(..key).count();
+        if self.contains_key(&key) {
+            Ok(below)
+        } else {
+            Err(below)
+        }
+    }
+
+    fn sum(&self) -> u64 {
+        self.values().sum()
+    }
+}
+
+impl Subject for Vec<(u64, u64)> {
+    const NAME: &'static str = "sorted Vec";
+
+    fn build(entries: Vec<(u64, u64)>) -> Self {
+        entries
+    }
+
+    fn insert(&mut self, key: u64, value: u64) {
+        match Subject::rank(self, key) {
+            Ok(i) => self[i].1 = value,
+            Err(i) => Vec::insert(self, i, (key, value)),
+        }
+    }
+
+    fn remove(&mut self, key: u64) {
+        if let Ok(i) = Subject::rank(self, key) {
+            black_box(Vec::remove(self, i));
+        }
+    }
+
+    fn lookup(&self, key: u64) -> Option<&u64> {
+        let i = Subject::rank(self, key).ok()?;
+        Some(&self[i].1)
+    }
+
+    fn index(&self, i: usize) -> Option<(&u64, &u64)> {
+        self.get(i).map(|(k, v)| (k, v))
+    }
+
+    fn rank(&self, key: u64) -> Result<usize, usize> {
+        self.binary_search_by_key(&key, |(k, _v)| *k)
+    }
+
+    fn sum(&self) -> u64 {
+        self.iter().map(|(_k, v)| v).sum()
+    }
+}
+
+// A fixed xorshift sequence, so every run probes the same keys.
+fn probes(seed: u64) -> impl Iterator<Item = u64> {
+    let mut state = seed;
+    std::iter::repeat_with(move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    })
+}
+
+fn slot(i: usize, size: usize) -> u64 {
+    ((i % size).wrapping_mul(SCATTER) % size) as u64
+}
+
+// Times `iters` edits, each given a distinct slot, on copies of `base`.
+// A copy takes at most a tenth of its size in edits before it is replaced,
+// so the structure stays near the size being measured. Copying and
+// dropping are not timed.
+fn time_edits<S: Subject>(base: &S, size: usize, iters: u64, edit: impl Fn(&mut S, u64)) -> Duration {
+    let per_copy = (size / 10).max(1) as u64;
+    let mut total = Duration::ZERO;
+    let mut done = 0;
+
+    while done < iters {
+        let count = per_copy.min(iters - done);
+        let mut subject = base.clone();
+        let start = Instant::now();
+        for i in 0..count {
+            edit(&mut subject, slot(i as usize, size));
+        }
+        total += start.elapsed();
+        done += count;
+    }
+
+    total
+}
+
+fn bench_subject<S: Subject>(c: &mut Criterion, size: usize) {
+    let subject = S::build((0..size as u64).map(|i| (i * 2, i)).collect());
+    let id = BenchmarkId::new(S::NAME, size);
+    let sample_size = if size >= 1_000_000 { 10 } else { 100 };
+
+    let mut group = c.benchmark_group("insert");
+    group.sample_size(sample_size);
+    group.bench_function(id.clone(), |b| {
+        b.iter_custom(|iters| time_edits(&subject, size, iters, |s, slot| s.insert(slot * 2 + 1, slot)))
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("remove");
+    group.sample_size(sample_size);
+    group.bench_function(id.clone(), |b| {
+        b.iter_custom(|iters| time_edits(&subject, size, iters, |s, slot| s.remove(slot * 2)))
+    });
+    group.finish();
+
+    // About half the probed keys are present.
+    let mut group = c.benchmark_group("lookup");
+    group.sample_size(sample_size);
+    let mut keys = probes(0x2545_f491_4f6c_dd1d).map(|key| key % (size as u64 * 2));
+    group.bench_function(id.clone(), |b| b.iter(|| black_box(subject.lookup(keys.next().unwrap()))));
+    group.finish();
+
+    let mut group = c.benchmark_group("index");
+    group.sample_size(sample_size);
+    let mut positions = probes(0x9e37_79b9_7f4a_7c15).map(|i| i as usize % size);
+    group.bench_function(id.clone(), |b| b.iter(|| black_box(subject.index(positions.next().unwrap()))));
+    group.finish();
+
+    let mut group = c.benchmark_group("rank");
+    group.sample_size(sample_size);
+    let mut keys = probes(0x6a09_e667_f3bc_c909).map(|key| key % (size as u64 * 2));
+    group.bench_function(id.clone(), |b| b.iter(|| black_box(subject.rank(keys.next().unwrap()))));
+    group.finish();
+
+    let mut group = c.benchmark_group("iterate");
+    group.sample_size(sample_size);
+    group.throughput(Throughput::Elements(size as u64));
+    group.bench_function(id, |b| b.iter(|| black_box(subject.sum())));
+    group.finish();
+}
+
+fn operations(c: &mut Criterion) {
+    for size in SIZES {
+        bench_subject::<IndexableSortedMap<u64, u64>>(c, size);
+        bench_subject::<BTreeMap<u64, u64>>(c, size);
+        bench_subject::<Vec<(u64, u64)>>(c, size);
+    }
+}
+
+criterion_group!(benches, operations);
+criterion_main!(benches);
@@ -0,0 +1,28 @@
+#ifndef INDEXABLE_SORTED_MAP_H
+#define INDEXABLE_SORTED_MAP_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct IsmMap IsmMap;
+
+IsmMap *ism_new(void);
+void ism_free(IsmMap *map);
+size_t ism_len(const IsmMap *map);
+
+bool ism_insert(IsmMap *map, const uint8_t *key, size_t key_len, void *value, void **out_value);
+bool ism_lookup(const IsmMap *map, const uint8_t *key, size_t key_len, void **out_value);
+bool ism_index(const IsmMap *map, size_t i, const uint8_t **out_key, size_t *out_key_len, void **out_value);
+bool ism_rank(const IsmMap *map, const uint8_t *key, size_t key_len, size_t *out_index);
+bool ism_remove(IsmMap *map, const uint8_t *key, size_t key_len, void **out_value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
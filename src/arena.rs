@@ -0,0 +1,123 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use crate::{IndexableSortedMap, Measure, NodePool, SortedMapBuilder};
+
+// How many allocations a map takes from its arena at a time, so the lock
+// is taken once per batch rather than once per node.
+const BATCH: usize = 64;
+
+/// A shared stock of tree nodes and leaf buffers, allocated up front, for
+/// maps that must not call the global allocator as they change. Maps and
+/// sets made with `new_in`, and maps built with `SortedMapBuilder::new_in`,
+/// take what they need from the stock a batch at a time and give
+/// everything back when they are dropped. So do the maps derived from
+/// them: those split off, merged, cloned or rebuilt in bulk, and the
+/// iterators that `into_iter` and `drain` leave emptying the tree. Handles are cheap to clone and can be sent between threads.
+///
+/// Once the stock runs out, maps allocate for themselves.
+pub struct NodeArena<K: Ord, V, M: Measure<K, V> = ()> {
+    stock: Arc<Mutex<NodePool<K, V, M>>>,
+}
+
+// SAFETY: the stock only ever holds uninitialized nodes and empty leaf
+// vectors, so no `K` or `V` is reachable through an arena, and handing
+// the bare allocations between threads is sound whatever the types. This
+// keeps a map's auto traits those of its entries.
+unsafe impl<K: Ord, V, M: Measure<K, V>> Send for NodeArena<K, V, M> {}
+unsafe impl<K: Ord, V, M: Measure<K, V>> Sync for NodeArena<K, V, M> {}
+
+impl<K: Ord, V, M: Measure<K, V>> Clone for NodeArena<K, V, M> {
+    fn clone(&self) -> Self {
+        NodeArena { stock: self.stock.clone() }
+    }
+}
+
+impl<K: Ord, V, M: Measure<K, V>> Default for NodeArena<K, V, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V, M: Measure<K, V>> NodeArena<K, V, M> {
+    pub fn new() -> Self {
+        NodeArena { stock: Arc::new(Mutex::new(NodePool::new())) }
+    }
+
+    /// An arena stocked for maps holding `capacity` entries between them.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let arena = Self::new();
+        arena.reserve(capacity);
+        arena
+    }
+
+    /// Tops the stock up to enough for `additional` entries, counting
+    /// what it already holds.
+    pub fn reserve(&self, additional: usize) {
+        self.stock().reserve(additional);
+    }
+
+    /// Frees the allocations the arena holds. Maps drawing on it keep
+    /// working, and give theirs back when they are dropped.
+    pub fn shrink_to_fit(&self) {
+        self.stock().shrink_to_fit();
+    }
+
+    // The stock is left consistent even if a holder of the lock panics,
+    // so poisoning is ignored.
+    fn stock(&self) -> MutexGuard<'_, NodePool<K, V, M>> {
+        self.stock.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<K: Ord, V> IndexableSortedMap<K, V> {
+    /// An empty map that takes its nodes from `arena`.
+    pub fn new_in(arena: &NodeArena<K, V>) -> Self {
+        IndexableSortedMap::with_pool(NodePool::new_in(arena))
+    }
+}
+
+impl<K: Ord, V> SortedMapBuilder<K, V> {
+    /// A builder whose map takes its nodes from `arena`.
+    pub fn new_in(arena: &NodeArena<K, V>) -> Self {
+        SortedMapBuilder::with_pool(NodePool::new_in(arena))
+    }
+}
+
+impl<K: Ord, V, M: Measure<K, V>> NodePool<K, V, M> {
+    fn new_in(arena: &NodeArena<K, V, M>) -> Self {
+        NodePool {
+            spare: Vec::new(),
+            buffers: Vec::new(),
+            arena: Some(arena.clone()),
+            #[cfg(feature = "cow")]
+            cloner: Default::default(),
+        }
+    }
+
+    // Takes up to a batch of each kind of allocation from the arena, if
+    // there is one.
+    pub(crate) fn restock(&mut self) {
+        let Some(arena) = &self.arena else {
+            return;
+        };
+        let mut stock = arena.stock();
+
+        let wanted = BATCH.saturating_sub(self.spare.len()).min(stock.spare.len());
+        let from = stock.spare.len() - wanted;
+        self.spare.extend(stock.spare.drain(from..));
+
+        let wanted = BATCH.saturating_sub(self.buffers.len()).min(stock.buffers.len());
+        let from = stock.buffers.len() - wanted;
+        self.buffers.extend(stock.buffers.drain(from..));
+    }
+}
+
+impl<K: Ord, V, M: Measure<K, V>> Drop for NodePool<K, V, M> {
+    fn drop(&mut self) {
+        if let Some(arena) = &self.arena {
+            let mut stock = arena.stock();
+            stock.spare.append(&mut self.spare);
+            stock.buffers.append(&mut self.buffers);
+        }
+    }
+}
@@ -0,0 +1,538 @@
+use std::borrow::Borrow;
+use std::collections::VecDeque;
+use std::ops::{self, Bound, Deref, RangeBounds};
+use std::{array, iter, slice};
+
+use crate::{find, tree_mut, Inserted, Node, NodePool, Removed, Target, Tree};
+
+/// A summary kept for every subtree of an `AugmentedSortedMap`, such as a
+/// sum or a maximum over its entries. `combine` must be associative with
+/// `empty` as its identity, so that a subtree's summary does not depend on
+/// the shape of the tree.
+pub trait Measure<K, V> {
+    type Summary: Clone;
+
+    fn measure(key: &K, value: &V) -> Self::Summary;
+
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary;
+
+    fn empty() -> Self::Summary;
+}
+
+/// Keeps nothing beyond the size every subtree already records. This is
+/// what the other maps in the crate are built on.
+impl<K, V> Measure<K, V> for () {
+    type Summary = ();
+
+    fn measure(_key: &K, _value: &V) -> Self::Summary {}
+
+    fn combine(_left: &(), _right: &()) -> Self::Summary {}
+
+    fn empty() -> Self::Summary {}
+}
+
+/// A sorted map that allows lookup by index and also keeps the summary
+/// `M` of every subtree up to date through insertions and removals. The
+/// summary of any key range is then the combination of O(log n) subtree
+/// summaries.
+pub struct AugmentedSortedMap<K: Ord, V, M: Measure<K, V>> {
+    root: Option<Node<K, V, M>>,
+    pool: NodePool<K, V, M>,
+}
+
+// The two or three children of a branch, held in place rather than
+// collected, so that walking down the tree does not allocate. A branch of
+// two repeats its last child to fill the array.
+struct Children<'a, K: Ord, V, M: Measure<K, V>> {
+    nodes: [&'a Node<K, V, M>; 3],
+    len: usize,
+}
+
+// The subtrees not yet visited, in order, between the entries left in the
+// leaves at either end.
+pub struct AugmentedIter<'a, K: Ord, V, M: Measure<K, V>> {
+    front: slice::Iter<'a, (K, V)>,
+    nodes: VecDeque<&'a Node<K, V, M>>,
+    back: slice::Iter<'a, (K, V)>,
+    remaining: usize,
+}
+
+/// The entries in a run of positions whose subtrees were not ruled out by
+/// the `visit` predicate, in key order.
+pub struct AugmentedSearch<'a, K: Ord, V, M: Measure<K, V>, F> {
+    // The rest of the leaf being visited, and the position of its next
+    // entry.
+    entries: &'a [(K, V)],
+    position: usize,
+    stack: Vec<(&'a Node<K, V, M>, usize)>,
+    start: usize,
+    end: usize,
+    visit: F,
+}
+
+impl<K: Ord, V, M: Measure<K, V>> Default for AugmentedSortedMap<K, V, M> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V, M: Measure<K, V>> Drop for AugmentedSortedMap<K, V, M> {
+    fn drop(&mut self) {
+        if let Some(root) = self.root.take() {
+            self.pool.dismantle(root);
+        }
+    }
+}
+
+impl<K: Ord, V, M: Measure<K, V>> AugmentedSortedMap<K, V, M> {
+    pub fn new() -> Self {
+        AugmentedSortedMap {
+            root: None,
+            pool: NodePool::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.root.as_ref().map_or(0, |node| node.size)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn lookup<Q: ?Sized + Ord>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        let i = self.rank(key).ok()?;
+        self.index(i).map(|(_k, v)| v)
+    }
+
+    pub fn index(&self, mut i: usize) -> Option<(&K, &V)> {
+        let mut node = self.root.as_ref()?;
+        if i >= node.size {
+            return None;
+        }
+
+        loop {
+            match node.tree.as_ref() {
+                Tree::Leaf(entries) => {
+                    let (k, v) = &entries[i];
+                    return Some((k, v));
+                },
+                _ => {
+                    for child in node.children() {
+                        if i < child.size {
+                            node = child;
+                            break;
+                        }
+                        i -= child.size;
+                    }
+                },
+            }
+        }
+    }
+
+    pub fn rank<Q: ?Sized + Ord>(&self, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+    {
+        let Some(mut node) = self.root.as_ref() else {
+            return Err(0);
+        };
+        let mut offset = 0;
+
+        loop {
+            match node.tree.as_ref() {
+                Tree::Leaf(entries) => {
+                    return match find(entries, key) {
+                        Ok(slot) => Ok(offset + slot),
+                        Err(slot) => Err(offset + slot),
+                    };
+                },
+                _ => {
+                    let children = node.children();
+                    let (position, _) = node.locate(Target::Key(key));
+                    offset += children[..position].iter().map(|child| child.size).sum::<usize>();
+                    node = children[position];
+                },
+            }
+        }
+    }
+
+    /// The number of leading entries whose keys satisfy `pred`, which must
+    /// hold for some prefix of the keys and for none after it.
+    pub fn partition_point<F: FnMut(&K) -> bool>(&self, mut pred: F) -> usize {
+        let Some(mut node) = self.root.as_ref() else {
+            return 0;
+        };
+        let mut offset = 0;
+
+        loop {
+            match node.tree.as_ref() {
+                Tree::Leaf(entries) => {
+                    return offset + entries.partition_point(|(k, _v)| pred(k));
+                },
+                _ => {
+                    let children = node.children();
+                    let position = children.iter().skip(1).take_while(|child| pred(child.min_key())).count();
+                    offset += children[..position].iter().map(|child| child.size).sum::<usize>();
+                    node = children[position];
+                },
+            }
+        }
+    }
+
+    /// Inserts an entry, returning the value it displaced if `key` was
+    /// already present. The original key is kept in that case.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let Some(root) = &mut self.root else {
+            self.root = Some(self.pool.singleton(key, value));
+            return None;
+        };
+
+        match root.insert(&mut self.pool, key, || value, true).0 {
+            Inserted::Replaced(old) => Some(old),
+            Inserted::Present | Inserted::Grew => None,
+            Inserted::Split(right) => {
+                let left = self.root.take().unwrap();
+                self.root = Some(self.pool.branch2(left, right));
+                None
+            },
+        }
+    }
+
+    pub fn remove<Q: ?Sized + Ord>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        let root = self.root.as_mut()?;
+
+        match root.remove(&mut self.pool, Target::Key(key)) {
+            Removed::Missing => None,
+            Removed::Entry { entry: (_k, v), .. } => {
+                if root.size == 0 {
+                    let root = self.root.take().unwrap();
+                    self.pool.dismantle(root);
+                }
+                Some(v)
+            },
+        }
+    }
+
+    /// Applies `f` to the value under `key`, then brings the summaries
+    /// along its path up to date.
+    pub fn update<Q: ?Sized + Ord, R, F: FnOnce(&mut V) -> R>(&mut self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+    {
+        self.root.as_mut()?.update(key, f)
+    }
+
+    /// The summary of every entry.
+    pub fn summary(&self) -> M::Summary {
+        self.root.as_ref().map_or_else(M::empty, |node| node.summary.clone())
+    }
+
+    /// The summary of the entries whose keys lie in `range`, in O(log n).
+    pub fn query_range<Q: ?Sized + Ord, R: RangeBounds<Q>>(&self, range: R) -> M::Summary
+    where
+        K: Borrow<Q>,
+    {
+        let positions = self.range_positions(&range);
+        self.query_by_index(positions)
+    }
+
+    /// The summary of the entries at the positions in `range`, in
+    /// O(log n).
+    pub fn query_by_index(&self, range: ops::Range<usize>) -> M::Summary {
+        match &self.root {
+            Some(node) if range.start < range.end.min(node.size) => node.fold(range.start, range.end),
+            _ => M::empty(),
+        }
+    }
+
+    /// The first entry, along with its position, at which the summary of
+    /// every entry up to and including it satisfies `pred`. `pred` must be
+    /// false for some prefix of the running summaries and true after it,
+    /// as for a running total exceeding a threshold.
+    pub fn find_prefix<F: FnMut(&M::Summary) -> bool>(&self, mut pred: F) -> Option<(usize, &K, &V)> {
+        let mut node = self.root.as_ref()?;
+        if !pred(&node.summary) {
+            return None;
+        }
+
+        let mut before = M::empty();
+        let mut offset = 0;
+
+        loop {
+            match node.tree.as_ref() {
+                Tree::Leaf(entries) => {
+                    let last = entries.len() - 1;
+
+                    for (slot, (k, v)) in entries.iter().enumerate() {
+                        before = M::combine(&before, &M::measure(k, v));
+                        if slot == last || pred(&before) {
+                            return Some((offset + slot, k, v));
+                        }
+                    }
+                },
+                _ => {
+                    let children = node.children();
+                    let last = children.len() - 1;
+
+                    for (position, child) in children.into_iter().enumerate() {
+                        let through = M::combine(&before, &child.summary);
+                        if position == last || pred(&through) {
+                            node = child;
+                            break;
+                        }
+                        before = through;
+                        offset += child.size;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Iterates over the entries whose keys lie in `range`, skipping every
+    /// subtree, and every entry, for whose summary `visit` returns false.
+    /// When `visit` rules out all but k entries this costs O(k log n).
+    pub fn search<Q: ?Sized + Ord, R: RangeBounds<Q>, F: FnMut(&M::Summary) -> bool>(
+        &self,
+        range: R,
+        visit: F,
+    ) -> AugmentedSearch<'_, K, V, M, F>
+    where
+        K: Borrow<Q>,
+    {
+        let positions = self.range_positions(&range);
+        self.search_by_index(positions, visit)
+    }
+
+    /// As `search`, over the entries at the positions in `range`.
+    pub fn search_by_index<F: FnMut(&M::Summary) -> bool>(
+        &self,
+        range: ops::Range<usize>,
+        visit: F,
+    ) -> AugmentedSearch<'_, K, V, M, F> {
+        AugmentedSearch {
+            entries: &[],
+            position: 0,
+            stack: self.root.iter().map(|node| (node, 0)).collect(),
+            start: range.start,
+            end: range.end,
+            visit,
+        }
+    }
+
+    pub fn iter(&self) -> AugmentedIter<'_, K, V, M> {
+        AugmentedIter {
+            front: [].iter(),
+            nodes: self.root.iter().collect(),
+            back: [].iter(),
+            remaining: self.len(),
+        }
+    }
+
+    fn range_positions<Q: ?Sized + Ord, R: RangeBounds<Q>>(&self, range: &R) -> ops::Range<usize>
+    where
+        K: Borrow<Q>,
+    {
+        let start = match range.start_bound() {
+            Bound::Unbounded => 0,
+            Bound::Included(k) => match self.rank(k) {
+                Ok(i) | Err(i) => i,
+            },
+            Bound::Excluded(k) => match self.rank(k) {
+                Ok(i) => i + 1,
+                Err(i) => i,
+            },
+        };
+
+        let end = match range.end_bound() {
+            Bound::Unbounded => self.len(),
+            Bound::Included(k) => match self.rank(k) {
+                Ok(i) => i + 1,
+                Err(i) => i,
+            },
+            Bound::Excluded(k) => match self.rank(k) {
+                Ok(i) | Err(i) => i,
+            },
+        };
+
+        start..end.max(start)
+    }
+}
+
+impl<K: Ord, V, M: Measure<K, V>> Node<K, V, M> {
+    fn children(&self) -> Children<'_, K, V, M> {
+        match self.tree.as_ref() {
+            Tree::Leaf(_entries) => unreachable!("a leaf has no children"),
+            Tree::Branch2(a, b) => Children { nodes: [a, b, b], len: 2 },
+            Tree::Branch3(a, b, c) => Children { nodes: [a, b, c], len: 3 },
+        }
+    }
+
+    fn update<Q: ?Sized + Ord, R, F: FnOnce(&mut V) -> R>(&mut self, key: &Q, f: F) -> Option<R>
+    where
+        K: Borrow<Q>,
+    {
+        let result = match tree_mut(&mut self.tree) {
+            Tree::Leaf(entries) => {
+                let slot = find(entries, key).ok()?;
+                f(&mut entries[slot].1)
+            },
+            _ => {
+                let (i, _) = self.locate(Target::Key(key));
+                self.child_mut(i).update(key, f)?
+            },
+        };
+
+        self.checkpoint = 0;
+        self.refresh();
+        Some(result)
+    }
+
+    // The summary of the entries at positions `start..end` of this
+    // subtree, which must overlap it.
+    fn fold(&self, start: usize, end: usize) -> M::Summary {
+        if start == 0 && end >= self.size {
+            return self.summary.clone();
+        }
+
+        if let Tree::Leaf(entries) = self.tree.as_ref() {
+            return entries[start..end.min(entries.len())]
+                .iter()
+                .fold(M::empty(), |summary, (k, v)| M::combine(&summary, &M::measure(k, v)));
+        }
+
+        let mut summary = M::empty();
+        let mut offset = 0;
+
+        for child in self.children() {
+            if start < offset + child.size && offset < end {
+                let part = child.fold(start.saturating_sub(offset), end - offset);
+                summary = M::combine(&summary, &part);
+            }
+            offset += child.size;
+        }
+
+        summary
+    }
+}
+
+impl<'a, K: Ord, V, M: Measure<K, V>> Iterator for AugmentedIter<'a, K, V, M> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((k, v)) = self.front.next() {
+                self.remaining -= 1;
+                return Some((k, v));
+            }
+
+            let Some(node) = self.nodes.pop_front() else {
+                let (k, v) = self.back.next()?;
+                self.remaining -= 1;
+                return Some((k, v));
+            };
+
+            match node.tree.as_ref() {
+                Tree::Leaf(entries) => self.front = entries.iter(),
+                _ => {
+                    for child in node.children().into_iter().rev() {
+                        self.nodes.push_front(child);
+                    }
+                },
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K: Ord, V, M: Measure<K, V>> DoubleEndedIterator for AugmentedIter<'a, K, V, M> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((k, v)) = self.back.next_back() {
+                self.remaining -= 1;
+                return Some((k, v));
+            }
+
+            let Some(node) = self.nodes.pop_back() else {
+                let (k, v) = self.front.next_back()?;
+                self.remaining -= 1;
+                return Some((k, v));
+            };
+
+            match node.tree.as_ref() {
+                Tree::Leaf(entries) => self.back = entries.iter(),
+                _ => self.nodes.extend(node.children()),
+            }
+        }
+    }
+}
+
+impl<'a, K: Ord, V, M: Measure<K, V>> ExactSizeIterator for AugmentedIter<'a, K, V, M> {}
+
+impl<'a, K: Ord, V, M: Measure<K, V>, F: FnMut(&M::Summary) -> bool> Iterator
+    for AugmentedSearch<'a, K, V, M, F>
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            while let Some(((k, v), rest)) = self.entries.split_first() {
+                let position = self.position;
+                self.entries = rest;
+                self.position += 1;
+
+                if self.start <= position && position < self.end && (self.visit)(&M::measure(k, v)) {
+                    return Some((k, v));
+                }
+            }
+
+            let (node, offset) = self.stack.pop()?;
+
+            if offset >= self.end || offset + node.size <= self.start || !(self.visit)(&node.summary) {
+                continue;
+            }
+
+            match node.tree.as_ref() {
+                Tree::Leaf(entries) => {
+                    self.entries = entries;
+                    self.position = offset;
+                },
+                _ => {
+                    let children = node.children();
+                    let mut end = offset + node.size;
+
+                    for child in children.into_iter().rev() {
+                        end -= child.size;
+                        self.stack.push((child, end));
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl<'a, K: Ord, V, M: Measure<K, V>> Deref for Children<'a, K, V, M> {
+    type Target = [&'a Node<K, V, M>];
+
+    fn deref(&self) -> &Self::Target {
+        &self.nodes[..self.len]
+    }
+}
+
+impl<'a, K: Ord, V, M: Measure<K, V>> IntoIterator for Children<'a, K, V, M> {
+    type Item = &'a Node<K, V, M>;
+    type IntoIter = iter::Take<array::IntoIter<&'a Node<K, V, M>, 3>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.nodes.into_iter().take(self.len)
+    }
+}
@@ -0,0 +1,88 @@
+use std::mem;
+
+use crate::IndexableSortedMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eviction {
+    Smallest,
+    Largest,
+}
+
+/// A map holding at most `capacity` entries. Inserting into a full map
+/// evicts the smallest or largest entry, per the eviction policy, which
+/// makes it a top-N (or bottom-N) tracker.
+pub struct BoundedIndexableSortedMap<K: Ord, V> {
+    map: IndexableSortedMap<K, V>,
+    capacity: usize,
+    eviction: Eviction,
+}
+
+impl<K: Ord, V> BoundedIndexableSortedMap<K, V> {
+    pub fn new(capacity: usize, eviction: Eviction) -> Self {
+        BoundedIndexableSortedMap {
+            map: IndexableSortedMap::new(),
+            capacity,
+            eviction,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn eviction(&self) -> Eviction {
+        self.eviction
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn lookup(&self, key: &K) -> Option<&V> {
+        self.map.lookup(key)
+    }
+
+    pub fn index(&self, i: usize) -> Option<(&K, &V)> {
+        self.map.index(i)
+    }
+
+    pub fn rank(&self, key: &K) -> Result<usize, usize> {
+        self.map.rank(key)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.map.remove(key)
+    }
+
+    /// Returns whichever entry left the map: the previous entry for `key`
+    /// if it was replaced, or the evicted entry if the map was full. The
+    /// evicted entry may be the one just inserted.
+    pub fn insert(&mut self, key: K, value: V) -> Option<(K, V)> {
+        if let Ok(i) = self.map.key_position(&key) {
+            let old = mem::replace(&mut self.map[i], value);
+            return Some((key, old));
+        }
+        self.map.insert(key, value);
+
+        if self.map.len() > self.capacity {
+            match self.eviction {
+                Eviction::Smallest => self.map.pop_first(),
+                Eviction::Largest => self.map.pop_last(),
+            }
+        } else {
+            None
+        }
+    }
+
+    pub fn as_map(&self) -> &IndexableSortedMap<K, V> {
+        &self.map
+    }
+
+    pub fn into_inner(self) -> IndexableSortedMap<K, V> {
+        self.map
+    }
+}
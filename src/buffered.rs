@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+
+use crate::{IndexableSortedMap, SortedMapBuilder};
+
+/// A map for write-heavy bursts. New entries land in a small ordered buffer
+/// and are merged into the tree in bulk once it fills, which is much
+/// cheaper than rebuilding a tree path per insert. As in an LSM tree, a
+/// buffered entry shadows any entry for the same key in the tree until the
+/// flush replaces it. Lookups check the buffer first; counting and
+/// positional queries flush it, so they take `&mut self`.
+pub struct BufferedIndexableSortedMap<K: Ord, V> {
+    map: IndexableSortedMap<K, V>,
+    buffer: BTreeMap<K, V>,
+    buffer_capacity: usize,
+}
+
+impl<K: Ord, V> Default for BufferedIndexableSortedMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> BufferedIndexableSortedMap<K, V> {
+    pub fn new() -> Self {
+        BufferedIndexableSortedMap::with_buffer_capacity(1024)
+    }
+
+    /// Flushes once `buffer_capacity` entries, or as many as the tree holds
+    /// if that is more, are buffered. Letting the buffer keep pace with the
+    /// tree spreads the O(n) merge of each flush over as many inserts.
+    pub fn with_buffer_capacity(buffer_capacity: usize) -> Self {
+        BufferedIndexableSortedMap {
+            map: IndexableSortedMap::new(),
+            buffer: BTreeMap::new(),
+            buffer_capacity: buffer_capacity.max(1),
+        }
+    }
+
+    /// The number of entries. Buffered entries may shadow ones in the
+    /// tree, so this flushes to count them once.
+    pub fn len(&mut self) -> usize {
+        self.flush();
+        self.map.len()
+    }
+
+    pub fn is_empty(&mut self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of entries waiting in the buffer, some of which may
+    /// shadow entries in the tree.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn lookup(&self, key: &K) -> Option<&V> {
+        self.buffer.get(key).or_else(|| self.map.lookup(key))
+    }
+
+    /// Buffers an entry without looking for one it replaces, so the tree
+    /// is only touched when the buffer fills. This is the insert to use
+    /// for sustained writes.
+    pub fn put(&mut self, key: K, value: V) {
+        self.buffer.insert(key, value);
+
+        if self.buffer.len() >= self.buffer_capacity.max(self.map.len()) {
+            self.flush();
+        }
+    }
+
+    /// Inserts an entry, returning the value it displaced. Finding that
+    /// value costs a tree descent whenever the key is not buffered; a key
+    /// already in the tree then has its value replaced there, in place.
+    /// Use `put` when the old value isn't needed.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(old) = self.buffer.get_mut(&key) {
+            return Some(std::mem::replace(old, value));
+        }
+        if let Some(old) = self.map.lookup_mut(&key) {
+            return Some(std::mem::replace(old, value));
+        }
+
+        self.put(key, value);
+        None
+    }
+
+    /// Removes `key` from both the buffer and the tree, returning the
+    /// value that was visible.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let buffered = self.buffer.remove(key);
+        let stored = self.map.remove(key);
+        buffered.or(stored)
+    }
+
+    /// Merges the buffer into the tree, buffered values replacing those
+    /// they shadow.
+    pub fn flush(&mut self) {
+        if self.buffer.is_empty() {
+            return;
+        }
+
+        structural_span!("flush", len = self.map.len(), buffered = self.buffer.len());
+
+        let mut builder = SortedMapBuilder::with_pool(self.map.pool.sibling());
+        for (k, v) in std::mem::take(&mut self.buffer) {
+            builder.push_unchecked(k, v);
+        }
+
+        self.map.append(&mut builder.finish());
+    }
+
+    pub fn index(&mut self, i: usize) -> Option<(&K, &V)> {
+        self.flush();
+        self.map.index(i)
+    }
+
+    pub fn rank(&mut self, key: &K) -> Result<usize, usize> {
+        self.flush();
+        self.map.rank(key)
+    }
+
+    pub fn as_map(&mut self) -> &IndexableSortedMap<K, V> {
+        self.flush();
+        &self.map
+    }
+
+    pub fn into_inner(mut self) -> IndexableSortedMap<K, V> {
+        self.flush();
+        self.map
+    }
+}
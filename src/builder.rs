@@ -0,0 +1,312 @@
+use std::fmt;
+use std::mem;
+
+use crate::{IndexableSortedMap, Node, NodePool, LEAF_CAPACITY};
+
+/// Builds a map from entries pushed in strictly ascending key order, in
+/// O(n) overall. Entries are packed into full leaves as they arrive. Each
+/// level of the tree keeps at most four pending nodes; as soon as a
+/// 3-branch can be closed off it is handed to the level above, so the
+/// builder holds only O(log n) nodes outside finished subtrees.
+///
+/// The result has the same shape as `IndexableSortedMap::canonicalize`
+/// produces.
+pub struct SortedMapBuilder<K: Ord, V> {
+    levels: Vec<Vec<Node<K, V>>>,
+    // The entries of the last leaf, which is only handed to the bottom
+    // level once it is full and another entry arrives.
+    leaf: Vec<(K, V)>,
+    pool: NodePool<K, V>,
+    len: usize,
+}
+
+/// Returned by `SortedMapBuilder::push` when a key is not greater than the
+/// previously pushed one, and by `IndexableSortedMap::insert_before_index`
+/// when a key doesn't belong at the given position, handing back the
+/// rejected entry.
+pub struct OutOfOrder<K, V> {
+    pub key: K,
+    pub value: V,
+}
+
+/// Why an input claimed to be sorted was rejected. `position` is the index
+/// of the first entry whose key is not greater than the one before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortedInputError {
+    Duplicate { position: usize },
+    Descending { position: usize },
+}
+
+impl<K: Ord, V> Default for SortedMapBuilder<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> SortedMapBuilder<K, V> {
+    pub fn new() -> Self {
+        SortedMapBuilder::with_pool(NodePool::new())
+    }
+
+    pub(crate) fn with_pool(pool: NodePool<K, V>) -> Self {
+        SortedMapBuilder {
+            levels: Vec::new(),
+            leaf: Vec::new(),
+            pool,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn last_key(&self) -> Option<&K> {
+        self.leaf.last().map(|(k, _v)| k)
+    }
+
+    pub fn push(&mut self, key: K, value: V) -> Result<(), OutOfOrder<K, V>> {
+        if let Some(last) = self.last_key() {
+            if &key <= last {
+                return Err(OutOfOrder { key, value });
+            }
+        }
+
+        self.push_unchecked(key, value);
+        Ok(())
+    }
+
+    pub(crate) fn push_unchecked(&mut self, key: K, value: V) {
+        if self.leaf.len() == LEAF_CAPACITY {
+            let buffer = self.pool.buffer();
+            let full = mem::replace(&mut self.leaf, buffer);
+            let leaf = self.pool.leaf(full);
+            self.push_node(0, leaf);
+        } else if self.leaf.capacity() == 0 {
+            self.leaf = self.pool.buffer();
+        }
+
+        self.leaf.push((key, value));
+        self.len += 1;
+    }
+
+    fn push_node(&mut self, level: usize, node: Node<K, V>) {
+        if self.levels.len() == level {
+            self.levels.push(Vec::new());
+        }
+
+        self.levels[level].push(node);
+
+        // With five pending, a 3-branch can be closed and still leave
+        // enough behind to form a valid branch however the input ends.
+        if self.levels[level].len() == 5 {
+            let mut pending = self.levels[level].drain(..3);
+            let (a, b, c) = (pending.next().unwrap(), pending.next().unwrap(), pending.next().unwrap());
+            drop(pending);
+
+            let branch = self.pool.branch3(a, b, c);
+            self.push_node(level + 1, branch);
+        }
+    }
+
+    pub fn finish(mut self) -> IndexableSortedMap<K, V> {
+        structural_span!("build", len = self.len);
+
+        if !self.leaf.is_empty() {
+            let leaf = self.pool.leaf(mem::take(&mut self.leaf));
+            self.push_node(0, leaf);
+        }
+
+        let mut level = 0;
+
+        while level < self.levels.len() {
+            let pending = std::mem::take(&mut self.levels[level]);
+            let is_top = level + 1 == self.levels.len();
+
+            if is_top && pending.len() == 1 {
+                self.levels[level] = pending;
+                break;
+            }
+
+            let mut pending = pending.into_iter();
+            let mut remaining = pending.len();
+
+            while remaining > 0 {
+                let a = pending.next().unwrap();
+                let b = pending.next().unwrap();
+
+                let branch = if remaining == 2 || remaining == 4 {
+                    remaining -= 2;
+                    self.pool.branch2(a, b)
+                } else {
+                    remaining -= 3;
+                    let c = pending.next().unwrap();
+                    self.pool.branch3(a, b, c)
+                };
+
+                self.push_node(level + 1, branch);
+            }
+
+            level += 1;
+        }
+
+        IndexableSortedMap {
+            root: self.levels.pop().and_then(|mut top| top.pop()),
+            pool: self.pool,
+            lineage: 0,
+            #[cfg(feature = "heatmap")]
+            heat: Default::default(),
+        }
+    }
+}
+
+impl<K: Ord, V> IndexableSortedMap<K, V> {
+    /// Builds a map bottom-up, in O(n), from entries in strictly ascending
+    /// key order. Fails at the first entry that is out of order.
+    pub fn from_sorted_iter<I: IntoIterator<Item = (K, V)>>(entries: I) -> Result<Self, SortedInputError> {
+        let mut builder = SortedMapBuilder::new();
+
+        for (k, v) in entries {
+            if let Err(OutOfOrder { key, .. }) = builder.push(k, v) {
+                let position = builder.len();
+                return Err(if Some(&key) == builder.last_key() {
+                    SortedInputError::Duplicate { position }
+                } else {
+                    SortedInputError::Descending { position }
+                });
+            }
+        }
+
+        Ok(builder.finish())
+    }
+
+    /// Builds a map from a vector already in strictly ascending key order,
+    /// failing at the first entry that is out of order. This is the
+    /// checked conversion `TryFrom<Vec<(K, V)>>` used to provide; `From`
+    /// now sorts and deduplicates instead.
+    pub fn try_from_sorted_vec(entries: Vec<(K, V)>) -> Result<Self, SortedInputError> {
+        IndexableSortedMap::from_sorted_iter(entries)
+    }
+
+    pub(crate) fn from_unsorted(entries: Vec<(K, V)>) -> Self {
+        IndexableSortedMap::from_unsorted_in(NodePool::new(), entries)
+    }
+
+    // Sorts, keeps the last of any repeated keys as successive inserts
+    // would, and bulk-builds with nodes from `pool`.
+    fn from_unsorted_in(pool: NodePool<K, V>, mut entries: Vec<(K, V)>) -> Self {
+        if !entries.windows(2).all(|pair| pair[0].0 < pair[1].0) {
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+            let mut deduped: Vec<(K, V)> = Vec::with_capacity(entries.len());
+            for entry in entries {
+                match deduped.last_mut() {
+                    Some(last) if last.0 == entry.0 => *last = entry,
+                    _ => deduped.push(entry),
+                }
+            }
+            entries = deduped;
+        }
+
+        let mut builder = SortedMapBuilder::with_pool(pool);
+        for (k, v) in entries {
+            builder.push_unchecked(k, v);
+        }
+        builder.finish()
+    }
+}
+
+impl<K: Ord, V, const N: usize> From<[(K, V); N]> for IndexableSortedMap<K, V> {
+    fn from(entries: [(K, V); N]) -> Self {
+        IndexableSortedMap::from_unsorted(Vec::from(entries))
+    }
+}
+
+/// Builds an `IndexableSortedMap` from `key => value` pairs, in any order.
+/// A repeated key keeps its last value.
+#[macro_export]
+macro_rules! indexable_sorted_map {
+    ($($key:expr => $value:expr),* $(,)?) => {
+        $crate::IndexableSortedMap::from([$(($key, $value)),*])
+    };
+}
+
+/// Sorts the entries, keeping the last value of a repeated key, and
+/// builds the map in bulk. Input that is already sorted is built directly.
+///
+/// This replaces the validating `TryFrom<Vec<(K, V)>>`: `try_from` still
+/// compiles, through the blanket impl, but no longer rejects unsorted or
+/// duplicated input. Use `try_from_sorted_vec` to keep that check.
+impl<K: Ord, V> From<Vec<(K, V)>> for IndexableSortedMap<K, V> {
+    fn from(entries: Vec<(K, V)>) -> Self {
+        IndexableSortedMap::from_unsorted(entries)
+    }
+}
+
+// Nodes are uniquely owned, so a clone copies every entry, bulk-building
+// the copy in O(n). With `cow`, or as a `PersistentIndexableSortedMap`, a
+// clone takes O(1).
+#[cfg(not(feature = "cow"))]
+impl<K: Ord + Clone, V: Clone> Clone for IndexableSortedMap<K, V> {
+    fn clone(&self) -> Self {
+        let mut builder = SortedMapBuilder::with_pool(self.pool.sibling());
+        for (k, v) in self {
+            builder.push_unchecked(k.clone(), v.clone());
+        }
+        builder.finish()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for IndexableSortedMap<K, V> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(entries: I) -> Self {
+        IndexableSortedMap::from_unsorted(entries.into_iter().collect())
+    }
+}
+
+// The new entries are bulk-built into a map of their own, from the same
+// arena if there is one, and merged in, overriding existing values as
+// inserting them one by one would.
+impl<K: Ord, V> Extend<(K, V)> for IndexableSortedMap<K, V> {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, entries: I) {
+        let other = IndexableSortedMap::from_unsorted_in(self.pool.sibling(), entries.into_iter().collect());
+        if other.is_empty() {
+            return;
+        }
+
+        let ours = mem::replace(self, self.empty_like());
+        *self = ours.merge_with(other, |_k, _ours, theirs| theirs);
+    }
+}
+
+impl<K, V> fmt::Debug for OutOfOrder<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OutOfOrder").finish_non_exhaustive()
+    }
+}
+
+impl<K, V> fmt::Display for OutOfOrder<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "key is not greater than the previously pushed key")
+    }
+}
+
+impl<K, V> std::error::Error for OutOfOrder<K, V> {}
+
+impl fmt::Display for SortedInputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SortedInputError::Duplicate { position } => {
+                write!(f, "the key at position {} duplicates the previous key", position)
+            },
+            SortedInputError::Descending { position } => {
+                write!(f, "the key at position {} is less than the previous key", position)
+            },
+        }
+    }
+}
+
+impl std::error::Error for SortedInputError {}
@@ -0,0 +1,109 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{tree_mut, IndexableSortedMap, Node, NodePool, Tree};
+
+// Ids are unique across the process, so a sink shared by several maps, or
+// one fed again after a map is rebuilt, never sees an id reused for a
+// different subtree. Lineages are drawn from the same counter.
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Receives the records of an incremental checkpoint. Each record describes
+/// one subtree under a fresh id; children are always written before their
+/// parent and may refer to subtrees written by earlier checkpoints. The
+/// map's contents are the in-order leaves reachable from the last `root`.
+/// Ids no longer reachable from it can be discarded. Each map's
+/// checkpoints should go to a sink of its own; subtrees it takes from
+/// another map, as with `append` or `split_off`, are written out in full
+/// the first time. Entries are packed several to a leaf of the tree, and
+/// such a leaf is written as a branch over one record per entry.
+pub trait CheckpointSink<K, V> {
+    type Error;
+
+    fn leaf(&mut self, id: u64, key: &K, value: &V) -> Result<(), Self::Error>;
+    fn branch(&mut self, id: u64, children: &[u64]) -> Result<(), Self::Error>;
+    fn root(&mut self, root: Option<u64>) -> Result<(), Self::Error>;
+}
+
+impl<K: Ord, V> IndexableSortedMap<K, V> {
+    /// Writes every subtree built or modified since the last checkpoint,
+    /// referring to the rest by the ids they were written under. The first
+    /// checkpoint writes the whole tree; later ones write O(k log n)
+    /// records for k changed entries.
+    pub fn checkpoint<S: CheckpointSink<K, V>>(&mut self, sink: &mut S) -> Result<(), S::Error> {
+        if self.lineage == 0 {
+            self.lineage = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let root = match &mut self.root {
+            None => None,
+            Some(node) => Some(write(node, &mut self.pool, self.lineage, sink)?),
+        };
+
+        sink.root(root)
+    }
+
+    // Makes `self`, built from the pieces of a map with `lineage` and
+    // others, carry on that map's checkpoints.
+    pub(crate) fn continuing(mut self, lineage: u64) -> Self {
+        self.lineage = lineage;
+        self
+    }
+}
+
+// The pool is only called on with `cow`, to copy shared branches.
+#[cfg_attr(not(feature = "cow"), allow(clippy::only_used_in_recursion))]
+fn write<K: Ord, V, S: CheckpointSink<K, V>>(
+    node: &mut Node<K, V>,
+    pool: &mut NodePool<K, V>,
+    lineage: u64,
+    sink: &mut S,
+) -> Result<u64, S::Error> {
+    if node.checkpoint != 0 && node.lineage == lineage {
+        return Ok(node.checkpoint);
+    }
+
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+    if let Tree::Leaf(entries) = node.tree.as_ref() {
+        if let [(k, v)] = entries.as_slice() {
+            sink.leaf(id, k, v)?;
+        } else {
+            let mut children = Vec::with_capacity(entries.len());
+            for (k, v) in entries.iter() {
+                let entry_id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+                sink.leaf(entry_id, k, v)?;
+                children.push(entry_id);
+            }
+            sink.branch(id, &children)?;
+        }
+
+        node.checkpoint = id;
+        node.lineage = lineage;
+        return Ok(id);
+    }
+
+    // The branch records its children's ids in them, so with `cow` it must
+    // be its own. A leaf is only read.
+    #[cfg(feature = "cow")]
+    pool.unshare(node);
+
+    match tree_mut(&mut node.tree) {
+        Tree::Leaf(_) => unreachable!(),
+        Tree::Branch2(left, right) => {
+            let children = [write(left, pool, lineage, sink)?, write(right, pool, lineage, sink)?];
+            sink.branch(id, &children)?;
+        },
+        Tree::Branch3(left, middle, right) => {
+            let children = [
+                write(left, pool, lineage, sink)?,
+                write(middle, pool, lineage, sink)?,
+                write(right, pool, lineage, sink)?,
+            ];
+            sink.branch(id, &children)?;
+        },
+    }
+
+    node.checkpoint = id;
+    node.lineage = lineage;
+    Ok(id)
+}
@@ -0,0 +1,157 @@
+use std::cmp::Ordering;
+use std::marker::PhantomData;
+use std::ops::RangeBounds;
+
+use crate::{IndexableSortedMap, Range};
+
+/// An ordering on keys other than their own `Ord`, such as case-insensitive
+/// ordering of strings or ordering by one field of a struct. Comparators
+/// are types rather than values, usually unit structs, so that every map
+/// using one agrees on the order.
+///
+/// `compare` must be a total order, as `Ord::cmp` is. The map does not
+/// misbehave unsafely if it isn't, but lookups may then miss entries.
+pub trait Comparator<K: ?Sized> {
+    fn compare(a: &K, b: &K) -> Ordering;
+}
+
+/// A map whose keys are ordered by the comparator `C` rather than by
+/// their own `Ord`, which they needn't implement. Two keys that `C`
+/// considers equal are the same key, so inserting one replaces the entry
+/// under the other, keeping the original key.
+pub struct ComparatorIndexableSortedMap<K, V, C: Comparator<K>> {
+    map: IndexableSortedMap<OrderedBy<K, C>, V>,
+}
+
+// A key ordered by `C`. Being transparent, a `&K` can be viewed as one to
+// look it up without moving or copying it.
+#[repr(transparent)]
+struct OrderedBy<K, C> {
+    key: K,
+    order: PhantomData<fn() -> C>,
+}
+
+pub struct ComparatorIter<'a, K, V, C: Comparator<K>> {
+    range: Range<'a, OrderedBy<K, C>, V>,
+}
+
+impl<K, C: Comparator<K>> OrderedBy<K, C> {
+    fn new(key: K) -> Self {
+        OrderedBy { key, order: PhantomData }
+    }
+
+    fn from_ref(key: &K) -> &Self {
+        // SAFETY: `OrderedBy` is `repr(transparent)` over `K`, its only
+        // other field being zero-sized, so the two share a layout.
+        unsafe { &*(key as *const K as *const Self) }
+    }
+}
+
+impl<K, C: Comparator<K>> PartialEq for OrderedBy<K, C> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<K, C: Comparator<K>> Eq for OrderedBy<K, C> {}
+
+impl<K, C: Comparator<K>> PartialOrd for OrderedBy<K, C> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K, C: Comparator<K>> Ord for OrderedBy<K, C> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        C::compare(&self.key, &other.key)
+    }
+}
+
+impl<K, V, C: Comparator<K>> Default for ComparatorIndexableSortedMap<K, V, C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, C: Comparator<K>> ComparatorIndexableSortedMap<K, V, C> {
+    pub fn new() -> Self {
+        ComparatorIndexableSortedMap { map: IndexableSortedMap::new() }
+    }
+
+    /// An empty map ordered by `cmp`, for naming the comparator by value:
+    /// `ComparatorIndexableSortedMap::new_with_cmp(CaseInsensitive)`.
+    pub fn new_with_cmp(cmp: C) -> Self {
+        let _ = cmp;
+        Self::new()
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn lookup(&self, key: &K) -> Option<&V> {
+        self.map.lookup(OrderedBy::from_ref(key))
+    }
+
+    pub fn lookup_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.map.lookup_mut(OrderedBy::from_ref(key))
+    }
+
+    pub fn rank(&self, key: &K) -> Result<usize, usize> {
+        self.map.rank(OrderedBy::from_ref(key))
+    }
+
+    pub fn index(&self, i: usize) -> Option<(&K, &V)> {
+        self.map.index(i).map(|(k, v)| (&k.key, v))
+    }
+
+    /// Inserts an entry, returning the value it displaced if a key equal
+    /// to `key` under `C` was already present.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.map.insert(OrderedBy::new(key), value)
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.map.remove(OrderedBy::from_ref(key))
+    }
+
+    pub fn remove_at(&mut self, i: usize) -> Option<(K, V)> {
+        self.map.remove_at(i).map(|(k, v)| (k.key, v))
+    }
+
+    pub fn iter(&self) -> ComparatorIter<'_, K, V, C> {
+        ComparatorIter { range: self.map.range_by_index(..) }
+    }
+
+    /// Iterates over the entries whose keys fall within `range` under `C`.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> ComparatorIter<'_, K, V, C> {
+        let start = range.start_bound().map(OrderedBy::from_ref);
+        let end = range.end_bound().map(OrderedBy::from_ref);
+        ComparatorIter { range: self.map.range((start, end)) }
+    }
+}
+
+impl<'a, K, V, C: Comparator<K>> Iterator for ComparatorIter<'a, K, V, C> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.range.next().map(|(k, v)| (&k.key, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<'a, K, V, C: Comparator<K>> DoubleEndedIterator for ComparatorIter<'a, K, V, C> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.range.next_back().map(|(k, v)| (&k.key, v))
+    }
+}
+
+impl<'a, K, V, C: Comparator<K>> ExactSizeIterator for ComparatorIter<'a, K, V, C> {}
+
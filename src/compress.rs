@@ -0,0 +1,306 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::{IndexableSortedMap, SortedMapBuilder};
+
+// The encoding is a magic number and entry count, then a run of frames,
+// each holding up to `FRAME_ENTRIES` bincode-encoded entries compressed as
+// one LZ4 block, and finally an empty frame. Frames are independent, so a
+// reader only ever holds one decompressed frame while feeding the builder.
+const MAGIC: &[u8; 4] = b"ISM1";
+const FRAME_ENTRIES: usize = 4096;
+
+// LZ4 expands a block by at most this factor, so a frame whose size prefix
+// claims more than this multiple of its compressed size is corrupt.
+const MAX_EXPANSION: usize = 255;
+
+/// A problem `read_compressed_lenient` recovered from. Frames are counted
+/// from 0 and positions are those of entries in the encoded sequence.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeIssue {
+    /// The frame could not be decompressed or decoded; `recovered` of its
+    /// `entries` were decoded before the damage and kept.
+    CorruptFrame { frame: usize, entries: u32, recovered: u32 },
+    /// The entry's key was not greater than the one before, so it was
+    /// dropped.
+    OutOfOrder { position: u64 },
+    /// The input ended before the closing frame, in or before `frame`.
+    Truncated { frame: usize },
+    /// The header's entry count disagrees with the entries recovered.
+    LengthMismatch { expected: u64, recovered: u64 },
+}
+
+/// Why a map could not be encoded or decoded.
+#[derive(Debug)]
+pub enum CodecError {
+    Io(io::Error),
+    Encode(bincode::Error),
+    Decompress(lz4_flex::block::DecompressError),
+    Corrupt,
+}
+
+impl<K: Ord + Serialize, V: Serialize> IndexableSortedMap<K, V> {
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CodecError> {
+        let mut bytes = Vec::new();
+        self.write_compressed(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    pub fn write_compressed<W: Write>(&self, mut writer: W) -> Result<(), CodecError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&(self.len() as u64).to_le_bytes())?;
+
+        let mut entries = self.iter();
+        let mut frame = Vec::new();
+
+        loop {
+            frame.clear();
+            let mut count: u32 = 0;
+
+            for (k, v) in entries.by_ref().take(FRAME_ENTRIES) {
+                bincode::serialize_into(&mut frame, &(k, v))?;
+                count += 1;
+            }
+
+            if count == 0 {
+                break;
+            }
+
+            let compressed = lz4_flex::block::compress_prepend_size(&frame);
+            writer.write_all(&(compressed.len() as u32).to_le_bytes())?;
+            writer.write_all(&count.to_le_bytes())?;
+            writer.write_all(&compressed)?;
+        }
+
+        writer.write_all(&0u32.to_le_bytes())?;
+        Ok(())
+    }
+}
+
+impl<K: Ord + DeserializeOwned, V: DeserializeOwned> IndexableSortedMap<K, V> {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, CodecError> {
+        Self::read_compressed(bytes)
+    }
+
+    pub fn read_compressed<R: Read>(mut reader: R) -> Result<Self, CodecError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(CodecError::Corrupt);
+        }
+
+        let mut len = [0; 8];
+        reader.read_exact(&mut len)?;
+        let len = u64::from_le_bytes(len);
+
+        let mut builder = SortedMapBuilder::new();
+        let mut compressed = Vec::new();
+
+        loop {
+            let mut header = [0; 4];
+            reader.read_exact(&mut header)?;
+            let size = u32::from_le_bytes(header) as usize;
+
+            if size == 0 {
+                break;
+            }
+
+            reader.read_exact(&mut header)?;
+            let count = u32::from_le_bytes(header);
+
+            read_frame(&mut reader, size, &mut compressed)?;
+            let frame = decompress_frame(&compressed)?;
+
+            let mut frame = frame.as_slice();
+            for _ in 0..count {
+                let (k, v) = decode_entry::<K, V>(&mut frame).map_err(|_| CodecError::Corrupt)?;
+                if builder.push(k, v).is_err() {
+                    return Err(CodecError::Corrupt);
+                }
+            }
+
+            if !frame.is_empty() {
+                return Err(CodecError::Corrupt);
+            }
+        }
+
+        if builder.len() as u64 != len {
+            return Err(CodecError::Corrupt);
+        }
+
+        Ok(builder.finish())
+    }
+
+    pub fn from_bytes_lenient(bytes: &[u8]) -> Result<(Self, Vec<DecodeIssue>), CodecError> {
+        Self::read_compressed_lenient(bytes)
+    }
+
+    /// Like `read_compressed`, but recovers what it can from damaged input,
+    /// such as a partially written file: corrupt frames are skipped,
+    /// out-of-order entries dropped and a truncated tail ignored, each
+    /// reported as an issue alongside the recovered map. Only input that
+    /// does not start with a valid header is an error.
+    pub fn read_compressed_lenient<R: Read>(mut reader: R) -> Result<(Self, Vec<DecodeIssue>), CodecError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(CodecError::Corrupt);
+        }
+
+        let mut len = [0; 8];
+        reader.read_exact(&mut len)?;
+        let len = u64::from_le_bytes(len);
+
+        let mut builder = SortedMapBuilder::new();
+        let mut issues = Vec::new();
+        let mut compressed = Vec::new();
+        let mut position = 0;
+
+        for frame_index in 0.. {
+            let mut header = [0; 8];
+            if reader.read_exact(&mut header[..4]).is_err() {
+                issues.push(DecodeIssue::Truncated { frame: frame_index });
+                break;
+            }
+
+            let size = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+            if size == 0 {
+                break;
+            }
+
+            if reader.read_exact(&mut header[4..]).is_err() || read_frame(&mut reader, size, &mut compressed).is_err() {
+                issues.push(DecodeIssue::Truncated { frame: frame_index });
+                break;
+            }
+
+            let count = u32::from_le_bytes(header[4..].try_into().unwrap());
+            let frame = decompress_frame(&compressed).unwrap_or_default();
+            let mut frame = frame.as_slice();
+            let mut recovered = 0;
+
+            while recovered < count {
+                let Ok((k, v)) = decode_entry::<K, V>(&mut frame) else {
+                    break;
+                };
+
+                if builder.push(k, v).is_err() {
+                    issues.push(DecodeIssue::OutOfOrder { position });
+                }
+
+                position += 1;
+                recovered += 1;
+            }
+
+            if recovered < count || !frame.is_empty() {
+                issues.push(DecodeIssue::CorruptFrame { frame: frame_index, entries: count, recovered });
+                position += u64::from(count - recovered);
+            }
+        }
+
+        if builder.len() as u64 != len {
+            issues.push(DecodeIssue::LengthMismatch { expected: len, recovered: builder.len() as u64 });
+        }
+
+        Ok((builder.finish(), issues))
+    }
+}
+
+// Reads a frame of `size` bytes into `buffer`, growing it only as data
+// arrives so that a corrupt size cannot force a huge allocation up front.
+fn read_frame<R: Read>(reader: &mut R, size: usize, buffer: &mut Vec<u8>) -> io::Result<()> {
+    buffer.clear();
+    reader.take(size as u64).read_to_end(buffer)?;
+
+    if buffer.len() < size {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    Ok(())
+}
+
+// Decompresses a frame, checking its size prefix first so that a corrupt
+// one cannot force a huge allocation.
+fn decompress_frame(compressed: &[u8]) -> Result<Vec<u8>, CodecError> {
+    let Some(prefix) = compressed.get(..4) else {
+        return Err(CodecError::Corrupt);
+    };
+
+    let size = u32::from_le_bytes(prefix.try_into().unwrap()) as usize;
+    if size > compressed.len().saturating_mul(MAX_EXPANSION) {
+        return Err(CodecError::Corrupt);
+    }
+
+    Ok(lz4_flex::block::decompress_size_prepended(compressed)?)
+}
+
+// Decodes the next entry of a decompressed frame, as `bincode::deserialize`
+// would but limited to what is left of the frame, so that a corrupt length
+// inside an entry is an error rather than an allocation of that size.
+fn decode_entry<K: DeserializeOwned, V: DeserializeOwned>(frame: &mut &[u8]) -> bincode::Result<(K, V)> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(frame.len() as u64)
+        .deserialize_from(frame)
+}
+
+impl fmt::Display for DecodeIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeIssue::CorruptFrame { frame, entries, recovered } => {
+                write!(f, "frame {} is corrupt; recovered {} of its {} entries", frame, recovered, entries)
+            },
+            DecodeIssue::OutOfOrder { position } => {
+                write!(f, "the entry at position {} is out of order", position)
+            },
+            DecodeIssue::Truncated { frame } => write!(f, "the input is truncated at frame {}", frame),
+            DecodeIssue::LengthMismatch { expected, recovered } => {
+                write!(f, "expected {} entries but recovered {}", expected, recovered)
+            },
+        }
+    }
+}
+
+impl From<io::Error> for CodecError {
+    fn from(e: io::Error) -> Self {
+        CodecError::Io(e)
+    }
+}
+
+impl From<bincode::Error> for CodecError {
+    fn from(e: bincode::Error) -> Self {
+        CodecError::Encode(e)
+    }
+}
+
+impl From<lz4_flex::block::DecompressError> for CodecError {
+    fn from(e: lz4_flex::block::DecompressError) -> Self {
+        CodecError::Decompress(e)
+    }
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Io(e) => write!(f, "i/o error: {}", e),
+            CodecError::Encode(e) => write!(f, "encoding error: {}", e),
+            CodecError::Decompress(e) => write!(f, "decompression error: {}", e),
+            CodecError::Corrupt => write!(f, "input is not a valid encoded map"),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CodecError::Io(e) => Some(e),
+            CodecError::Encode(e) => Some(e),
+            CodecError::Decompress(e) => Some(e),
+            CodecError::Corrupt => None,
+        }
+    }
+}
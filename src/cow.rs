@@ -0,0 +1,136 @@
+use std::borrow::Borrow;
+use std::sync::Arc;
+
+use crate::{tree_mut, IndexableSortedMap, Measure, Node, NodePool, Target, Tree};
+
+// Copies a leaf's entries into an empty vector.
+pub(crate) type Cloner<K, V> = fn(&[(K, V)], &mut Vec<(K, V)>);
+
+fn clone_entries<K: Clone, V: Clone>(entries: &[(K, V)], copy: &mut Vec<(K, V)>) {
+    copy.extend_from_slice(entries);
+}
+
+// A clone shares the whole tree with the original, so it takes O(1).
+// Afterwards each map copies a node before editing it if the other still
+// holds it, along with the path down to it, so an edit costs O(log n)
+// copies at most and the two maps only ever diverge by what was edited.
+// The clones may go their separate ways across threads, so as with an
+// `Arc` the entries must be `Send` and `Sync`.
+impl<K: Ord + Clone + Send + Sync, V: Clone + Send + Sync> Clone for IndexableSortedMap<K, V> {
+    fn clone(&self) -> Self {
+        self.pool.cloner.get_or_init(|| clone_entries);
+
+        let mut map = IndexableSortedMap::with_pool(self.pool.sibling());
+        map.root = self.root.as_ref().map(Node::share);
+        map
+    }
+}
+
+impl<K: Ord, V> IndexableSortedMap<K, V> {
+    // Gives every node on the path to `target` a tree of its own, so that
+    // the path can be edited in place.
+    pub(crate) fn unshare_path<Q: ?Sized + Ord>(&mut self, target: Target<'_, Q>)
+    where
+        K: Borrow<Q>,
+    {
+        if let Some(root) = &mut self.root {
+            root.unshare_path(&mut self.pool, target);
+        }
+    }
+
+    // Gives every node a tree of its own, for edits that may reach any
+    // entry.
+    pub(crate) fn unshare_all(&mut self) {
+        if let Some(root) = &mut self.root {
+            root.unshare_all(&mut self.pool);
+        }
+    }
+}
+
+impl<K: Ord, V, M: Measure<K, V>> Node<K, V, M> {
+    // Another handle on this node's tree.
+    fn share(&self) -> Self {
+        Node {
+            first: self.first,
+            size: self.size,
+            summary: self.summary.clone(),
+            checkpoint: self.checkpoint,
+            lineage: self.lineage,
+            tree: Arc::clone(&self.tree),
+        }
+    }
+
+    // Copying a leaf moves its first entry, so each node is refreshed once
+    // the path below it is its own.
+    fn unshare_path<Q: ?Sized + Ord>(&mut self, pool: &mut NodePool<K, V, M>, target: Target<'_, Q>)
+    where
+        K: Borrow<Q>,
+    {
+        pool.unshare(self);
+        if let Tree::Leaf(_) = *self.tree {
+            return;
+        }
+
+        let (i, target) = self.locate(target);
+        self.child_mut(i).unshare_path(pool, target);
+        self.refresh();
+    }
+
+    fn unshare_all(&mut self, pool: &mut NodePool<K, V, M>) {
+        pool.unshare(self);
+
+        match tree_mut(&mut self.tree) {
+            Tree::Leaf(_) => return,
+            Tree::Branch2(left, right) => {
+                left.unshare_all(pool);
+                right.unshare_all(pool);
+            },
+            Tree::Branch3(left, middle, right) => {
+                left.unshare_all(pool);
+                middle.unshare_all(pool);
+                right.unshare_all(pool);
+            },
+        }
+
+        self.refresh();
+    }
+}
+
+impl<K: Ord, V, M: Measure<K, V>> NodePool<K, V, M> {
+    // A copy of a tree that a clone still holds. A branch's copy shares
+    // its children with the original.
+    pub(crate) fn copy(&mut self, tree: &Tree<K, V, M>) -> Tree<K, V, M> {
+        match tree {
+            Tree::Leaf(entries) => {
+                let clone = *self.cloner.get().expect("only a cloned map shares its leaves");
+                let mut copy = self.buffer();
+                clone(entries, &mut copy);
+                Tree::Leaf(copy)
+            },
+            Tree::Branch2(left, right) => Tree::Branch2(left.share(), right.share()),
+            Tree::Branch3(left, middle, right) => Tree::Branch3(left.share(), middle.share(), right.share()),
+        }
+    }
+
+    // Gives `node` a tree of its own if a clone still holds its current
+    // one. Returns whether that copied a leaf, which moves the first entry
+    // of the nodes above it that it is leftmost under.
+    pub(crate) fn unshare(&mut self, node: &mut Node<K, V, M>) -> bool {
+        if Arc::get_mut(&mut node.tree).is_some() {
+            return false;
+        }
+
+        let tree = self.copy(&node.tree);
+        node.tree = self.alloc(tree);
+        node.refresh();
+        matches!(*node.tree, Tree::Leaf(_))
+    }
+
+    // Takes on `other`'s way of copying leaves, for a map that takes over
+    // its nodes.
+    pub(crate) fn adopt(&mut self, other: &Self) {
+        if let Some(&clone) = other.cloner.get() {
+            let _ = self.cloner.set(clone);
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use std::borrow::Borrow;
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+use crate::{find, tree_mut, IndexableSortedMap, Node, OutOfOrder, Target, Tree, TreeZipper, LEAF_CAPACITY};
+
+/// A zipper position detached from the map, small enough to hand to a
+/// client between requests. `rank` is the entry's index when the token was
+/// taken; resuming goes by `key`, so the scan picks up in the right place
+/// even if entries were inserted or removed in the meantime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CursorToken<K> {
+    pub key: K,
+    pub rank: usize,
+}
+
+impl<'a, K: Ord + Clone, V> TreeZipper<'a, K, V> {
+    pub fn token(&self) -> CursorToken<K> {
+        CursorToken {
+            key: self.focused().0.clone(),
+            rank: self.position(),
+        }
+    }
+}
+
+impl<K: Ord, V> IndexableSortedMap<K, V> {
+    /// Re-establishes a zipper at the token's key or, if that entry has
+    /// since been removed, at the first entry after it. Returns `None` if
+    /// no such entry remains.
+    pub fn resume_at(&self, token: &CursorToken<K>) -> Option<TreeZipper<'_, K, V>> {
+        self.zipper()?.advance_to(&token.key)
+    }
+}
+
+/// A position in a map that can be moved in either direction and can edit
+/// the map around it: change the focused value, remove the focused entry or
+/// insert entries on either side of it. Like a `TreeZipper`, the cursor
+/// keeps the path down to its leaf, so reading the focus is O(1) and moving
+/// is amortized O(1). Edits that stay within the focused leaf are made in
+/// place, adjusting the nodes along the path; only those that split or
+/// merge a node go through the map and descend again from the root. One
+/// past the last entry is a valid position with no focus.
+pub struct CursorMut<'a, K: Ord, V> {
+    // Held as a pointer rather than a `&mut`, which would be reasserted
+    // over the whole map, root node included, each time the cursor moves.
+    map: NonNull<IndexableSortedMap<K, V>>,
+    _map: PhantomData<&'a mut IndexableSortedMap<K, V>>,
+    position: usize,
+    // The branches above the focused leaf, each with the index of the
+    // child the path takes. These point into `map`, which the cursor
+    // borrows exclusively, so they stay valid until the cursor edits the
+    // map through its own methods, after which the path is rebuilt.
+    path: Vec<(NonNull<Node<K, V>>, usize)>,
+    // The focused leaf, which is `None` exactly when the cursor is past the
+    // end, and the focus's slot in it.
+    leaf: Option<NonNull<Node<K, V>>>,
+    slot: usize,
+}
+
+// SAFETY: the pointers only lead into the map the cursor borrows mutably,
+// so the cursor is as safe to send or share as that borrow.
+unsafe impl<K: Ord + Send, V: Send> Send for CursorMut<'_, K, V> {}
+unsafe impl<K: Ord + Sync, V: Sync> Sync for CursorMut<'_, K, V> {}
+
+impl<K: Ord, V> IndexableSortedMap<K, V> {
+    /// A cursor at the first entry.
+    pub fn cursor_mut(&mut self) -> CursorMut<'_, K, V> {
+        self.cursor_mut_at(0)
+    }
+
+    /// A cursor at position `i`, clamped to one past the last entry.
+    pub fn cursor_mut_at(&mut self, i: usize) -> CursorMut<'_, K, V> {
+        let mut cursor = CursorMut {
+            map: NonNull::from(self),
+            _map: PhantomData,
+            position: 0,
+            path: Vec::new(),
+            leaf: None,
+            slot: 0,
+        };
+        cursor.seek(Target::<K>::Index(i));
+        cursor
+    }
+}
+
+impl<'a, K: Ord, V> CursorMut<'a, K, V> {
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn focus(&self) -> Option<(&K, &V)> {
+        self.leaf?;
+        let (k, v) = &self.entries()[self.slot];
+        Some((k, v))
+    }
+
+    pub fn focus_mut(&mut self) -> Option<(&K, &mut V)> {
+        let leaf = self.leaf?;
+        self.touch(0);
+
+        // SAFETY: the leaf is in the map the cursor borrows mutably, and
+        // the returned borrow holds the cursor until it ends.
+        let leaf = unsafe { &mut *leaf.as_ptr() };
+        let Tree::Leaf(entries) = tree_mut(&mut leaf.tree) else { unreachable!() };
+        let (k, v) = &mut entries[self.slot];
+        Some((k, v))
+    }
+
+    pub fn key(&self) -> Option<&K> {
+        self.focus().map(|(k, _v)| k)
+    }
+
+    pub fn value_mut(&mut self) -> Option<&mut V> {
+        self.focus_mut().map(|(_k, v)| v)
+    }
+
+    /// Moves to the next entry, or one past the last. Returns false if the
+    /// cursor was already past the end.
+    pub fn move_next(&mut self) -> bool {
+        if self.leaf.is_none() {
+            return false;
+        }
+
+        self.position += 1;
+        if self.slot + 1 < self.entries().len() {
+            self.slot += 1;
+        } else {
+            self.next_leaf();
+        }
+        true
+    }
+
+    /// Moves to the previous entry. Returns false if the cursor was already
+    /// at the first.
+    pub fn move_prev(&mut self) -> bool {
+        if self.position == 0 {
+            return false;
+        }
+
+        if self.leaf.is_none() {
+            self.seek(Target::<K>::Index(self.position - 1));
+        } else {
+            self.position -= 1;
+            if self.slot > 0 {
+                self.slot -= 1;
+            } else {
+                self.prev_leaf();
+            }
+        }
+        true
+    }
+
+    /// Moves to the first entry with a key of at least `key`.
+    pub fn move_to<Q: ?Sized + Ord>(&mut self, key: &Q)
+    where
+        K: Borrow<Q>,
+    {
+        self.seek(Target::Key(key));
+    }
+
+    /// Inserts an entry just before the focus, which stays where it was.
+    /// Fails, handing the entry back, if `key` doesn't belong there.
+    pub fn insert_before(&mut self, key: K, value: V) -> Result<(), OutOfOrder<K, V>> {
+        if self.leaf.is_some() && self.slot > 0 && self.entries().len() < LEAF_CAPACITY {
+            let entries = self.entries();
+            if !(entries[self.slot - 1].0 < key && key < entries[self.slot].0) {
+                return Err(OutOfOrder { key, value });
+            }
+
+            self.edit_leaf(|entries, slot| entries.insert(slot, (key, value)));
+            self.touch(1);
+            self.slot += 1;
+            self.position += 1;
+            return Ok(());
+        }
+
+        let position = self.position;
+        let result = self.map_mut().insert_before_index(position, key, value);
+        self.seek(Target::<K>::Index(position + usize::from(result.is_ok())));
+        result
+    }
+
+    /// Inserts an entry just after the focus, which stays where it was.
+    /// Fails, handing the entry back, if `key` doesn't belong there or the
+    /// cursor is past the end.
+    pub fn insert_after(&mut self, key: K, value: V) -> Result<(), OutOfOrder<K, V>> {
+        if self.leaf.is_none() {
+            return Err(OutOfOrder { key, value });
+        }
+
+        let entries = self.entries();
+        if self.slot + 1 < entries.len() && entries.len() < LEAF_CAPACITY {
+            if !(entries[self.slot].0 < key && key < entries[self.slot + 1].0) {
+                return Err(OutOfOrder { key, value });
+            }
+
+            self.edit_leaf(|entries, slot| entries.insert(slot + 1, (key, value)));
+            self.touch(1);
+            return Ok(());
+        }
+
+        let position = self.position;
+        let result = self.map_mut().insert_before_index(position + 1, key, value);
+        self.seek(Target::<K>::Index(position));
+        result
+    }
+
+    /// Removes the focused entry, moving the focus to the one after it.
+    pub fn remove_current(&mut self) -> Option<(K, V)> {
+        self.leaf?;
+
+        // A leaf left at least a quarter full is one the map would leave
+        // as it is.
+        if self.entries().len() > LEAF_CAPACITY / 4 {
+            let entry = self.edit_leaf(|entries, slot| entries.remove(slot));
+            self.touch(-1);
+            if self.slot == self.entries().len() {
+                self.next_leaf();
+            }
+            return Some(entry);
+        }
+
+        let position = self.position;
+        let entry = self.map_mut().remove_at(position);
+        self.seek(Target::<K>::Index(self.position));
+        entry
+    }
+
+    pub fn as_map(&self) -> &IndexableSortedMap<K, V> {
+        // SAFETY: the cursor borrows the map for `'a`, and `&self` rules
+        // out edits while the map is borrowed.
+        unsafe { self.map.as_ref() }
+    }
+
+    // The map, for edits that may reshape the tree. The path is stale
+    // afterwards, whether or not the edit went ahead, until the caller
+    // seeks again.
+    fn map_mut(&mut self) -> &mut IndexableSortedMap<K, V> {
+        // SAFETY: as in `as_map`, with `&mut self` ruling out any other
+        // borrow.
+        unsafe { self.map.as_mut() }
+    }
+
+    fn entries(&self) -> &[(K, V)] {
+        let Some(leaf) = self.leaf else {
+            return &[];
+        };
+
+        // SAFETY: the leaf is in the map the cursor borrows, and `&self`
+        // rules out edits while the entries are borrowed.
+        match unsafe { leaf.as_ref() }.tree.as_ref() {
+            Tree::Leaf(entries) => entries,
+            _ => unreachable!(),
+        }
+    }
+
+    // Applies `f` to the focused leaf's entries and its slot. `f` must
+    // leave the leaf with between one and `LEAF_CAPACITY` entries.
+    fn edit_leaf<R>(&mut self, f: impl FnOnce(&mut Vec<(K, V)>, usize) -> R) -> R {
+        let leaf = self.leaf.expect("the cursor is past the end");
+
+        // SAFETY: as in `focus_mut`; nothing else refers to the leaf.
+        let leaf = unsafe { &mut *leaf.as_ptr() };
+        let Tree::Leaf(entries) = tree_mut(&mut leaf.tree) else { unreachable!() };
+        f(entries, self.slot)
+    }
+
+    // Brings the path up to date after the focused leaf changed length by
+    // `delta` entries: each node on it has its size adjusted, its
+    // checkpoint cleared and its first entry refreshed, as the map's own
+    // edits do.
+    fn touch(&mut self, delta: isize) {
+        let leaf = self.leaf.expect("the cursor is past the end");
+
+        for node in self.path.iter().map(|(node, _)| *node).chain([leaf]).rev() {
+            // SAFETY: the path leads down from the map's root to the leaf,
+            // and the cursor borrows the map mutably.
+            let node = unsafe { &mut *node.as_ptr() };
+            node.size = node.size.checked_add_signed(delta).unwrap();
+            node.checkpoint = 0;
+            node.refresh();
+        }
+    }
+
+    // Rebuilds the path from the root towards `target`, which is clamped
+    // to one past the last entry.
+    fn seek<Q: ?Sized + Ord>(&mut self, target: Target<'_, Q>)
+    where
+        K: Borrow<Q>,
+    {
+        self.path.clear();
+        self.leaf = None;
+        self.slot = 0;
+        self.position = self.as_map().len();
+
+        if matches!(target, Target::Index(i) if i >= self.position) {
+            return;
+        }
+        // With `cow`, whatever a clone still holds on the way down is
+        // copied first, so that the path can be edited through.
+        #[cfg(feature = "cow")]
+        self.map_mut().unshare_path(target);
+
+        let Some(root) = self.map_mut().root.as_mut() else {
+            return;
+        };
+
+        let mut node = NonNull::from(root);
+        let mut target = target;
+        let mut offset = 0;
+
+        loop {
+            // SAFETY: `node` was reached from the root just now.
+            let current = unsafe { &mut *node.as_ptr() };
+
+            if let Tree::Leaf(entries) = current.tree.as_ref() {
+                self.slot = match target {
+                    Target::Key(key) => match find(entries, key) {
+                        Ok(slot) | Err(slot) => slot,
+                    },
+                    Target::Index(i) => i,
+                };
+                self.position = offset + self.slot;
+                self.leaf = Some(node);
+
+                // Every key in the leaf is less than the one sought.
+                if self.slot == entries.len() {
+                    self.next_leaf();
+                }
+                return;
+            }
+
+            let (i, within) = current.locate(target);
+            offset += (0..i).map(|j| current.child_mut(j).size).sum::<usize>();
+            self.path.push((node, i));
+            node = NonNull::from(current.child_mut(i));
+            target = within;
+        }
+    }
+
+    // Moves to the first entry of the leaf after the focused one, or past
+    // the end if there is none. `position` must already be updated.
+    fn next_leaf(&mut self) {
+        while let Some((parent, i)) = self.path.pop() {
+            // SAFETY: as in `touch`.
+            let parent_node = unsafe { &mut *parent.as_ptr() };
+
+            if i + 1 < arity(parent_node) {
+                self.path.push((parent, i + 1));
+                self.descend(NonNull::from(parent_node.child_mut(i + 1)), false);
+                return;
+            }
+        }
+
+        self.leaf = None;
+        self.slot = 0;
+    }
+
+    // Moves to the last entry of the leaf before the focused one, which
+    // exists since the focus is not the first entry. `position` must
+    // already be updated.
+    fn prev_leaf(&mut self) {
+        while let Some((parent, i)) = self.path.pop() {
+            if i > 0 {
+                // SAFETY: as in `touch`.
+                let parent_node = unsafe { &mut *parent.as_ptr() };
+                self.path.push((parent, i - 1));
+                self.descend(NonNull::from(parent_node.child_mut(i - 1)), true);
+                return;
+            }
+        }
+
+        unreachable!("no entry before the first");
+    }
+
+    // Descends from `node` to its first entry, or to its last if `last`.
+    fn descend(&mut self, mut node: NonNull<Node<K, V>>, last: bool) {
+        #[cfg(feature = "cow")]
+        let mut copied = false;
+
+        loop {
+            #[cfg(feature = "cow")]
+            {
+                copied |= self.unshare(node);
+            }
+
+            // SAFETY: as in `touch`.
+            let current = unsafe { &mut *node.as_ptr() };
+
+            if let Tree::Leaf(entries) = current.tree.as_ref() {
+                self.slot = if last { entries.len() - 1 } else { 0 };
+                self.leaf = Some(node);
+
+                // The copy moved the first entry of the nodes above that
+                // it is leftmost under.
+                #[cfg(feature = "cow")]
+                if copied {
+                    for (node, _) in self.path.iter().rev() {
+                        // SAFETY: as in `touch`.
+                        unsafe { &mut *node.as_ptr() }.refresh();
+                    }
+                }
+                return;
+            }
+
+            let i = if last { arity(current) - 1 } else { 0 };
+            self.path.push((node, i));
+            node = NonNull::from(current.child_mut(i));
+        }
+    }
+
+    // With `cow`, gives a node the cursor has just reached a tree of its
+    // own if a clone still holds its current one. Returns whether that
+    // copied a leaf.
+    #[cfg(feature = "cow")]
+    fn unshare(&mut self, node: NonNull<Node<K, V>>) -> bool {
+        // SAFETY: as in `touch`. Only the map's pool is borrowed, not the
+        // map as a whole, so the pointers along the path stay valid.
+        unsafe { (*self.map.as_ptr()).pool.unshare(&mut *node.as_ptr()) }
+    }
+}
+
+fn arity<K: Ord, V>(node: &Node<K, V>) -> usize {
+    match node.tree.as_ref() {
+        Tree::Leaf(_) => 0,
+        Tree::Branch2(..) => 2,
+        Tree::Branch3(..) => 3,
+    }
+}
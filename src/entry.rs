@@ -0,0 +1,205 @@
+use std::mem;
+use std::ptr::NonNull;
+
+use crate::{find, tree_mut, IndexableSortedMap, Tree};
+
+/// A view into a single entry of a map, which may be vacant or occupied,
+/// as returned by `IndexableSortedMap::entry`. The descent that finds the
+/// entry also records its position and, for an occupied entry, where it
+/// lies in its leaf, so reading or updating it needs no further descent.
+/// Inserting into a vacant entry descends once more.
+pub enum Entry<'a, K: Ord, V> {
+    Vacant(VacantEntry<'a, K, V>),
+    Occupied(OccupiedEntry<'a, K, V>),
+}
+
+pub struct VacantEntry<'a, K: Ord, V> {
+    map: &'a mut IndexableSortedMap<K, V>,
+    key: K,
+    index: usize,
+}
+
+pub struct OccupiedEntry<'a, K: Ord, V> {
+    map: &'a mut IndexableSortedMap<K, V>,
+    // The entry in its leaf. Only the map's own methods can move it, and
+    // the entry holds the map's unique borrow, so it stays put until the
+    // entry is removed through `map`.
+    entry: NonNull<(K, V)>,
+    index: usize,
+}
+
+// SAFETY: the pointer only leads into the map the entry borrows mutably,
+// so the entry is as safe to send or share as that borrow.
+unsafe impl<K: Ord + Send, V: Send> Send for OccupiedEntry<'_, K, V> {}
+unsafe impl<K: Ord + Sync, V: Sync> Sync for OccupiedEntry<'_, K, V> {}
+
+impl<K: Ord, V> IndexableSortedMap<K, V> {
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        #[cfg(feature = "cow")]
+        self.unshare_path(crate::Target::Key(&key));
+
+        match self.find_entry(&key) {
+            Ok((index, entry)) => Entry::Occupied(OccupiedEntry { map: self, entry, index }),
+            Err(index) => Entry::Vacant(VacantEntry { map: self, key, index }),
+        }
+    }
+
+    // Finds `key` along with its position and where the entry lies in its
+    // leaf, or the position it would be inserted at. The path is marked as
+    // changed, since the caller may go on to change the value.
+    fn find_entry(&mut self, key: &K) -> Result<(usize, NonNull<(K, V)>), usize> {
+        let Some(mut node) = self.root.as_mut() else {
+            return Err(0);
+        };
+        let mut offset = 0;
+
+        loop {
+            node.checkpoint = 0;
+
+            match tree_mut(&mut node.tree) {
+                Tree::Leaf(entries) => {
+                    return match find(entries, key) {
+                        Ok(slot) => Ok((offset + slot, NonNull::from(&mut entries[slot]))),
+                        Err(slot) => Err(offset + slot),
+                    };
+                },
+                Tree::Branch2(left, right) => {
+                    if key < right.min_key() {
+                        node = left;
+                    } else {
+                        offset += left.size;
+                        node = right;
+                    }
+                },
+                Tree::Branch3(left, middle, right) => {
+                    if key < middle.min_key() {
+                        node = left;
+                    } else if key < right.min_key() {
+                        offset += left.size;
+                        node = middle;
+                    } else {
+                        offset += left.size + middle.size;
+                        node = right;
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl<'a, K: Ord, V> Entry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Vacant(entry) => entry.key(),
+            Entry::Occupied(entry) => entry.key(),
+        }
+    }
+
+    /// The position the entry has, or would have once inserted.
+    pub fn index(&self) -> usize {
+        match self {
+            Entry::Vacant(entry) => entry.index(),
+            Entry::Occupied(entry) => entry.index(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        match self {
+            Entry::Vacant(entry) => entry.insert(default),
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Vacant(entry) => entry.insert(default()),
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+
+    pub fn or_insert_with_key<F: FnOnce(&K) -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Vacant(entry) => {
+                let value = default(&entry.key);
+                entry.insert(value)
+            },
+            Entry::Occupied(entry) => entry.into_mut(),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(self, f: F) -> Self {
+        match self {
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            },
+        }
+    }
+}
+
+impl<'a, K: Ord, V> VacantEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V {
+        let VacantEntry { map, key, .. } = self;
+        map.insert_entry(key, || value, false).2
+    }
+}
+
+impl<'a, K: Ord, V> OccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        // SAFETY: see `entry`; shared access goes through `&self`.
+        unsafe { &self.entry.as_ref().0 }
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn get(&self) -> &V {
+        // SAFETY: as for `key`.
+        unsafe { &self.entry.as_ref().1 }
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        // SAFETY: see `entry`; exclusive access goes through `&mut self`.
+        unsafe { &mut self.entry.as_mut().1 }
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        // SAFETY: see `entry`; the map stays borrowed for `'a`, and the
+        // entry is consumed, so nothing else can reach the value.
+        unsafe { &mut (*self.entry.as_ptr()).1 }
+    }
+
+    /// Replaces the value, returning the old one.
+    pub fn insert(&mut self, value: V) -> V {
+        mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> V {
+        self.remove_entry().1
+    }
+
+    pub fn remove_entry(self) -> (K, V) {
+        self.map.remove_at(self.index).unwrap()
+    }
+}
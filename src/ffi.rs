@@ -0,0 +1,176 @@
+//! C bindings over a map from byte-string keys to opaque value pointers.
+//! Values are never dereferenced or freed by the map; their ownership stays
+//! with the caller. See `include/indexable_sorted_map.h`.
+
+use std::ffi::c_void;
+use std::slice;
+
+use crate::IndexableSortedMap;
+
+pub struct IsmMap {
+    map: IndexableSortedMap<Box<[u8]>, *mut c_void>,
+}
+
+unsafe fn key_slice<'a>(key: *const u8, key_len: usize) -> &'a [u8] {
+    if key_len == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(key, key_len)
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn ism_new() -> *mut IsmMap {
+    Box::into_raw(Box::new(IsmMap { map: IndexableSortedMap::new() }))
+}
+
+/// # Safety
+///
+/// `map` must be null or a handle returned by `ism_new` that has not yet
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn ism_free(map: *mut IsmMap) {
+    if !map.is_null() {
+        drop(Box::from_raw(map));
+    }
+}
+
+/// # Safety
+///
+/// `map` must be a live handle returned by `ism_new`.
+#[no_mangle]
+pub unsafe extern "C" fn ism_len(map: *const IsmMap) -> usize {
+    (*map).map.len()
+}
+
+/// Inserts or replaces the entry for `key`. If it replaced one, writes the
+/// previous value to `out_value` and returns true; otherwise returns false.
+///
+/// # Safety
+///
+/// `map` must be a live handle, `key` must point to `key_len` readable
+/// bytes and `out_value` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn ism_insert(
+    map: *mut IsmMap,
+    key: *const u8,
+    key_len: usize,
+    value: *mut c_void,
+    out_value: *mut *mut c_void,
+) -> bool {
+    let map = &mut (*map).map;
+    let key: Box<[u8]> = key_slice(key, key_len).into();
+
+    match map.insert(key, value) {
+        None => false,
+        Some(old) => {
+            *out_value = old;
+            true
+        },
+    }
+}
+
+/// Writes the value for `key` to `out_value` and returns true, or returns
+/// false if there is no such entry.
+///
+/// # Safety
+///
+/// `map` must be a live handle, `key` must point to `key_len` readable
+/// bytes and `out_value` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn ism_lookup(
+    map: *const IsmMap,
+    key: *const u8,
+    key_len: usize,
+    out_value: *mut *mut c_void,
+) -> bool {
+    let key = key_slice(key, key_len);
+
+    match (*map).map.lookup(key) {
+        None => false,
+        Some(value) => {
+            *out_value = *value;
+            true
+        },
+    }
+}
+
+/// Writes the `i`th entry to the out parameters and returns true, or
+/// returns false if `i` is out of bounds. The key pointer stays valid until
+/// the map is next modified.
+///
+/// # Safety
+///
+/// `map` must be a live handle and the out parameters must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn ism_index(
+    map: *const IsmMap,
+    i: usize,
+    out_key: *mut *const u8,
+    out_key_len: *mut usize,
+    out_value: *mut *mut c_void,
+) -> bool {
+    match (*map).map.index(i) {
+        None => false,
+        Some((key, value)) => {
+            *out_key = key.as_ptr();
+            *out_key_len = key.len();
+            *out_value = *value;
+            true
+        },
+    }
+}
+
+/// Writes the position of `key` to `out_index` and returns true if it is
+/// present. Otherwise writes the position it would be inserted at and
+/// returns false.
+///
+/// # Safety
+///
+/// `map` must be a live handle, `key` must point to `key_len` readable
+/// bytes and `out_index` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn ism_rank(
+    map: *const IsmMap,
+    key: *const u8,
+    key_len: usize,
+    out_index: *mut usize,
+) -> bool {
+    let key = key_slice(key, key_len);
+
+    match (*map).map.rank(key) {
+        Ok(i) => {
+            *out_index = i;
+            true
+        },
+        Err(i) => {
+            *out_index = i;
+            false
+        },
+    }
+}
+
+/// Removes the entry for `key`, writing its value to `out_value` and
+/// returning true, or returns false if there is no such entry.
+///
+/// # Safety
+///
+/// `map` must be a live handle, `key` must point to `key_len` readable
+/// bytes and `out_value` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn ism_remove(
+    map: *mut IsmMap,
+    key: *const u8,
+    key_len: usize,
+    out_value: *mut *mut c_void,
+) -> bool {
+    let key = key_slice(key, key_len);
+
+    match (*map).map.remove(key) {
+        None => false,
+        Some(value) => {
+            *out_value = value;
+            true
+        },
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::mem;
+
+use crate::IndexableSortedMap;
+
+// Counters per expected entry and probes per key. Ten counters and seven
+// probes give roughly a 1% false positive rate at full capacity.
+const COUNTERS_PER_ENTRY: usize = 10;
+const PROBES: u64 = 7;
+
+/// A map paired with a counting Bloom filter over its keys, so lookups of
+/// absent keys usually return without descending the tree. Counters rather
+/// than bits let removals clear their keys from the filter. The filter is
+/// rebuilt at twice the size whenever the map outgrows it.
+pub struct FilteredIndexableSortedMap<K: Ord + Hash, V> {
+    map: IndexableSortedMap<K, V>,
+    counters: Vec<u8>,
+    capacity: usize,
+    hasher: RandomState,
+}
+
+impl<K: Ord + Hash, V> Default for FilteredIndexableSortedMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Hash, V> FilteredIndexableSortedMap<K, V> {
+    pub fn new() -> Self {
+        FilteredIndexableSortedMap::with_capacity(64)
+    }
+
+    /// Sizes the filter for `capacity` keys before it needs rebuilding.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+
+        FilteredIndexableSortedMap {
+            map: IndexableSortedMap::new(),
+            counters: vec![0; (capacity * COUNTERS_PER_ENTRY).next_power_of_two()],
+            capacity,
+            hasher: RandomState::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Whether `key` may be present. A `false` is always right.
+    pub fn may_contain(&self, key: &K) -> bool {
+        self.probes(key).all(|i| self.counters[i] > 0)
+    }
+
+    pub fn lookup(&self, key: &K) -> Option<&V> {
+        if self.may_contain(key) {
+            self.map.lookup(key)
+        } else {
+            None
+        }
+    }
+
+    pub fn index(&self, i: usize) -> Option<(&K, &V)> {
+        self.map.index(i)
+    }
+
+    pub fn rank(&self, key: &K) -> Result<usize, usize> {
+        self.map.rank(key)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if self.may_contain(&key) {
+            if let Some(existing) = self.map.lookup_mut(&key) {
+                return Some(mem::replace(existing, value));
+            }
+        }
+
+        self.add(&key);
+        self.map.insert(key, value);
+
+        if self.map.len() > self.capacity {
+            self.rebuild(self.capacity * 2);
+        }
+
+        None
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        if !self.may_contain(key) {
+            return None;
+        }
+
+        let value = self.map.remove(key)?;
+
+        // A saturated counter may be undercounting, so it stays put.
+        for i in probes(&self.hasher, self.counters.len(), key) {
+            if self.counters[i] < u8::MAX {
+                self.counters[i] -= 1;
+            }
+        }
+
+        Some(value)
+    }
+
+    pub fn as_map(&self) -> &IndexableSortedMap<K, V> {
+        &self.map
+    }
+
+    pub fn into_inner(self) -> IndexableSortedMap<K, V> {
+        self.map
+    }
+
+    fn add(&mut self, key: &K) {
+        for i in probes(&self.hasher, self.counters.len(), key) {
+            self.counters[i] = self.counters[i].saturating_add(1);
+        }
+    }
+
+    fn rebuild(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        self.counters = vec![0; (capacity * COUNTERS_PER_ENTRY).next_power_of_two()];
+
+        let mut zipper = self.map.zipper();
+        while let Some(z) = zipper {
+            for i in probes(&self.hasher, self.counters.len(), z.focus().0) {
+                self.counters[i] = self.counters[i].saturating_add(1);
+            }
+            zipper = z.advance(1);
+        }
+    }
+
+    fn probes(&self, key: &K) -> impl Iterator<Item = usize> {
+        probes(&self.hasher, self.counters.len(), key)
+    }
+}
+
+// Double hashing: the probes are h1, h1 + h2, h1 + 2 h2, ... taken modulo
+// the (power of two) number of counters, with h2 odd so they are distinct.
+fn probes<K: Hash>(hasher: &RandomState, counters: usize, key: &K) -> impl Iterator<Item = usize> {
+    let hash = hasher.hash_one(key);
+    let h1 = hash & 0xffff_ffff;
+    let h2 = (hash >> 32) | 1;
+    let mask = counters as u64 - 1;
+
+    (0..PROBES).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) & mask) as usize)
+}
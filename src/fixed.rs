@@ -0,0 +1,137 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+/// An ordered, rank-queryable map holding at most `N` entries inline,
+/// without any heap allocation. Entries are kept sorted in a fixed array,
+/// so lookups and positional queries are O(log N) and O(1), while inserts
+/// and removals shift at most `N` entries.
+pub struct FixedIndexableSortedMap<K: Ord, V, const N: usize> {
+    entries: [Option<(K, V)>; N],
+    len: usize,
+}
+
+/// Returned by `FixedIndexableSortedMap::insert` when the map is full,
+/// handing back the rejected entry.
+pub struct CapacityError<K, V> {
+    pub key: K,
+    pub value: V,
+}
+
+impl<K: Ord, V, const N: usize> Default for FixedIndexableSortedMap<K, V, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V, const N: usize> FixedIndexableSortedMap<K, V, N> {
+    pub fn new() -> Self {
+        FixedIndexableSortedMap {
+            entries: std::array::from_fn(|_| None),
+            len: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub(crate) fn entries(&self, range: std::ops::Range<usize>) -> &[Option<(K, V)>] {
+        &self.entries[range]
+    }
+
+    fn entry(&self, i: usize) -> &(K, V) {
+        self.entries[i].as_ref().unwrap()
+    }
+
+    pub fn rank(&self, key: &K) -> Result<usize, usize> {
+        let mut low = 0;
+        let mut high = self.len;
+
+        while low < high {
+            let mid = low + (high - low) / 2;
+
+            match self.entry(mid).0.cmp(key) {
+                Ordering::Less => { low = mid + 1; },
+                Ordering::Greater => { high = mid; },
+                Ordering::Equal => { return Ok(mid); },
+            }
+        }
+
+        Err(low)
+    }
+
+    pub fn lookup(&self, key: &K) -> Option<&V> {
+        let i = self.rank(key).ok()?;
+        Some(&self.entry(i).1)
+    }
+
+    pub fn index(&self, i: usize) -> Option<(&K, &V)> {
+        if i < self.len {
+            let (k, v) = self.entry(i);
+            Some((k, v))
+        } else {
+            None
+        }
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Result<Option<V>, CapacityError<K, V>> {
+        match self.rank(&key) {
+            Ok(i) => {
+                let (_k, old) = self.entries[i].replace((key, value)).unwrap();
+                Ok(Some(old))
+            },
+            Err(i) => {
+                if self.len == N {
+                    return Err(CapacityError { key, value });
+                }
+
+                self.entries[i..=self.len].rotate_right(1);
+                self.entries[i] = Some((key, value));
+                self.len += 1;
+                Ok(None)
+            },
+        }
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let i = self.rank(key).ok()?;
+
+        let (_k, v) = self.entries[i].take().unwrap();
+        self.entries[i..self.len].rotate_left(1);
+        self.len -= 1;
+        Some(v)
+    }
+
+    pub fn clear(&mut self) {
+        for entry in &mut self.entries[..self.len] {
+            *entry = None;
+        }
+        self.len = 0;
+    }
+}
+
+impl<K, V> fmt::Debug for CapacityError<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CapacityError").finish_non_exhaustive()
+    }
+}
+
+impl<K, V> fmt::Display for CapacityError<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "map is at capacity")
+    }
+}
+
+impl<K, V> std::error::Error for CapacityError<K, V> {}
@@ -0,0 +1,156 @@
+use std::borrow::Borrow;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{IndexableSortedMap, Node, Tree};
+
+// Buckets are the subtrees two levels below the root, numbered by the
+// child indices leading to them, so there are at most 3 * 3 of them.
+const DEPTH: usize = 2;
+const BUCKETS: usize = 9;
+
+#[derive(Default)]
+pub(crate) struct Heat {
+    counters: [AtomicU64; BUCKETS],
+    // The arities of the root and its children when counting began. A
+    // child of the root splitting or merging renumbers the buckets after
+    // it, so the counts are dropped rather than credited to the wrong
+    // regions.
+    shape: AtomicU64,
+}
+
+impl Heat {
+    // Drops the counts when the root itself splits, merges or is replaced,
+    // which moves every bucket down or up a level.
+    pub(crate) fn reshaped(&mut self) {
+        for counter in &mut self.counters {
+            *counter.get_mut() = 0;
+        }
+    }
+
+    fn reset(&self) {
+        for counter in &self.counters {
+            counter.store(0, Ordering::Relaxed);
+        }
+    }
+
+    // Starts the counts afresh if the top levels have changed shape since
+    // they were last looked at.
+    fn settle<K: Ord, V>(&self, root: &Node<K, V>) {
+        let shape = shape(root);
+        if self.shape.swap(shape, Ordering::Relaxed) != shape {
+            self.reset();
+        }
+    }
+}
+
+// The arities of the nodes above the buckets, two bits each, which are
+// never 0 for a node that is present.
+fn shape<K: Ord, V>(root: &Node<K, V>) -> u64 {
+    fn arity<K: Ord, V>(node: &Node<K, V>) -> u64 {
+        match node.tree.as_ref() {
+            Tree::Leaf(_) => 1,
+            Tree::Branch2(..) => 2,
+            Tree::Branch3(..) => 3,
+        }
+    }
+
+    match root.tree.as_ref() {
+        Tree::Leaf(_) => 1,
+        Tree::Branch2(left, right) => 2 | arity(left) << 2 | arity(right) << 4,
+        Tree::Branch3(left, middle, right) => 3 | arity(left) << 2 | arity(middle) << 4 | arity(right) << 6,
+    }
+}
+
+/// One region of the key space in a heatmap report: the subtree starting at
+/// `first_key`, how many entries it holds and how many key lookups have
+/// landed in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeatmapBucket<'a, K> {
+    pub first_key: &'a K,
+    pub entries: usize,
+    pub accesses: u64,
+}
+
+impl<K: Ord, V> IndexableSortedMap<K, V> {
+    pub(crate) fn record_access<Q: ?Sized + Ord>(&self, key: &Q)
+    where
+        K: Borrow<Q>,
+    {
+        let Some(mut node) = self.root.as_ref() else {
+            return;
+        };
+        self.heat.settle(node);
+
+        let mut bucket = 0;
+        let mut depth = 0;
+
+        while depth < DEPTH {
+            let (child, i) = match node.tree.as_ref() {
+                Tree::Leaf(_) => break,
+                Tree::Branch2(left, right) => {
+                    if key < right.min_key().borrow() { (left, 0) } else { (right, 1) }
+                },
+                Tree::Branch3(left, middle, right) => {
+                    if key < middle.min_key().borrow() {
+                        (left, 0)
+                    } else if key < right.min_key().borrow() {
+                        (middle, 1)
+                    } else {
+                        (right, 2)
+                    }
+                },
+            };
+
+            node = child;
+            bucket = bucket * 3 + i;
+            depth += 1;
+        }
+
+        // In a tree too shallow to reach a bucket, pad as if descending
+        // through first children.
+        let bucket = bucket * 3usize.pow((DEPTH - depth) as u32);
+        self.heat.counters[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Lookup counts per region of the key space, in key order. Regions are
+    /// the subtrees two levels below the root. Only `lookup`, `lookup_mut`
+    /// and `rank`, and the searches built on `rank`, count; the map's own
+    /// searches while editing don't. Counts describe the current shape of
+    /// the tree, so they start again from zero whenever the top two levels
+    /// change shape, and after bulk rebuilds such as `canonicalize`.
+    pub fn heatmap(&self) -> Vec<HeatmapBucket<'_, K>> {
+        let mut buckets = Vec::new();
+
+        if let Some(root) = &self.root {
+            self.heat.settle(root);
+            self.collect_buckets(root, 0, 0, &mut buckets);
+        }
+
+        buckets
+    }
+
+    pub fn reset_heatmap(&self) {
+        self.heat.reset();
+    }
+
+    fn collect_buckets<'a>(&'a self, node: &'a Node<K, V>, depth: usize, bucket: usize, buckets: &mut Vec<HeatmapBucket<'a, K>>) {
+        let children: &[&Node<K, V>] = match node.tree.as_ref() {
+            Tree::Branch2(left, right) if depth < DEPTH => &[left, right],
+            Tree::Branch3(left, middle, right) if depth < DEPTH => &[left, middle, right],
+            _ => {
+                let bucket = bucket * 3usize.pow((DEPTH - depth) as u32);
+
+                buckets.push(HeatmapBucket {
+                    first_key: node.min_key(),
+                    entries: node.size,
+                    accesses: self.heat.counters[bucket].load(Ordering::Relaxed),
+                });
+                return;
+            },
+        };
+
+        for (i, child) in children.iter().enumerate() {
+            self.collect_buckets(child, depth + 1, bucket * 3 + i, buckets);
+        }
+    }
+}
@@ -0,0 +1,216 @@
+use std::ops::{Bound, RangeBounds};
+
+use crate::{
+    BoundedIndexableSortedMap, FilteredIndexableSortedMap, FixedIndexableSortedMap, IndexableSortedMap,
+    PersistentIndexableSortedMap, PersistentRange, Range, SeparatedIndexableSortedMap, SeparatedIter, ValueStore,
+};
+
+/// The read-only interface shared by the map types, for code that should
+/// accept whichever one it is handed.
+pub trait IndexableMap {
+    type Key: Ord;
+    type Value;
+    type Range<'a>: DoubleEndedIterator<Item = (&'a Self::Key, &'a Self::Value)>
+    where
+        Self: 'a;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn lookup(&self, key: &Self::Key) -> Option<&Self::Value>;
+
+    fn index(&self, i: usize) -> Option<(&Self::Key, &Self::Value)>;
+
+    /// `Ok` with the position of `key`, or `Err` with the position it
+    /// would be inserted at.
+    fn rank(&self, key: &Self::Key) -> Result<usize, usize>;
+
+    fn range<R: RangeBounds<Self::Key>>(&self, range: R) -> Self::Range<'_>;
+}
+
+impl<K: Ord, V> IndexableMap for IndexableSortedMap<K, V> {
+    type Key = K;
+    type Value = V;
+    type Range<'a> = Range<'a, K, V> where Self: 'a;
+
+    fn len(&self) -> usize {
+        IndexableSortedMap::len(self)
+    }
+
+    fn lookup(&self, key: &K) -> Option<&V> {
+        IndexableSortedMap::lookup(self, key)
+    }
+
+    fn index(&self, i: usize) -> Option<(&K, &V)> {
+        IndexableSortedMap::index(self, i)
+    }
+
+    fn rank(&self, key: &K) -> Result<usize, usize> {
+        IndexableSortedMap::rank(self, key)
+    }
+
+    fn range<R: RangeBounds<K>>(&self, range: R) -> Range<'_, K, V> {
+        IndexableSortedMap::range(self, range)
+    }
+}
+
+impl<K: Ord, V> IndexableMap for BoundedIndexableSortedMap<K, V> {
+    type Key = K;
+    type Value = V;
+    type Range<'a> = Range<'a, K, V> where Self: 'a;
+
+    fn len(&self) -> usize {
+        self.as_map().len()
+    }
+
+    fn lookup(&self, key: &K) -> Option<&V> {
+        self.as_map().lookup(key)
+    }
+
+    fn index(&self, i: usize) -> Option<(&K, &V)> {
+        self.as_map().index(i)
+    }
+
+    fn rank(&self, key: &K) -> Result<usize, usize> {
+        self.as_map().rank(key)
+    }
+
+    fn range<R: RangeBounds<K>>(&self, range: R) -> Range<'_, K, V> {
+        self.as_map().range(range)
+    }
+}
+
+impl<K: Ord + std::hash::Hash, V> IndexableMap for FilteredIndexableSortedMap<K, V> {
+    type Key = K;
+    type Value = V;
+    type Range<'a> = Range<'a, K, V> where Self: 'a;
+
+    fn len(&self) -> usize {
+        self.as_map().len()
+    }
+
+    fn lookup(&self, key: &K) -> Option<&V> {
+        FilteredIndexableSortedMap::lookup(self, key)
+    }
+
+    fn index(&self, i: usize) -> Option<(&K, &V)> {
+        self.as_map().index(i)
+    }
+
+    fn rank(&self, key: &K) -> Result<usize, usize> {
+        self.as_map().rank(key)
+    }
+
+    fn range<R: RangeBounds<K>>(&self, range: R) -> Range<'_, K, V> {
+        self.as_map().range(range)
+    }
+}
+
+impl<K: Ord, V> IndexableMap for PersistentIndexableSortedMap<K, V> {
+    type Key = K;
+    type Value = V;
+    type Range<'a> = PersistentRange<'a, K, V> where Self: 'a;
+
+    fn len(&self) -> usize {
+        PersistentIndexableSortedMap::len(self)
+    }
+
+    fn lookup(&self, key: &K) -> Option<&V> {
+        PersistentIndexableSortedMap::lookup(self, key)
+    }
+
+    fn index(&self, i: usize) -> Option<(&K, &V)> {
+        PersistentIndexableSortedMap::index(self, i)
+    }
+
+    fn rank(&self, key: &K) -> Result<usize, usize> {
+        PersistentIndexableSortedMap::rank(self, key)
+    }
+
+    fn range<R: RangeBounds<K>>(&self, range: R) -> PersistentRange<'_, K, V> {
+        PersistentIndexableSortedMap::range(self, range)
+    }
+}
+
+impl<K: Ord, V, S: ValueStore<V>> IndexableMap for SeparatedIndexableSortedMap<K, V, S> {
+    type Key = K;
+    type Value = V;
+    type Range<'a> = SeparatedIter<'a, K, V, S> where Self: 'a;
+
+    fn len(&self) -> usize {
+        SeparatedIndexableSortedMap::len(self)
+    }
+
+    fn lookup(&self, key: &K) -> Option<&V> {
+        SeparatedIndexableSortedMap::lookup(self, key)
+    }
+
+    fn index(&self, i: usize) -> Option<(&K, &V)> {
+        SeparatedIndexableSortedMap::index(self, i)
+    }
+
+    fn rank(&self, key: &K) -> Result<usize, usize> {
+        SeparatedIndexableSortedMap::rank(self, key)
+    }
+
+    fn range<R: RangeBounds<K>>(&self, range: R) -> SeparatedIter<'_, K, V, S> {
+        SeparatedIndexableSortedMap::range(self, range)
+    }
+}
+
+type FixedRange<'a, K, V> = std::iter::Map<std::slice::Iter<'a, Option<(K, V)>>, fn(&'a Option<(K, V)>) -> (&'a K, &'a V)>;
+
+impl<K: Ord, V, const N: usize> IndexableMap for FixedIndexableSortedMap<K, V, N> {
+    type Key = K;
+    type Value = V;
+    type Range<'a> = FixedRange<'a, K, V> where Self: 'a;
+
+    fn len(&self) -> usize {
+        FixedIndexableSortedMap::len(self)
+    }
+
+    fn lookup(&self, key: &K) -> Option<&V> {
+        FixedIndexableSortedMap::lookup(self, key)
+    }
+
+    fn index(&self, i: usize) -> Option<(&K, &V)> {
+        FixedIndexableSortedMap::index(self, i)
+    }
+
+    fn rank(&self, key: &K) -> Result<usize, usize> {
+        FixedIndexableSortedMap::rank(self, key)
+    }
+
+    fn range<R: RangeBounds<K>>(&self, range: R) -> FixedRange<'_, K, V> {
+        let start = match range.start_bound() {
+            Bound::Unbounded => 0,
+            Bound::Included(k) => match self.rank(k) {
+                Ok(i) | Err(i) => i,
+            },
+            Bound::Excluded(k) => match self.rank(k) {
+                Ok(i) => i + 1,
+                Err(i) => i,
+            },
+        };
+
+        let end = match range.end_bound() {
+            Bound::Unbounded => self.len(),
+            Bound::Included(k) => match self.rank(k) {
+                Ok(i) => i + 1,
+                Err(i) => i,
+            },
+            Bound::Excluded(k) => match self.rank(k) {
+                Ok(i) | Err(i) => i,
+            },
+        };
+
+        let entries = self.entries(start..end.max(start));
+        entries.iter().map(|entry| {
+            let (k, v) = entry.as_ref().unwrap();
+            (k, v)
+        })
+    }
+}
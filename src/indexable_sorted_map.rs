@@ -1,548 +1,2082 @@
-use std::mem;
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem::{self, MaybeUninit};
+use std::ops::{Bound, Index, IndexMut, RangeBounds};
+use std::ptr::{self, NonNull};
+use std::slice;
+use std::vec;
+
+// Opens a `tracing` span for the rest of the enclosing block around a
+// structural operation, with the given size fields. Expands to nothing
+// without the `tracing` feature.
+macro_rules! structural_span {
+    ($name:literal $(, $field:ident = $value:expr)* $(,)?) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!($name $(, $field = $value)*).entered();
+    };
+}
+
+// Emits a `tracing` event for a structural change.
+macro_rules! structural_event {
+    ($message:literal $(, $field:ident = $value:expr)* $(,)?) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($field = $value,)* $message);
+    };
+}
+
+mod augmented;
+mod bounded;
+mod buffered;
+mod builder;
+mod checkpoint;
+mod comparator;
+mod cursor;
+mod entry;
+mod filtered;
+mod fixed;
+mod indexable_map;
+mod interval;
+mod merge;
+mod multimap;
+mod persistent;
+mod separated;
+mod set;
+mod sharded;
+mod split;
+mod stable;
+mod weighted;
+
+#[cfg(feature = "arena")]
+mod arena;
+
+#[cfg(feature = "cow")]
+mod cow;
+
+#[cfg(feature = "lz4")]
+mod compress;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(feature = "heatmap")]
+mod heatmap;
+
+#[cfg(feature = "intern")]
+mod intern;
+
+#[cfg(feature = "proptest")]
+mod proptest_impls;
+
+#[cfg(feature = "pyo3")]
+mod python;
+
+#[cfg(feature = "rayon")]
+mod rayon_impls;
+
+#[cfg(feature = "rcu")]
+mod rcu;
+
+#[cfg(feature = "serde")]
+mod serde_impls;
+
+#[cfg(feature = "futures")]
+mod stream;
+
+#[cfg(feature = "serde")]
+pub mod structured;
+
+#[cfg(any(test, feature = "validate"))]
+mod validate;
+
+pub use augmented::{AugmentedIter, AugmentedSearch, AugmentedSortedMap, Measure};
+pub use bounded::{BoundedIndexableSortedMap, Eviction};
+pub use buffered::BufferedIndexableSortedMap;
+pub use builder::{OutOfOrder, SortedInputError, SortedMapBuilder};
+pub use checkpoint::CheckpointSink;
+pub use comparator::{Comparator, ComparatorIndexableSortedMap, ComparatorIter};
+pub use cursor::{CursorMut, CursorToken};
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use filtered::FilteredIndexableSortedMap;
+pub use fixed::{CapacityError, FixedIndexableSortedMap};
+pub use indexable_map::IndexableMap;
+pub use interval::{IntervalIter, IntervalMap, Overlapping};
+pub use merge::Conflicts;
+pub use multimap::{IndexableSortedMultiMap, MultiIter, MultiValues};
+pub use persistent::{PersistentIndexableSortedMap, PersistentRange};
+pub use separated::{BoxStore, SeparatedIndexableSortedMap, SeparatedIter, SlabStore, ValueStore};
+pub use set::{IndexableSortedSet, SetIntoIter, SetIter};
+pub use sharded::ShardedIndexableSortedMap;
+pub use stable::StableIndexableSortedMap;
+pub use weighted::{WeightedIter, WeightedSortedMap};
+
+#[cfg(feature = "arena")]
+pub use arena::NodeArena;
+
+#[cfg(feature = "lz4")]
+pub use compress::{CodecError, DecodeIssue};
+
+#[cfg(feature = "heatmap")]
+pub use heatmap::HeatmapBucket;
+
+#[cfg(feature = "intern")]
+pub use intern::{InternedStr, Interner};
+
+#[cfg(feature = "pyo3")]
+pub use python::PyIndexableSortedMap;
+
+#[cfg(feature = "rayon")]
+pub use rayon_impls::{ParIntoIter, ParIter, ParIterMut};
+
+#[cfg(feature = "rcu")]
+pub use rcu::{RcuIndexableSortedMap, RcuSnapshot};
+
+#[cfg(feature = "futures")]
+pub use stream::{EntryStream, IntoEntryStream};
+
+#[cfg(any(test, feature = "validate"))]
+pub use validate::InvariantError;
 
-pub struct IndexableSortedMap<K: Ord + Clone, V> {
+pub struct IndexableSortedMap<K: Ord, V> {
     root: Option<Node<K, V>>,
+    pool: NodePool<K, V>,
+    // Which map's checkpoints the ids in this map's nodes may refer to, or
+    // 0 if it has never been checkpointed. Subtrees that arrive from
+    // another map keep that map's lineage, so `checkpoint` writes them out
+    // again rather than refer to records its sink was never sent.
+    lineage: u64,
+    #[cfg(feature = "heatmap")]
+    heat: heatmap::Heat,
+}
+
+// The most entries a leaf holds. Bulk builds fill leaves to this, and an
+// insertion that overfills one splits it in half.
+const LEAF_CAPACITY: usize = 16;
+
+// With `perf`, each node gets a cache line of its own, so reading a
+// branch's children never drags in a neighbour's.
+#[cfg_attr(feature = "perf", repr(align(64)))]
+enum Tree<K: Ord, V, M: Measure<K, V> = ()> {
+    // Between one and `LEAF_CAPACITY` entries, in key order.
+    Leaf(Vec<(K, V)>),
+    Branch2(Node<K, V, M>, Node<K, V, M>),
+    Branch3(Node<K, V, M>, Node<K, V, M>, Node<K, V, M>),
+}
+
+// A subtree, along with what is cached about it. `M` is the summary that
+// an `AugmentedSortedMap` keeps of each subtree; every other map keeps
+// `()`, which takes no space and no time to maintain.
+struct Node<K: Ord, V, M: Measure<K, V> = ()> {
+    // The first entry of the leftmost leaf below, whose key is the
+    // subtree's smallest. It points into that leaf's vector, so anything
+    // that changes which leaf is leftmost, or that leaf's vector, must
+    // `refresh` the nodes above it.
+    first: NonNull<(K, V)>,
+    size: usize,
+    // The subtree's entries folded by `M`. Anything that changes an entry
+    // below must `refresh` the nodes above it, as for `first`.
+    summary: M::Summary,
+    // The id this subtree was written under by the last checkpoint, or 0 if
+    // it has been built or modified since. Anything handing out `&mut V`
+    // must clear it along the path. The id only stands for the subtree in
+    // the map whose `lineage` wrote it.
+    checkpoint: u64,
+    lineage: u64,
+    tree: Shared<Tree<K, V, M>>,
+}
+
+// With `cow`, a clone shares its trees with the map it was cloned from,
+// and whichever map edits one first takes a copy of it if the other still
+// holds it.
+#[cfg(not(feature = "cow"))]
+type Shared<T> = Box<T>;
+#[cfg(feature = "cow")]
+type Shared<T> = std::sync::Arc<T>;
+
+// SAFETY: `first` points into the node's own subtree, so the node is
+// exactly as safe to send or share as the entries it owns. With `cow`, a
+// tree is only ever shared between clones, and only maps of entries that
+// are both `Send` and `Sync` can be cloned, as with an `Arc`.
+unsafe impl<K: Ord + Send, V: Send, M: Measure<K, V>> Send for Node<K, V, M> where M::Summary: Send {}
+unsafe impl<K: Ord + Sync, V: Sync, M: Measure<K, V>> Sync for Node<K, V, M> where M::Summary: Sync {}
+
+#[derive(Clone, Copy)]
+enum TreeContext {
+    Branch2Left,
+    Branch2Right,
+    Branch3Left,
+    Branch3Middle,
+    Branch3Right,
+}
+
+struct NodeContext<'a, K: Ord, V> {
+    context: TreeContext,
+    node: &'a Node<K, V>,
+}
+
+pub struct TreeZipper<'a, K: Ord, V> {
+    stack: Vec<NodeContext<'a, K, V>>,
+    leaf: &'a [(K, V)],
+    slot: usize,
+}
+
+pub struct Page<'a, K, V> {
+    pub entries: Vec<(&'a K, &'a V)>,
+    pub page_index: usize,
+    pub page_size: usize,
+    pub total_pages: usize,
+    pub total_entries: usize,
+}
+
+pub struct GroupBy<'a, K: Ord, V, G, F> {
+    zipper: Option<TreeZipper<'a, K, V>>,
+    next_group: Option<G>,
+    f: F,
+}
+
+pub struct Group<'a, K: Ord, V> {
+    zipper: Option<TreeZipper<'a, K, V>>,
+    remaining: usize,
+}
+
+pub struct StepByIndex<'a, K: Ord, V> {
+    zipper: Option<TreeZipper<'a, K, V>>,
+    step: usize,
+    remaining: usize,
+}
+
+pub struct Iter<'a, K: Ord, V> {
+    range: Range<'a, K, V>,
+}
+
+pub struct Range<'a, K: Ord, V> {
+    front: Option<TreeZipper<'a, K, V>>,
+    back: Option<TreeZipper<'a, K, V>>,
+    remaining: usize,
+}
+
+pub struct Windows<'a, K: Ord, V> {
+    zipper: Option<TreeZipper<'a, K, V>>,
+    width: usize,
+    remaining: usize,
+}
+
+// The subtrees not yet visited, between the entries left in the leaves
+// each end is part way through.
+pub struct IterMut<'a, K: Ord, V> {
+    front: slice::IterMut<'a, (K, V)>,
+    nodes: VecDeque<&'a mut Node<K, V>>,
+    back: slice::IterMut<'a, (K, V)>,
+    remaining: usize,
+}
+
+pub struct Keys<'a, K: Ord, V> {
+    iter: Iter<'a, K, V>,
+}
+
+pub struct Values<'a, K: Ord, V> {
+    iter: Iter<'a, K, V>,
+}
+
+pub struct ValuesMut<'a, K: Ord, V> {
+    iter: IterMut<'a, K, V>,
+}
+
+pub struct IntoIter<K: Ord, V> {
+    entries: IntoEntries<K, V>,
+    remaining: usize,
+}
+
+// The subtrees not yet visited, in order, between the entries left in the
+// leaves each end is part way through. Each end splits the subtree facing
+// it until it reaches a leaf. `front` is held in reverse so that both ends
+// take entries with `pop`, which keeps each leaf's vector whole to give
+// back to `pool` once emptied, along with the nodes.
+struct IntoEntries<K: Ord, V> {
+    front: Vec<(K, V)>,
+    nodes: VecDeque<Node<K, V>>,
+    back: Vec<(K, V)>,
+    pool: NodePool<K, V>,
+}
+
+struct NodePool<K: Ord, V, M: Measure<K, V> = ()> {
+    // The boxes themselves are what is kept for reuse.
+    #[allow(clippy::vec_box)]
+    spare: Vec<Shared<MaybeUninit<Tree<K, V, M>>>>,
+    // Emptied leaf vectors, each with room for an overfull leaf.
+    buffers: Vec<Vec<(K, V)>>,
+    #[cfg(feature = "arena")]
+    arena: Option<NodeArena<K, V, M>>,
+    // How to copy a leaf shared with a clone, set by `Clone`, which is the
+    // only place that knows the entries can be cloned.
+    #[cfg(feature = "cow")]
+    cloner: std::sync::OnceLock<cow::Cloner<K, V>>,
+}
+
+// SAFETY: the spare allocations are uninitialized and the buffers empty,
+// so a pool holds no entries. With `cow` the spares are `Arc`s, which
+// would otherwise make a map only as `Send` as its entries are `Sync`.
+#[cfg(feature = "cow")]
+unsafe impl<K: Ord, V, M: Measure<K, V>> Send for NodePool<K, V, M> {}
+#[cfg(feature = "cow")]
+unsafe impl<K: Ord, V, M: Measure<K, V>> Sync for NodePool<K, V, M> {}
+
+enum InsertResult<K: Ord, V> {
+    SameDepth(Node<K, V>),
+    Overflow(Node<K, V>, Node<K, V>),
+}
+
+// What an insertion did to the node it was applied to: replaced the
+// value of an existing key, found the key present and left it alone, grew
+// the subtree by one entry, or grew it past three children, splitting off
+// the returned node as its right sibling.
+enum Inserted<K: Ord, V, M: Measure<K, V> = ()> {
+    Replaced(V),
+    Present,
+    Grew,
+    Split(Node<K, V, M>),
+}
+
+// Where an insertion left the entry for its key: its position within the
+// subtree it was applied to, counting any sibling split off, and its value.
+// The pointer leads into a leaf's entries, which stay put while the nodes
+// above the leaf are rebuilt.
+struct Landed<V> {
+    position: usize,
+    value: NonNull<V>,
+}
+
+// What a removal did to the node it was applied to. `underflow` means the
+// node was left with a single child and has been replaced by it, a level
+// shorter than its siblings. A leaf may be left empty, for its parent to
+// unlink.
+enum Removed<K: Ord, V> {
+    Missing,
+    Entry { entry: (K, V), underflow: bool },
+}
+
+// The entry a removal is after: the one with a given key, or the one at a
+// given position within the subtree being descended.
+enum Target<'a, Q: ?Sized> {
+    Key(&'a Q),
+    Index(usize),
+}
+
+impl<K: Ord, V> Default for IndexableSortedMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Dismantles the tree from a worklist of subtrees rather than leaving it
+// to the recursive drop of each box.
+impl<K: Ord, V> Drop for IndexableSortedMap<K, V> {
+    fn drop(&mut self) {
+        if let Some(root) = self.root.take() {
+            self.pool.dismantle(root);
+        }
+    }
+}
+
+impl<K: Ord + fmt::Debug, V: fmt::Debug> fmt::Debug for IndexableSortedMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+// Maps compare and hash by their entries in order, whatever the shape of
+// their trees.
+impl<K: Ord, V: PartialEq> PartialEq for IndexableSortedMap<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().eq(other.iter())
+    }
+}
+
+impl<K: Ord, V: Eq> Eq for IndexableSortedMap<K, V> {}
+
+impl<K: Ord, V: PartialOrd> PartialOrd for IndexableSortedMap<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.iter().partial_cmp(other.iter())
+    }
+}
+
+impl<K: Ord, V: Ord> Ord for IndexableSortedMap<K, V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.iter().cmp(other.iter())
+    }
+}
+
+impl<K: Ord + Hash, V: Hash> Hash for IndexableSortedMap<K, V> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for entry in self {
+            entry.hash(state);
+        }
+    }
+}
+
+impl<K: Ord, V> Index<usize> for IndexableSortedMap<K, V> {
+    type Output = V;
+
+    fn index(&self, i: usize) -> &V {
+        match IndexableSortedMap::index(self, i) {
+            Some((_k, v)) => v,
+            None => panic!("index {} out of range for map of length {}", i, self.len()),
+        }
+    }
+}
+
+impl<K: Ord, V> IndexMut<usize> for IndexableSortedMap<K, V> {
+    fn index_mut(&mut self, i: usize) -> &mut V {
+        let len = self.len();
+        match IndexableSortedMap::index_mut(self, i) {
+            Some((_k, v)) => v,
+            None => panic!("index {} out of range for map of length {}", i, len),
+        }
+    }
+}
+
+impl<K: Ord + Borrow<Q>, V, Q: ?Sized + Ord> Index<&Q> for IndexableSortedMap<K, V> {
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.lookup(key).expect("key not found")
+    }
+}
+
+impl<K: Ord + Borrow<Q>, V, Q: ?Sized + Ord> IndexMut<&Q> for IndexableSortedMap<K, V> {
+    fn index_mut(&mut self, key: &Q) -> &mut V {
+        self.lookup_mut(key).expect("key not found")
+    }
 }
 
-enum Tree<K: Ord + Clone, V> {
-    Leaf(K, V),
-    Branch2(Node<K, V>, Node<K, V>),
-    Branch3(Node<K, V>, Node<K, V>, Node<K, V>),
+impl<K: Ord, V> IndexableSortedMap<K, V> {
+    pub fn new() -> Self {
+        IndexableSortedMap::with_pool(NodePool::new())
+    }
+
+    fn with_pool(pool: NodePool<K, V>) -> Self {
+        IndexableSortedMap {
+            root: None,
+            pool,
+            lineage: 0,
+            #[cfg(feature = "heatmap")]
+            heat: Default::default(),
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut map = Self::new();
+        map.reserve(capacity);
+        map
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.pool.reserve(additional);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.pool.shrink_to_fit();
+    }
+
+    pub fn compact(&mut self) {
+        self.canonicalize();
+    }
+
+    /// Rebuilds the tree into a shape that depends only on the number of
+    /// entries. Two maps with the same
+    /// entries are structurally identical after canonicalizing, whatever
+    /// their insertion histories.
+    pub fn canonicalize(&mut self) {
+        if !self.is_canonical() {
+            structural_span!("canonicalize", len = self.len());
+            let entries = self.take_entries();
+            let mut builder = SortedMapBuilder::with_pool(mem::replace(&mut self.pool, NodePool::new()));
+
+            for (k, v) in entries {
+                builder.push_unchecked(k, v);
+            }
+
+            *self = builder.finish();
+        }
+    }
+
+    pub fn is_canonical(&self) -> bool {
+        let mut levels: Vec<Vec<usize>> = Vec::new();
+        let mut leaves = Vec::new();
+        let mut stack: Vec<(&Node<K, V>, usize)> = self.root.iter().map(|node| (node, 0)).collect();
+
+        while let Some((node, depth)) = stack.pop() {
+            let children: Vec<&Node<K, V>> = match node.tree.as_ref() {
+                Tree::Leaf(entries) => {
+                    leaves.push(entries.len());
+                    continue;
+                },
+                Tree::Branch2(left, right) => vec![left, right],
+                Tree::Branch3(left, middle, right) => vec![left, middle, right],
+            };
+
+            if levels.len() == depth {
+                levels.push(Vec::new());
+            }
+            levels[depth].push(children.len());
+            stack.extend(children.into_iter().rev().map(|child| (child, depth + 1)));
+        }
+
+        // Every leaf but the last is full.
+        let packed = leaves.split_last().is_none_or(|(_last, full)| full.iter().all(|&len| len == LEAF_CAPACITY));
+
+        packed && levels.iter().all(|arities| {
+            let mut remaining: usize = arities.iter().sum();
+
+            arities.iter().all(|&arity| {
+                let expected = if remaining == 2 || remaining == 4 { 2 } else { 3 };
+                remaining -= arity;
+                arity == expected
+            })
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.root {
+            None => 0,
+            Some(node) => node.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn lookup<Q: ?Sized + Ord>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        #[cfg(feature = "heatmap")]
+        self.record_access(key);
+
+        let mut node = self.root.as_ref()?;
+
+        loop {
+            match node.tree.as_ref() {
+                Tree::Leaf(entries) => {
+                    return find(entries, key).ok().map(|slot| &entries[slot].1);
+                },
+                Tree::Branch2(left, right) => {
+                    prefetch(left);
+                    prefetch(right);
+
+                    if key < right.min_key().borrow() {
+                        node = left;
+                    } else {
+                        node = right;
+                    }
+                },
+                Tree::Branch3(left, middle, right) => {
+                    prefetch(left);
+                    prefetch(middle);
+                    prefetch(right);
+
+                    if key < middle.min_key().borrow() {
+                        node = left;
+                    } else if key < right.min_key().borrow() {
+                        node = middle;
+                    } else {
+                        node = right;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Like `lookup`, but the value can be changed in place. The descent
+    /// doesn't restructure any nodes.
+    pub fn lookup_mut<Q: ?Sized + Ord>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+    {
+        #[cfg(feature = "heatmap")]
+        self.record_access(key);
+        #[cfg(feature = "cow")]
+        self.unshare_path(Target::Key(key));
+
+        let mut node = self.root.as_mut()?;
+
+        loop {
+            node.checkpoint = 0;
+
+            match tree_mut(&mut node.tree) {
+                Tree::Leaf(entries) => {
+                    return find(entries, key).ok().map(|slot| &mut entries[slot].1);
+                },
+                Tree::Branch2(left, right) => {
+                    if key < right.min_key().borrow() {
+                        node = left;
+                    } else {
+                        node = right;
+                    }
+                },
+                Tree::Branch3(left, middle, right) => {
+                    if key < middle.min_key().borrow() {
+                        node = left;
+                    } else if key < right.min_key().borrow() {
+                        node = middle;
+                    } else {
+                        node = right;
+                    }
+                },
+            }
+        }
+    }
+
+    /// The position of `key` in sorted order, or if it is absent, `Err` of
+    /// the position it would be inserted at, like `slice::binary_search`.
+    pub fn rank<Q: ?Sized + Ord>(&self, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+    {
+        #[cfg(feature = "heatmap")]
+        self.record_access(key);
+
+        self.key_position(key)
+    }
+
+    // `rank` for the map's own use, which the heatmap doesn't count as a
+    // lookup.
+    pub(crate) fn key_position<Q: ?Sized + Ord>(&self, key: &Q) -> Result<usize, usize>
+    where
+        K: Borrow<Q>,
+    {
+        let mut node = match &self.root {
+            None => { return Err(0); },
+            Some(node) => node,
+        };
+        let mut offset = 0;
+
+        loop {
+            match node.tree.as_ref() {
+                Tree::Leaf(entries) => {
+                    return match find(entries, key) {
+                        Ok(slot) => Ok(offset + slot),
+                        Err(slot) => Err(offset + slot),
+                    };
+                },
+                Tree::Branch2(left, right) => {
+                    prefetch(left);
+                    prefetch(right);
+
+                    if key < right.min_key().borrow() {
+                        node = left;
+                    } else {
+                        offset += left.size;
+                        node = right;
+                    }
+                },
+                Tree::Branch3(left, middle, right) => {
+                    prefetch(left);
+                    prefetch(middle);
+                    prefetch(right);
+
+                    if key < middle.min_key().borrow() {
+                        node = left;
+                    } else if key < right.min_key().borrow() {
+                        offset += left.size;
+                        node = middle;
+                    } else {
+                        offset += left.size + middle.size;
+                        node = right;
+                    }
+                },
+            }
+        }
+    }
+
+    pub fn index(&self, i: usize) -> Option<(&K, &V)> {
+        let mut zipper = self.zipper()?;
+        zipper = zipper.advance(i)?;
+        Some(zipper.into_focus())
+    }
+
+    /// The entry `delta` positions after `key`, or before it for a negative
+    /// `delta`, found with a single descent. If `key` is absent, +1 and -1
+    /// are the entries either side of where it would be.
+    pub fn get_offset<Q: ?Sized + Ord>(&self, key: &Q, delta: isize) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+    {
+        let zipper = match self.zipper()?.advance_to(key) {
+            Some(zipper) => zipper,
+            // Every key is smaller, so only the tail is reachable.
+            None => {
+                return if delta < 0 {
+                    self.len().checked_sub(delta.unsigned_abs()).and_then(|i| self.index(i))
+                } else {
+                    None
+                };
+            },
+        };
+
+        let delta = if zipper.focused().0.borrow() == key || delta < 0 {
+            delta
+        } else if delta > 0 {
+            delta - 1
+        } else {
+            return None;
+        };
+
+        zipper.move_by(delta).map(TreeZipper::into_focus)
+    }
+
+    /// The entry closest to `key` by `distance`, which is only consulted
+    /// for the nearest entries on either side. Ties go to the smaller key.
+    pub fn nearest<D: PartialOrd, F: FnMut(&K, &K) -> D>(&self, key: &K, mut distance: F) -> Option<(&K, &V)> {
+        let i = match self.rank(key) {
+            Ok(i) => return self.index(i),
+            Err(i) => i,
+        };
+
+        let below = i.checked_sub(1).and_then(|i| self.index(i));
+        let above = self.index(i);
+
+        match (below, above) {
+            (Some(below), Some(above)) => {
+                if distance(key, above.0) < distance(key, below.0) {
+                    Some(above)
+                } else {
+                    Some(below)
+                }
+            },
+            (below, above) => below.or(above),
+        }
+    }
+
+    /// The child indices leading from the root to `key`'s leaf, followed
+    /// by its slot within the leaf. A path stays valid until the map is
+    /// next modified.
+    pub fn path_of<Q: ?Sized + Ord>(&self, key: &Q) -> Option<Vec<u8>>
+    where
+        K: Borrow<Q>,
+    {
+        let mut node = self.root.as_ref()?;
+        let mut path = Vec::new();
+
+        loop {
+            match node.tree.as_ref() {
+                Tree::Leaf(entries) => {
+                    let slot = find(entries, key).ok()?;
+                    path.push(slot as u8);
+                    return Some(path);
+                },
+                Tree::Branch2(left, right) => {
+                    if key < right.min_key().borrow() {
+                        path.push(0);
+                        node = left;
+                    } else {
+                        path.push(1);
+                        node = right;
+                    }
+                },
+                Tree::Branch3(left, middle, right) => {
+                    if key < middle.min_key().borrow() {
+                        path.push(0);
+                        node = left;
+                    } else if key < right.min_key().borrow() {
+                        path.push(1);
+                        node = middle;
+                    } else {
+                        path.push(2);
+                        node = right;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Follows a path from `path_of` without comparing any keys. Returns
+    /// `None` if the path does not end at an entry of the current tree.
+    pub fn get_by_path(&self, path: &[u8]) -> Option<(&K, &V)> {
+        let mut node = self.root.as_ref()?;
+        let (&slot, path) = path.split_last()?;
+
+        for &child in path {
+            node = match (node.tree.as_ref(), child) {
+                (Tree::Branch2(left, _right), 0) => left,
+                (Tree::Branch2(_left, right), 1) => right,
+                (Tree::Branch3(left, _middle, _right), 0) => left,
+                (Tree::Branch3(_left, middle, _right), 1) => middle,
+                (Tree::Branch3(_left, _middle, right), 2) => right,
+                _ => return None,
+            };
+        }
+
+        match node.tree.as_ref() {
+            Tree::Leaf(entries) => entries.get(slot as usize).map(|(k, v)| (k, v)),
+            _ => None,
+        }
+    }
+
+    /// Like `index`, but the value can be changed in place.
+    pub fn index_mut(&mut self, mut i: usize) -> Option<(&K, &mut V)> {
+        #[cfg(feature = "cow")]
+        if i < self.len() {
+            self.unshare_path(Target::<K>::Index(i));
+        }
+
+        let mut node = self.root.as_mut()?;
+        if i >= node.size {
+            return None;
+        }
+
+        loop {
+            node.checkpoint = 0;
+
+            match tree_mut(&mut node.tree) {
+                Tree::Leaf(entries) => {
+                    let (k, v) = &mut entries[i];
+                    return Some((k, v));
+                },
+                Tree::Branch2(left, right) => {
+                    if i < left.size {
+                        node = left;
+                    } else {
+                        i -= left.size;
+                        node = right;
+                    }
+                },
+                Tree::Branch3(left, middle, right) => {
+                    if i < left.size {
+                        node = left;
+                    } else if i < left.size + middle.size {
+                        i -= left.size;
+                        node = middle;
+                    } else {
+                        i -= left.size + middle.size;
+                        node = right;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Returns the position of `key` and its value, inserting `default()`
+    /// first if it is absent. Either way this is a single descent.
+    pub fn get_or_insert_index<F: FnOnce() -> V>(&mut self, key: K, default: F) -> (usize, &mut V) {
+        let (_, i, v) = self.insert_entry(key, default, false);
+        (i, v)
+    }
+
+    /// Returns the value under `key`, inserting `default()` first if it is
+    /// absent. When the key is present this is a single descent that
+    /// neither calls `default` nor restructures the tree.
+    pub fn get_or_insert_with<F: FnOnce() -> V>(&mut self, key: K, default: F) -> &mut V {
+        self.get_or_insert_index(key, default).1
+    }
+
+    pub fn page(&self, page_index: usize, page_size: usize) -> Page<'_, K, V> {
+        assert!(page_size > 0, "page_size must be non-zero");
+
+        let total_entries = self.len();
+        let mut entries = Vec::new();
+
+        let mut zipper = page_index
+            .checked_mul(page_size)
+            .and_then(|start| self.zipper()?.advance(start));
+
+        while let Some(z) = zipper {
+            entries.push(z.focused());
+            if entries.len() == page_size {
+                break;
+            }
+            zipper = z.advance(1);
+        }
+
+        Page {
+            entries,
+            page_index,
+            page_size,
+            total_pages: total_entries.div_ceil(page_size),
+            total_entries,
+        }
+    }
+
+    /// Iterates over every entry in key order, in O(n) overall.
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter { range: self.range(..) }
+    }
+
+    /// Iterates over every entry in key order with mutable access to the
+    /// values.
+    pub fn iter_mut(&mut self) -> IterMut<'_, K, V> {
+        #[cfg(feature = "cow")]
+        self.unshare_all();
+
+        IterMut {
+            front: [].iter_mut(),
+            remaining: self.len(),
+            nodes: self.root.iter_mut().collect(),
+            back: [].iter_mut(),
+        }
+    }
+
+    /// Iterates over the keys in order.
+    pub fn keys(&self) -> Keys<'_, K, V> {
+        Keys { iter: self.iter() }
+    }
+
+    /// Iterates over the values in key order.
+    pub fn values(&self) -> Values<'_, K, V> {
+        Values { iter: self.iter() }
+    }
+
+    /// Iterates over the values in key order with mutable access.
+    pub fn values_mut(&mut self) -> ValuesMut<'_, K, V> {
+        ValuesMut { iter: self.iter_mut() }
+    }
+
+    /// Iterates over the entries whose keys lie in `range`, from either
+    /// end.
+    pub fn range<Q: ?Sized + Ord, R: RangeBounds<Q>>(&self, range: R) -> Range<'_, K, V>
+    where
+        K: Borrow<Q>,
+    {
+        let (start, end) = self.range_positions(&range);
+        self.range_between(start, end)
+    }
+
+    /// Iterates over the entries at the positions in `range`, from either
+    /// end. Seeking to each end costs O(log n), after which every step is
+    /// amortized O(1).
+    ///
+    /// Panics if the range is inverted or extends past the end, as slicing
+    /// would.
+    pub fn range_by_index<R: RangeBounds<usize>>(&self, range: R) -> Range<'_, K, V> {
+        let start = match range.start_bound() {
+            Bound::Unbounded => 0,
+            Bound::Included(&i) => i,
+            Bound::Excluded(&i) => i.checked_add(1).expect("range start overflows"),
+        };
+
+        let end = match range.end_bound() {
+            Bound::Unbounded => self.len(),
+            Bound::Included(&i) => i.checked_add(1).expect("range end overflows"),
+            Bound::Excluded(&i) => i,
+        };
+
+        assert!(start <= end, "range starts at {} but ends at {}", start, end);
+        assert!(end <= self.len(), "range end {} is out of bounds for length {}", end, self.len());
+
+        self.range_between(start, end)
+    }
+
+    fn range_between(&self, start: usize, end: usize) -> Range<'_, K, V> {
+        let remaining = end.saturating_sub(start);
+        let front = if remaining > 0 {
+            self.zipper().and_then(|z| z.advance(start))
+        } else {
+            None
+        };
+        let back = front.clone().and_then(|z| z.advance(remaining - 1));
+
+        Range { front, back, remaining }
+    }
+
+    /// The entry with the smallest key in `range`.
+    pub fn range_first<Q: ?Sized + Ord, R: RangeBounds<Q>>(&self, range: R) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+    {
+        let (start, end) = self.range_positions(&range);
+        if start < end { self.index(start) } else { None }
+    }
+
+    /// The entry with the largest key in `range`.
+    pub fn range_last<Q: ?Sized + Ord, R: RangeBounds<Q>>(&self, range: R) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+    {
+        let (start, end) = self.range_positions(&range);
+        if start < end { self.index(end - 1) } else { None }
+    }
+
+    /// The number of entries whose keys lie in `range`, as the difference
+    /// of the ranks of its bounds, in O(log n).
+    pub fn count_range<Q: ?Sized + Ord, R: RangeBounds<Q>>(&self, range: R) -> usize
+    where
+        K: Borrow<Q>,
+    {
+        let (start, end) = self.range_positions(&range);
+        end.saturating_sub(start)
+    }
+
+    /// The `n`th entry, counting from zero, among those whose keys lie in
+    /// `range`, found by ranking both bounds and then indexing, in
+    /// O(log n).
+    pub fn nth_in_range<Q: ?Sized + Ord, R: RangeBounds<Q>>(&self, range: R, n: usize) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+    {
+        let (start, end) = self.range_positions(&range);
+        let i = start.checked_add(n)?;
+        if i < end { self.index(i) } else { None }
+    }
+
+    /// The first entry with a key of at least `key`, along with its
+    /// position.
+    pub fn lower_bound<Q: ?Sized + Ord>(&self, key: &Q) -> Option<(usize, &K, &V)>
+    where
+        K: Borrow<Q>,
+    {
+        let i = match self.rank(key) {
+            Ok(i) | Err(i) => i,
+        };
+        self.positioned(Some(i))
+    }
+
+    /// The last entry with a key of at most `key`, along with its position.
+    pub fn upper_bound<Q: ?Sized + Ord>(&self, key: &Q) -> Option<(usize, &K, &V)>
+    where
+        K: Borrow<Q>,
+    {
+        let i = match self.rank(key) {
+            Ok(i) => Some(i),
+            Err(i) => i.checked_sub(1),
+        };
+        self.positioned(i)
+    }
+
+    /// The last entry with a key less than `key`, along with its position.
+    pub fn predecessor<Q: ?Sized + Ord>(&self, key: &Q) -> Option<(usize, &K, &V)>
+    where
+        K: Borrow<Q>,
+    {
+        let i = match self.rank(key) {
+            Ok(i) | Err(i) => i.checked_sub(1),
+        };
+        self.positioned(i)
+    }
+
+    /// The first entry with a key greater than `key`, along with its
+    /// position.
+    pub fn successor<Q: ?Sized + Ord>(&self, key: &Q) -> Option<(usize, &K, &V)>
+    where
+        K: Borrow<Q>,
+    {
+        let i = match self.rank(key) {
+            Ok(i) => i + 1,
+            Err(i) => i,
+        };
+        self.positioned(Some(i))
+    }
+
+    fn positioned(&self, i: Option<usize>) -> Option<(usize, &K, &V)> {
+        let i = i?;
+        let (k, v) = self.index(i)?;
+        Some((i, k, v))
+    }
+
+    // The positions of the first entry in `range` and of the first entry
+    // past it. `start` exceeds `end` if the bounds are inverted.
+    pub(crate) fn range_positions<Q: ?Sized + Ord, R: RangeBounds<Q>>(&self, range: &R) -> (usize, usize)
+    where
+        K: Borrow<Q>,
+    {
+        let start = match range.start_bound() {
+            Bound::Unbounded => 0,
+            Bound::Included(k) => match self.key_position(k) {
+                Ok(i) | Err(i) => i,
+            },
+            Bound::Excluded(k) => match self.key_position(k) {
+                Ok(i) => i + 1,
+                Err(i) => i,
+            },
+        };
+
+        let end = match range.end_bound() {
+            Bound::Unbounded => self.len(),
+            Bound::Included(k) => match self.key_position(k) {
+                Ok(i) => i + 1,
+                Err(i) => i,
+            },
+            Bound::Excluded(k) => match self.key_position(k) {
+                Ok(i) | Err(i) => i,
+            },
+        };
+
+        (start, end)
+    }
+
+    pub fn range_step_by_index(&self, start: usize, step: usize) -> StepByIndex<'_, K, V> {
+        assert!(step > 0, "step must be non-zero");
+
+        let remaining = match self.len().checked_sub(start) {
+            None | Some(0) => 0,
+            Some(after) => (after - 1) / step + 1,
+        };
+
+        StepByIndex {
+            zipper: self.zipper().and_then(|z| z.advance(start)),
+            step,
+            remaining,
+        }
+    }
+
+    /// Iterates over every run of `width` consecutive entries, in order,
+    /// as with `slice::windows`. Each window is itself an iterator over its
+    /// entries, and moving to the next window costs a single step rather
+    /// than a fresh descent.
+    pub fn windows(&self, width: usize) -> Windows<'_, K, V> {
+        assert!(width > 0, "width must be non-zero");
+
+        Windows {
+            zipper: self.zipper(),
+            width,
+            remaining: (self.len() + 1).saturating_sub(width),
+        }
+    }
+
+    pub fn group_by<G: PartialEq, F: FnMut(&K) -> G>(&self, f: F) -> GroupBy<'_, K, V, G, F> {
+        GroupBy {
+            zipper: self.zipper(),
+            next_group: None,
+            f,
+        }
+    }
+
+    /// Keeps only the entries for which `f` returns true, rebuilding the
+    /// tree in one pass.
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        self.retain_indexed(|_i, k, v| f(k, v));
+    }
+
+    /// Keeps only the entries for which `f` returns true. `f` sees each
+    /// entry in order along with its index before any removals, and the
+    /// survivors are rebuilt bottom-up in one pass.
+    pub fn retain_indexed<F: FnMut(usize, &K, &mut V) -> bool>(&mut self, mut f: F) {
+        structural_span!("retain", len = self.len());
+        let entries = self.take_entries();
+        let mut builder = SortedMapBuilder::with_pool(mem::replace(&mut self.pool, NodePool::new()));
+
+        for (i, (k, mut v)) in entries.enumerate() {
+            if f(i, &k, &mut v) {
+                builder.push_unchecked(k, v);
+            }
+        }
+
+        *self = builder.finish();
+    }
+
+    /// Splits the map in one pass, sending each entry to the map at the
+    /// index `classify` returns. The result has one map per index up to
+    /// the largest returned, some of which may be empty. Each map is built
+    /// bottom-up from its sorted entries rather than by repeated insertion.
+    pub fn classify_into<F: FnMut(&K, &V) -> usize>(mut self, mut classify: F) -> Vec<Self> {
+        structural_span!("classify_into", len = self.len());
+        let mut builders: Vec<SortedMapBuilder<K, V>> = Vec::new();
+
+        for (k, v) in self.take_entries() {
+            let class = classify(&k, &v);
+            if builders.len() <= class {
+                builders.resize_with(class + 1, || SortedMapBuilder::with_pool(self.pool.sibling()));
+            }
+            builders[class].push_unchecked(k, v);
+        }
+
+        builders.into_iter().map(SortedMapBuilder::finish).collect()
+    }
+
+    /// Inserts an entry, returning the value it displaced if `key` was
+    /// already present. The original key is kept in that case.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.insert_entry(key, || value, true).0
+    }
+
+    // Inserts `key` with the value `value` makes, unless the key is present
+    // and `replace` is false. Returns the value displaced, if any, along
+    // with the entry's position and its value, all from a single descent.
+    pub(crate) fn insert_entry<F: FnOnce() -> V>(&mut self, key: K, value: F, replace: bool) -> (Option<V>, usize, &mut V) {
+        #[cfg(feature = "cow")]
+        self.unshare_path(Target::Key(&key));
+
+        let Some(root) = &mut self.root else {
+            let root = self.root.insert(self.pool.singleton(key, value()));
+            let Tree::Leaf(entries) = tree_mut(&mut root.tree) else { unreachable!() };
+            return (None, 0, &mut entries[0].1);
+        };
+
+        let (inserted, landed) = root.insert(&mut self.pool, key, value, replace);
+
+        let old = match inserted {
+            Inserted::Replaced(old) => Some(old),
+            Inserted::Present | Inserted::Grew => None,
+            Inserted::Split(right) => {
+                let left = self.root.take().unwrap();
+                structural_event!("root split", len = left.size + right.size);
+                self.root = Some(self.pool.branch2(left, right));
+                #[cfg(feature = "heatmap")]
+                self.heat.reshaped();
+                None
+            },
+        };
+
+        // SAFETY: `landed.value` points at the entry's value in its leaf.
+        // Nothing else refers to it, and the leaf's entries stay where they
+        // are for as long as `self` is mutably borrowed.
+        (old, landed.position, unsafe { &mut *landed.value.as_ptr() })
+    }
+
+    pub fn remove<Q: ?Sized + Ord>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        self.remove_target(Target::Key(key)).map(|(_k, v)| v)
+    }
+
+    /// Inserts an entry so that it lands at position `i`, for callers that
+    /// track positions rather than keys. Fails, handing the entry back, if
+    /// `key` does not fall strictly between the keys at `i - 1` and `i`.
+    ///
+    /// Panics if `i` is greater than the length.
+    pub fn insert_before_index(&mut self, i: usize, key: K, value: V) -> Result<(), OutOfOrder<K, V>> {
+        assert!(i <= self.len(), "index {} out of range for map of length {}", i, self.len());
+
+        let after_previous = i == 0 || self.index(i - 1).is_some_and(|(k, _v)| k < &key);
+        let before_next = self.index(i).is_none_or(|(k, _v)| &key < k);
+
+        if !(after_previous && before_next) {
+            return Err(OutOfOrder { key, value });
+        }
+
+        self.insert(key, value);
+        Ok(())
+    }
+
+    /// Removes and returns the entry at position `i`, descending by subtree
+    /// sizes rather than by key.
+    pub fn remove_at(&mut self, i: usize) -> Option<(K, V)> {
+        if i >= self.len() {
+            return None;
+        }
+
+        self.remove_target(Target::Index(i))
+    }
+
+    fn remove_target<Q: ?Sized + Ord>(&mut self, target: Target<'_, Q>) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+    {
+        #[cfg(feature = "cow")]
+        self.unshare_path(target);
+
+        let root = self.root.as_mut()?;
+
+        match root.remove(&mut self.pool, target) {
+            Removed::Missing => None,
+            Removed::Entry { entry, underflow, .. } => {
+                if root.size == 0 {
+                    let root = self.root.take().unwrap();
+                    self.pool.dismantle(root);
+                    #[cfg(feature = "heatmap")]
+                    self.heat.reshaped();
+                } else if underflow {
+                    structural_event!("root merged", len = root.size);
+                    #[cfg(feature = "heatmap")]
+                    self.heat.reshaped();
+                }
+                Some(entry)
+            },
+        }
+    }
+
+    /// The entry with the smallest key.
+    pub fn first_key_value(&self) -> Option<(&K, &V)> {
+        self.index(0)
+    }
+
+    /// The entry with the largest key.
+    pub fn last_key_value(&self) -> Option<(&K, &V)> {
+        self.index(self.len().checked_sub(1)?)
+    }
+
+    /// Removes and returns the entry with the smallest key.
+    pub fn pop_first(&mut self) -> Option<(K, V)> {
+        self.remove_at(0)
+    }
+
+    /// Removes and returns the entry with the largest key.
+    pub fn pop_last(&mut self) -> Option<(K, V)> {
+        self.remove_at(self.len().checked_sub(1)?)
+    }
+
+    /// Removes every entry, yielding them in key order. The map is left
+    /// empty even if the iterator is dropped early.
+    pub fn drain(&mut self) -> IntoIter<K, V> {
+        IntoIter {
+            remaining: self.len(),
+            entries: self.take_entries(),
+        }
+    }
+
+    pub fn zipper<'a>(&'a self) -> Option<TreeZipper<'a, K, V>> {
+        self.root.as_ref().map(|x| x.zipper())
+    }
+
+    // Hands the map's own pool to the entries, as nothing else will use it.
+    fn into_entries(mut self) -> IntoEntries<K, V> {
+        let mut entries = self.take_entries();
+        mem::swap(&mut entries.pool, &mut self.pool);
+        entries
+    }
+
+    fn take_entries(&mut self) -> IntoEntries<K, V> {
+        IntoEntries {
+            front: Vec::new(),
+            nodes: self.root.take().into_iter().collect(),
+            back: Vec::new(),
+            pool: self.pool.sibling(),
+        }
+    }
+
+    // An empty map drawing on the same arena as this one, if there is one.
+    fn empty_like(&self) -> Self {
+        IndexableSortedMap::with_pool(self.pool.sibling())
+    }
+}
+
+// Starts loading a child's subtree while its siblings' keys are compared,
+// so the descent is not stalled on the load once the child is chosen. Only
+// does anything with `perf` on x86_64.
+#[inline(always)]
+fn prefetch<K: Ord, V, M: Measure<K, V>>(node: &Node<K, V, M>) {
+    #[cfg(all(feature = "perf", target_arch = "x86_64"))]
+    {
+        use std::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        let p = &*node.tree as *const Tree<K, V, M> as *const i8;
+        // SAFETY: SSE is part of the x86_64 baseline, and a prefetch never
+        // faults, whatever the address.
+        unsafe { _mm_prefetch::<_MM_HINT_T0>(p) };
+    }
+
+    #[cfg(not(all(feature = "perf", target_arch = "x86_64")))]
+    let _ = node;
+}
+
+// Searches a leaf's entries for `key`, as `slice::binary_search`.
+fn find<K: Borrow<Q>, V, Q: ?Sized + Ord>(entries: &[(K, V)], key: &Q) -> Result<usize, usize> {
+    entries.binary_search_by(|(k, _v)| k.borrow().cmp(key))
+}
+
+impl<K: Ord, V, M: Measure<K, V>> Tree<K, V, M> {
+    fn first(&self) -> NonNull<(K, V)> {
+        match self {
+            // SAFETY: a vector's pointer is never null. It is taken with
+            // `as_ptr` rather than through a reference to the entries, so
+            // it stays valid as the vector is changed in place.
+            Tree::Leaf(entries) => unsafe { NonNull::new_unchecked(entries.as_ptr().cast_mut()) },
+            Tree::Branch2(left, _) | Tree::Branch3(left, _, _) => left.first,
+        }
+    }
+
+    // Sums the children's cached sizes, so this is O(1).
+    fn len(&self) -> usize {
+        match self {
+            Tree::Leaf(entries) => entries.len(),
+            Tree::Branch2(left, right) => left.len() + right.len(),
+            Tree::Branch3(left, middle, right) => left.len() + middle.len() + right.len(),
+        }
+    }
+
+    // Folds a leaf's entries, or combines the children's cached summaries.
+    fn summary(&self) -> M::Summary {
+        match self {
+            Tree::Leaf(entries) => entries
+                .iter()
+                .fold(M::empty(), |summary, (k, v)| M::combine(&summary, &M::measure(k, v))),
+            Tree::Branch2(left, right) => M::combine(&left.summary, &right.summary),
+            Tree::Branch3(left, middle, right) => {
+                M::combine(&M::combine(&left.summary, &middle.summary), &right.summary)
+            },
+        }
+    }
+}
+
+impl<'a, Q: ?Sized> Clone for Target<'a, Q> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, Q: ?Sized> Copy for Target<'a, Q> {}
+
+impl<'a, Q: ?Sized + Ord> Target<'a, Q> {
+    // Which of a branch's children holds the target, and the target as
+    // seen from within that child.
+    fn locate<K: Ord + Borrow<Q>, V, M: Measure<K, V>>(self, children: &[&Node<K, V, M>]) -> (usize, Self) {
+        match self {
+            Target::Key(key) => {
+                let child = children[1..]
+                    .iter()
+                    .take_while(|child| child.min_key().borrow() <= key)
+                    .count();
+                (child, self)
+            },
+            Target::Index(mut i) => {
+                let mut child = 0;
+                while i >= children[child].size {
+                    i -= children[child].size;
+                    child += 1;
+                }
+                (child, Target::Index(i))
+            },
+        }
+    }
+
+    // The target's slot among a leaf's entries, if it is there. A descent
+    // by position only ever reaches the right leaf.
+    fn find<K: Borrow<Q>, V>(self, entries: &[(K, V)]) -> Option<usize> {
+        match self {
+            Target::Key(key) => find(entries, key).ok(),
+            Target::Index(i) => Some(i),
+        }
+    }
+}
+
+impl<K: Ord, V, M: Measure<K, V>> Node<K, V, M> {
+    fn len(&self) -> usize {
+        self.size
+    }
+
+    fn min_key(&self) -> &K {
+        // SAFETY: `first` points at the first entry of the leftmost leaf in
+        // this subtree, which `&self` keeps alive and unchanged. Leaves are
+        // only ever empty on their way to being unlinked.
+        unsafe { &self.first.as_ref().0 }
+    }
+
+    fn refresh(&mut self) {
+        self.first = self.tree.first();
+        self.summary = self.tree.summary();
+    }
+
+    // Which child a descent towards `target` takes, and the target as seen
+    // from within that child.
+    fn locate<'a, Q: ?Sized + Ord>(&self, target: Target<'a, Q>) -> (usize, Target<'a, Q>)
+    where
+        K: Borrow<Q>,
+    {
+        match self.tree.as_ref() {
+            Tree::Leaf(_) => unreachable!(),
+            Tree::Branch2(left, right) => target.locate(&[left, right]),
+            Tree::Branch3(left, middle, right) => target.locate(&[left, middle, right]),
+        }
+    }
+
+    fn child_mut(&mut self, i: usize) -> &mut Node<K, V, M> {
+        match (tree_mut(&mut self.tree), i) {
+            (Tree::Branch2(left, _), 0) | (Tree::Branch3(left, _, _), 0) => left,
+            (Tree::Branch2(_, right), 1) | (Tree::Branch3(_, right, _), 1) => right,
+            (Tree::Branch3(_, _, right), 2) => right,
+            _ => unreachable!(),
+        }
+    }
+
+    // Removal and insertion recurse once per level. Every leaf of a 2-3
+    // tree is at the same depth and every branch has at least two
+    // children, so a tree of n entries is at most log2(n) + 1 levels deep:
+    // 64 frames at the very most, however the map was built.
+    //
+    // Both update the path in place: each node on it has its size adjusted
+    // by one, its checkpoint cleared and its first entry refreshed, and its
+    // tree is only reshaped when a child splits or underflows.
+    fn remove<Q: ?Sized + Ord>(&mut self, pool: &mut NodePool<K, V, M>, target: Target<'_, Q>) -> Removed<K, V>
+    where
+        K: Borrow<Q>,
+    {
+        if let Tree::Leaf(entries) = tree_mut(&mut self.tree) {
+            let Some(slot) = target.find(entries) else {
+                return Removed::Missing;
+            };
+
+            let entry = entries.remove(slot);
+            self.size -= 1;
+            self.checkpoint = 0;
+            self.refresh();
+
+            return Removed::Entry { entry, underflow: false };
+        }
+
+        let (i, target) = self.locate(target);
+
+        let entry = match self.child_mut(i).remove(pool, target) {
+            Removed::Missing => return Removed::Missing,
+            Removed::Entry { entry, underflow } => {
+                let collapsed = if underflow { self.rebalance(pool, i) } else { self.thin(pool, i) };
+
+                if collapsed {
+                    return Removed::Entry { entry, underflow: true };
+                }
+                entry
+            },
+        };
+
+        self.size -= 1;
+        self.checkpoint = 0;
+        self.refresh();
+
+        Removed::Entry { entry, underflow: false }
+    }
+
+    // Takes child `i` out of this node. A branch of two children is
+    // replaced by the one that remains, leaving it a level shorter, which
+    // is reported as `true`.
+    fn unlink(&mut self, pool: &mut NodePool<K, V, M>, i: usize) -> (Node<K, V, M>, bool) {
+        if let Tree::Branch3(_, _, _) = self.tree.as_ref() {
+            let child = rebuild(tree_mut(&mut self.tree), |tree| match (tree, i) {
+                (Tree::Branch3(a, b, c), 0) => (Tree::Branch2(b, c), a),
+                (Tree::Branch3(a, b, c), 1) => (Tree::Branch2(a, c), b),
+                (Tree::Branch3(a, b, c), _) => (Tree::Branch2(a, b), c),
+                _ => unreachable!(),
+            });
+            (child, false)
+        } else {
+            let child = rebuild(self, |node| match (pool.take(node.tree), i) {
+                (Tree::Branch2(a, b), 0) => (b, a),
+                (Tree::Branch2(a, b), _) => (a, b),
+                _ => unreachable!(),
+            });
+            (child, true)
+        }
+    }
+
+    // Restores child `i`, which has underflowed to a level below its
+    // siblings, by merging it into a neighbour with two children or by
+    // moving across the nearest child of a neighbour with three. Returns
+    // whether a merge left this node with a single child, so that it
+    // underflowed in turn.
+    fn rebalance(&mut self, pool: &mut NodePool<K, V, M>, i: usize) -> bool {
+        let j = if i == 0 { 1 } else { i - 1 };
+
+        if let Tree::Branch2(_, _) = self.child_mut(j).tree.as_ref() {
+            let (child, collapsed) = self.unlink(pool, i);
+            let neighbour = if collapsed { self } else { self.child_mut(j.min(i)) };
+
+            neighbour.size += child.size;
+            neighbour.checkpoint = 0;
+            #[cfg(feature = "cow")]
+            pool.unshare(neighbour);
+            rebuild(tree_mut(&mut neighbour.tree), |tree| match tree {
+                Tree::Branch2(x, y) if j > i => (Tree::Branch3(child, x, y), ()),
+                Tree::Branch2(x, y) => (Tree::Branch3(x, y, child), ()),
+                _ => unreachable!(),
+            });
+            neighbour.refresh();
+
+            collapsed
+        } else {
+            let neighbour = self.child_mut(j);
+            #[cfg(feature = "cow")]
+            pool.unshare(neighbour);
+            let moved = rebuild(tree_mut(&mut neighbour.tree), |tree| match tree {
+                Tree::Branch3(x, y, z) if j > i => (Tree::Branch2(y, z), x),
+                Tree::Branch3(x, y, z) => (Tree::Branch2(x, y), z),
+                _ => unreachable!(),
+            });
+
+            neighbour.size -= moved.size;
+            neighbour.checkpoint = 0;
+            neighbour.refresh();
+
+            self.child_mut(i).pair(pool, moved, j < i);
+            false
+        }
+    }
+
+    // Folds child `i` into a neighbouring leaf if it is a leaf that has
+    // thinned to under a quarter full and the two fit in one, which always
+    // unlinks a leaf left empty. Returns whether that left this node with a
+    // single child, so that it underflowed.
+    fn thin(&mut self, pool: &mut NodePool<K, V, M>, i: usize) -> bool {
+        let j = if i == 0 { 1 } else { i - 1 };
+        let len = self.child_mut(i).size;
+        let fits = match self.child_mut(j).tree.as_ref() {
+            Tree::Leaf(entries) => len + entries.len() <= LEAF_CAPACITY,
+            _ => false,
+        };
+
+        if len >= LEAF_CAPACITY / 4 || !fits {
+            return false;
+        }
+
+        let (child, collapsed) = self.unlink(pool, i);
+        let neighbour = if collapsed { self } else { self.child_mut(j.min(i)) };
+        #[cfg(feature = "cow")]
+        pool.unshare(neighbour);
+
+        if let (Tree::Leaf(mut moved), Tree::Leaf(entries)) = (pool.take(child.tree), tree_mut(&mut neighbour.tree)) {
+            if j > i {
+                moved.append(entries);
+                mem::swap(entries, &mut moved);
+            } else {
+                entries.append(&mut moved);
+            }
+            pool.recycle(moved);
+        }
+
+        neighbour.size += len;
+        neighbour.checkpoint = 0;
+        neighbour.refresh();
+
+        collapsed
+    }
+
+    // Turns this node into a branch of itself and `other`, a subtree of the
+    // same height that belongs immediately before or after it.
+    fn pair(&mut self, pool: &mut NodePool<K, V, M>, other: Node<K, V, M>, other_first: bool) {
+        rebuild(self, |node| {
+            let (left, right) = if other_first { (other, node) } else { (node, other) };
+            (pool.branch2(left, right), ())
+        });
+    }
+
+    // Inserts `key` with the value `value` makes, unless the key is present
+    // and `replace` is false, in which case `value` is not called.
+    fn insert<F: FnOnce() -> V>(
+        &mut self,
+        pool: &mut NodePool<K, V, M>,
+        key: K,
+        value: F,
+        replace: bool,
+    ) -> (Inserted<K, V, M>, Landed<V>) {
+        if let Tree::Leaf(entries) = tree_mut(&mut self.tree) {
+            self.checkpoint = 0;
+
+            let (slot, inserted) = match find(entries, &key) {
+                Ok(slot) if replace => (slot, Inserted::Replaced(mem::replace(&mut entries[slot].1, value()))),
+                Ok(slot) => (slot, Inserted::Present),
+                Err(slot) => {
+                    entries.insert(slot, (key, value()));
+                    (slot, Inserted::Grew)
+                },
+            };
+
+            let mut right = (entries.len() > LEAF_CAPACITY).then(|| {
+                let mut right = pool.buffer();
+                right.extend(entries.drain(entries.len() / 2..));
+                right
+            });
+            let value = match &mut right {
+                Some(right) if slot >= entries.len() => NonNull::from(&mut right[slot - entries.len()].1),
+                _ => NonNull::from(&mut entries[slot].1),
+            };
+            self.size = entries.len();
+            self.refresh();
+
+            let inserted = match right {
+                None => inserted,
+                Some(right) => Inserted::Split(pool.leaf(right)),
+            };
+            return (inserted, Landed { position: slot, value });
+        }
+
+        let (i, _) = self.locate(Target::Key(&key));
+        let before: usize = (0..i).map(|j| self.child_mut(j).size).sum();
+
+        let (inserted, mut landed) = self.child_mut(i).insert(pool, key, value, replace);
+        landed.position += before;
+
+        let spill = match inserted {
+            Inserted::Replaced(_) | Inserted::Present => {
+                self.checkpoint = 0;
+                self.refresh();
+                return (inserted, landed);
+            },
+            Inserted::Grew => None,
+            Inserted::Split(new) => rebuild(tree_mut(&mut self.tree), |tree| match (tree, i) {
+                (Tree::Branch2(a, b), 0) => (Tree::Branch3(a, new, b), None),
+                (Tree::Branch2(a, b), _) => (Tree::Branch3(a, b, new), None),
+                (Tree::Branch3(a, b, c), 0) => (Tree::Branch2(a, new), Some((b, c))),
+                (Tree::Branch3(a, b, c), 1) => (Tree::Branch2(a, b), Some((new, c))),
+                (Tree::Branch3(a, b, c), _) => (Tree::Branch2(a, b), Some((c, new))),
+                (Tree::Leaf(_), _) => unreachable!(),
+            }),
+        };
+
+        self.size += 1;
+        self.checkpoint = 0;
+        self.refresh();
+
+        let inserted = match spill {
+            None => Inserted::Grew,
+            Some((c, d)) => {
+                let sibling = pool.branch2(c, d);
+                self.size -= sibling.size;
+                Inserted::Split(sibling)
+            },
+        };
+        (inserted, landed)
+    }
+}
+
+impl<K: Ord, V> Node<K, V> {
+    fn zipper<'a>(&'a self) -> TreeZipper<'a, K, V> {
+        let mut stack = Vec::new();
+        let mut focus = self;
+
+        loop {
+            match focus.tree.as_ref() {
+                Tree::Branch2(left, _right) => {
+                    stack.push(NodeContext {
+                        context: TreeContext::Branch2Left,
+                        node: focus,
+                    });
+                    focus = left;
+                },
+                Tree::Branch3(left, _middle, _right) => {
+                    stack.push(NodeContext {
+                        context: TreeContext::Branch3Left,
+                        node: focus,
+                    });
+                    focus = left;
+                },
+                Tree::Leaf(entries) => {
+                    return TreeZipper {
+                        stack,
+                        leaf: entries,
+                        slot: 0,
+                    };
+                }
+            }
+        }
+    }
 }
 
-struct Node<K: Ord + Clone, V> {
-    min_key: K,
-    size: usize,
-    tree: Box<Tree<K, V>>,
+// A node's tree, to edit in place. With `cow` it must not be shared with
+// a clone, which the edit paths see to by copying it first.
+fn tree_mut<K: Ord, V, M: Measure<K, V>>(tree: &mut Shared<Tree<K, V, M>>) -> &mut Tree<K, V, M> {
+    #[cfg(not(feature = "cow"))]
+    {
+        tree
+    }
+    #[cfg(feature = "cow")]
+    {
+        std::sync::Arc::get_mut(tree).expect("edited a tree still shared with a clone")
+    }
 }
 
-enum TreeContext {
-    Branch2Left,
-    Branch2Right,
-    Branch3Left,
-    Branch3Middle,
-    Branch3Right,
-}
+// Replaces `*slot` with a value built from its current one, which is moved
+// out meanwhile. The callers' `f` only moves nodes around, but should it
+// unwind the process aborts rather than let `*slot` be dropped twice.
+fn rebuild<T, R>(slot: &mut T, f: impl FnOnce(T) -> (T, R)) -> R {
+    struct Abort;
 
-struct NodeContext<'a, K: Ord + Clone, V> {
-    context: TreeContext,
-    node: &'a Node<K, V>,
-}
+    impl Drop for Abort {
+        fn drop(&mut self) {
+            std::process::abort();
+        }
+    }
 
-pub struct TreeZipper<'a, K: Ord + Clone, V> {
-    stack: Vec<NodeContext<'a, K, V>>,
-    focus: (&'a K, &'a V),
-}
+    let guard = Abort;
 
-enum InsertResult<K: Ord + Clone, V> {
-    SameDepth(Node<K, V>),
-    Overflow(Node<K, V>, Node<K, V>),
-}
+    // SAFETY: `*slot` is read out exactly once and written back before
+    // anything else can observe it. If `f` unwinds in between, `guard`
+    // aborts before the moved-out value could be used again.
+    let result = unsafe {
+        let (new, result) = f(ptr::read(slot));
+        ptr::write(slot, new);
+        result
+    };
 
-enum RemoveResult<K: Ord + Clone, V> {
-    SameDepth(Node<K, V>),
-    Underflow(Node<K, V>),
-    Empty,
+    mem::forget(guard);
+    result
 }
 
-impl<K: Ord+Clone, V> IndexableSortedMap<K, V> {
-    pub fn new() -> Self {
-        IndexableSortedMap { root: None }
+impl<K: Ord, V, M: Measure<K, V>> NodePool<K, V, M> {
+    fn new() -> Self {
+        NodePool {
+            spare: Vec::new(),
+            buffers: Vec::new(),
+            #[cfg(feature = "arena")]
+            arena: None,
+            #[cfg(feature = "cow")]
+            cloner: Default::default(),
+        }
     }
 
-    pub fn len(&self) -> usize {
-        match &self.root {
-            None => 0,
-            Some(node) => node.len(),
+    // An empty pool for another map, drawing on the same arena if there
+    // is one.
+    fn sibling(&self) -> Self {
+        NodePool {
+            spare: Vec::new(),
+            buffers: Vec::new(),
+            #[cfg(feature = "arena")]
+            arena: self.arena.clone(),
+            #[cfg(feature = "cow")]
+            cloner: self.cloner.clone(),
         }
     }
 
-    pub fn is_empty(&self) -> bool {
-        self.root.is_none()
-    }
+    // Leaves split in half when they overflow, so most are at least half
+    // full, and a 2-3 tree with n leaves has fewer than 2n nodes.
+    fn reserve(&mut self, additional: usize) {
+        let leaves = additional.div_ceil(LEAF_CAPACITY / 2);
+        let wanted = leaves.saturating_mul(2);
 
-    pub fn lookup(&self, key: &K) -> Option<&V> {
-        let mut zipper = self.zipper()?;
-        zipper = zipper.advance_to(key)?;
-        let (k, v) = zipper.into_focus();
+        if self.spare.len() < wanted {
+            self.spare.reserve_exact(wanted - self.spare.len());
 
-        if key == k {
-            Some(v)
-        } else {
-            None
+            while self.spare.len() < wanted {
+                self.spare.push(Shared::new_uninit());
+            }
         }
-    }
 
-    pub fn index(&self, i: usize) -> Option<(&K, &V)> {
-        let mut zipper = self.zipper()?;
-        zipper = zipper.advance(i)?;
-        Some(zipper.into_focus())
+        if self.buffers.len() < leaves {
+            self.buffers.reserve_exact(leaves - self.buffers.len());
+
+            while self.buffers.len() < leaves {
+                self.buffers.push(Vec::with_capacity(LEAF_CAPACITY + 1));
+            }
+        }
     }
 
-    pub fn insert(&mut self, key: K, value: V) {
-        let mut map = None;
-        mem::swap(&mut map, &mut self.root);
+    fn shrink_to_fit(&mut self) {
+        self.spare = Vec::new();
+        self.buffers = Vec::new();
+    }
 
-        match map {
-            None => {
-                self.root = Some(Node::singleton(key, value));
-            },
-            Some(node) => {
-                match node.insert(key, value) {
-                    InsertResult::SameDepth(new_node) => {
-                        self.root = Some(new_node);
-                    },
-                    InsertResult::Overflow(left, right) => {
-                        self.root = Some(Node::branch2(left, right));
-                    }
-                }
-            },
+    // Whether a freed allocation is worth holding on to: always if it
+    // came from an arena, otherwise only while there is room reserved for
+    // it.
+    fn keeps<T>(&self, spare: &Vec<T>) -> bool {
+        #[cfg(feature = "arena")]
+        if self.arena.is_some() {
+            return true;
         }
+
+        spare.len() < spare.capacity()
     }
 
-    pub fn remove(&mut self, key: &K) -> Option<V> {
-        let mut map = None;
-        mem::swap(&mut map, &mut self.root);
-
-        match map {
-            None => { None },
-            Some(node) => {
-                match node.remove(key) {
-                    (RemoveResult::SameDepth(new_node), result) => {
-                        self.root = Some(new_node);
-                        result
-                    },
-                    (RemoveResult::Underflow(new_node), result) => {
-                        self.root = Some(new_node);
-                        result
-                    },
-                    (RemoveResult::Empty, result) => {
-                        self.root = None;
-                        result
-                    },
-                }
+    fn alloc(&mut self, tree: Tree<K, V, M>) -> Shared<Tree<K, V, M>> {
+        #[cfg(feature = "arena")]
+        if self.spare.is_empty() {
+            self.restock();
+        }
+
+        #[cfg(not(feature = "cow"))]
+        match self.spare.pop() {
+            None => Box::new(tree),
+            Some(slot) => Box::write(slot, tree),
+        }
+        #[cfg(feature = "cow")]
+        match self.spare.pop() {
+            None => Shared::new(tree),
+            Some(mut slot) => {
+                // Spare slots are never shared, so this always succeeds.
+                Shared::get_mut(&mut slot).unwrap().write(tree);
+                // SAFETY: the slot was written just now.
+                unsafe { slot.assume_init() }
             },
         }
     }
 
-    pub fn zipper<'a>(&'a self) -> Option<TreeZipper<'a, K, V>> {
-        match &self.root {
-            None => { None },
-            Some(x) => { Some(x.zipper()) },
+    // An empty vector for a leaf's entries.
+    fn buffer(&mut self) -> Vec<(K, V)> {
+        #[cfg(feature = "arena")]
+        if self.buffers.is_empty() {
+            self.restock();
         }
-    }
-}
 
-impl<K: Ord+Clone, V> Tree<K, V> {
-    fn singleton(key: K, value: V) -> Tree<K, V> {
-        Tree::Leaf(key, value)
+        self.buffers.pop().unwrap_or_else(|| Vec::with_capacity(LEAF_CAPACITY + 1))
     }
 
-    fn min_key(&self) -> &K {
-        match self {
-            Tree::Leaf(k, _) => &k,
-            Tree::Branch2(left, _) => &left.min_key,
-            Tree::Branch3(left, _, _) => &left.min_key,
+    fn recycle(&mut self, mut buffer: Vec<(K, V)>) {
+        buffer.clear();
+
+        if buffer.capacity() > LEAF_CAPACITY && self.keeps(&self.buffers) {
+            self.buffers.push(buffer);
         }
     }
 
-    fn len(&self) -> usize {
-        match self {
-            Tree::Leaf(_, _) => 1,
-            Tree::Branch2(left, right) => left.len() + right.len(),
-            Tree::Branch3(left, middle, right) => left.len() + middle.len() + right.len(),
+    // Moves the tree out of its allocation, keeping the allocation for
+    // reuse if the pool has room for it. A tree a clone still holds is
+    // copied out instead, and left to the clone.
+    fn take(&mut self, tree: Shared<Tree<K, V, M>>) -> Tree<K, V, M> {
+        #[cfg(feature = "cow")]
+        let tree = {
+            let mut tree = tree;
+            if Shared::get_mut(&mut tree).is_none() {
+                return self.copy(&tree);
+            }
+            tree
+        };
+
+        let raw = Shared::into_raw(tree);
+
+        // SAFETY: `raw` comes from a live box that nothing else holds. Its
+        // contents are read out exactly once, after which the allocation
+        // is only ever treated as uninitialized.
+        let (tree, slot) = unsafe {
+            (ptr::read(raw), Shared::<MaybeUninit<Tree<K, V, M>>>::from_raw(raw as _))
+        };
+
+        if self.keeps(&self.spare) {
+            self.spare.push(slot);
         }
+
+        tree
     }
-}
 
-impl<K: Ord+Clone, V> Node<K, V> {
-    fn len(&self) -> usize {
-        self.size
+    // Drops every entry under `node`, giving back its allocations as it
+    // goes.
+    fn dismantle(&mut self, node: Node<K, V, M>) {
+        let mut nodes = vec![node];
+
+        while let Some(node) = nodes.pop() {
+            // A subtree a clone still holds is left to it. Should the
+            // clone let go meanwhile, the tree is dropped here instead.
+            #[cfg(feature = "cow")]
+            if Shared::strong_count(&node.tree) > 1 {
+                continue;
+            }
+
+            match self.take(node.tree) {
+                Tree::Leaf(entries) => self.recycle(entries),
+                Tree::Branch2(left, right) => nodes.extend([right, left]),
+                Tree::Branch3(left, middle, right) => nodes.extend([right, middle, left]),
+            }
+        }
     }
 
-    fn from_tree(tree: Tree<K, V>) -> Node<K, V> {
+    fn node(&mut self, tree: Tree<K, V, M>) -> Node<K, V, M> {
         Node {
-            min_key: tree.min_key().clone(),
+            first: tree.first(),
             size: tree.len(),
-            tree: Box::new(tree),
+            summary: tree.summary(),
+            checkpoint: 0,
+            lineage: 0,
+            tree: self.alloc(tree),
         }
     }
 
-    fn singleton(key: K, value: V) -> Node<K, V> {
-        Node::from_tree(Tree::singleton(key, value))
+    fn singleton(&mut self, key: K, value: V) -> Node<K, V, M> {
+        let mut entries = self.buffer();
+        entries.push((key, value));
+        self.leaf(entries)
+    }
+
+    fn leaf(&mut self, entries: Vec<(K, V)>) -> Node<K, V, M> {
+        self.node(Tree::Leaf(entries))
     }
 
-    fn branch2(left: Node<K, V>, right: Node<K, V>) -> Node<K, V> {
-        Node::from_tree(
+    fn branch2(&mut self, left: Node<K, V, M>, right: Node<K, V, M>) -> Node<K, V, M> {
+        self.node(
             Tree::Branch2(left, right)
         )
     }
 
-    fn branch3(left: Node<K, V>, middle: Node<K, V>, right: Node<K, V>) -> Node<K, V> {
-        Node::from_tree(
+    fn branch3(&mut self, left: Node<K, V, M>, middle: Node<K, V, M>, right: Node<K, V, M>) -> Node<K, V, M> {
+        self.node(
             Tree::Branch3(left, middle, right)
         )
     }
+}
 
-    fn branch4(a: Node<K, V>, b: Node<K, V>, c: Node<K, V>, d: Node<K, V>) -> Node<K, V> {
-        Node::branch2(Node::branch2(a, b), Node::branch2(c, d))
-    }
-
-    fn branch5(a: Node<K, V>, b: Node<K, V>, c: Node<K, V>, d: Node<K, V>, e: Node<K, V>) -> Node<K, V> {
-        Node::branch2(Node::branch2(a, b), Node::branch3(c, d, e))
+impl<'a, K: Ord, V> Clone for NodeContext<'a, K, V> {
+    fn clone(&self) -> Self {
+        NodeContext {
+            context: self.context,
+            node: self.node,
+        }
     }
+}
 
-    fn branch6(a: Node<K, V>, b: Node<K, V>, c: Node<K, V>, d: Node<K, V>, e: Node<K, V>, f: Node<K, V>) -> Node<K, V> {
-        Node::branch2(Node::branch3(a, b, c), Node::branch3(d, e, f))
+impl<'a, K: Ord, V> Clone for TreeZipper<'a, K, V> {
+    fn clone(&self) -> Self {
+        TreeZipper {
+            stack: self.stack.clone(),
+            leaf: self.leaf,
+            slot: self.slot,
+        }
     }
+}
 
-    fn branch7(a: Node<K, V>, b: Node<K, V>, c: Node<K, V>, d: Node<K, V>, e: Node<K, V>, f: Node<K, V>, g: Node<K, V>) -> Node<K, V> {
-        Node::branch3(Node::branch2(a, b), Node::branch2(c, d), Node::branch3(e, f, g))
+impl<'a, K: Ord, V> TreeZipper<'a, K, V> {
+    pub fn into_focus(self) -> (&'a K, &'a V) {
+        self.focused()
     }
 
-    fn merge1(u: Node<K, V>, x: Node<K, V>, y: Node<K, V>) -> Node<K, V> {
-        match (*x.tree, *y.tree) {
-            (Tree::Branch2(a, b), Tree::Branch2(c, d)) => Node::branch5(u, a, b, c, d),
-            (Tree::Branch2(a, b), Tree::Branch3(c, d, e)) => Node::branch6(u, a, b, c, d, e),
-            (Tree::Branch3(a, b, c), Tree::Branch2(d, e)) => Node::branch6(u, a, b, c, d, e),
-            (Tree::Branch3(a, b, c), Tree::Branch3(d, e, f)) => Node::branch7(u, a, b, c, d, e, f),
-            _ => unreachable!(),
-        }
+    pub fn focus(&self) -> (&K, &V) {
+        self.focused()
     }
 
-    fn merge2(x: Node<K, V>, u: Node<K, V>, y: Node<K, V>) -> Node<K, V> {
-        match (*x.tree, *y.tree) {
-            (Tree::Branch2(a, b), Tree::Branch2(c, d)) => Node::branch5(a, b, u, c, d),
-            (Tree::Branch2(a, b), Tree::Branch3(c, d, e)) => Node::branch6(a, b, u, c, d, e),
-            (Tree::Branch3(a, b, c), Tree::Branch2(d, e)) => Node::branch6(a, b, c, u, d, e),
-            (Tree::Branch3(a, b, c), Tree::Branch3(d, e, f)) => Node::branch7(a, b, c, u, d, e, f),
-            _ => unreachable!(),
-        }
+    pub(crate) fn focused(&self) -> (&'a K, &'a V) {
+        let (k, v) = &self.leaf[self.slot];
+        (k, v)
     }
 
-    fn merge3(x: Node<K, V>, y: Node<K, V>, u: Node<K, V>) -> Node<K, V> {
-        match (*x.tree, *y.tree) {
-            (Tree::Branch2(a, b), Tree::Branch2(c, d)) => Node::branch5(a, b, c, d, u),
-            (Tree::Branch2(a, b), Tree::Branch3(c, d, e)) => Node::branch6(a, b, c, d, e, u),
-            (Tree::Branch3(a, b, c), Tree::Branch2(d, e)) => Node::branch6(a, b, c, d, e, u),
-            (Tree::Branch3(a, b, c), Tree::Branch3(d, e, f)) => Node::branch7(a, b, c, d, e, f, u),
-            _ => unreachable!(),
-        }
+    /// The index of the focused entry.
+    pub fn position(&self) -> usize {
+        self.stack
+            .iter()
+            .map(|frame| match (frame.context, frame.node.tree.as_ref()) {
+                (TreeContext::Branch2Right, Tree::Branch2(left, _right)) => left.size,
+                (TreeContext::Branch3Middle, Tree::Branch3(left, _middle, _right)) => left.size,
+                (TreeContext::Branch3Right, Tree::Branch3(left, middle, _right)) => left.size + middle.size,
+                _ => 0,
+            })
+            .sum::<usize>()
+            + self.slot
     }
 
-    fn zipper<'a>(&'a self) -> TreeZipper<'a, K, V> {
-        let mut stack = Vec::new();
-        let mut focus = self;
-
-        loop {
-            match focus.tree.as_ref() {
-                Tree::Branch2(left, _right) => {
-                    stack.push(NodeContext {
-                        context: TreeContext::Branch2Left,
-                        node: &focus,
-                    });
-                    focus = &left;
-                },
-                Tree::Branch3(left, _middle, _right) => {
-                    stack.push(NodeContext {
-                        context: TreeContext::Branch3Left,
-                        node: &focus,
-                    });
-                    focus = &left;
-                },
-                Tree::Leaf(key, value) => {
-                    return TreeZipper {
-                        stack: stack,
-                        focus: (&key, &value),
-                    };
-                }
-            }
+    pub fn advance_to<Q: ?Sized + Ord>(mut self, k: &Q) -> Option<TreeZipper<'a, K, V>>
+    where
+        K: Borrow<Q>,
+    {
+        if self.focused().0.borrow() >= k {
+            return Some(self);
         }
-    }
 
-    pub fn remove(self, key: &K) -> (RemoveResult<K, V>, Option<V>) {
-        match *self.tree {
-            Tree::Leaf(lk, lv) => {
-                if &lk == key {
-                    (RemoveResult::Empty, Some(lv))
-                } else {
-                    (RemoveResult::SameDepth(Node::singleton(lk, lv)), None)
-                }
-            },
-            Tree::Branch2(left, right) => {
-                if key < &right.min_key {
-                    match left.remove(key) {
-                        (RemoveResult::Empty, result) => {
-                            (RemoveResult::Underflow(right), result)
-                        },
-                        (RemoveResult::SameDepth(new_left), result) => {
-                            (RemoveResult::SameDepth(Node::branch2(new_left, right)), result)
-                        },
-                        (RemoveResult::Underflow(new_left), result) => {
-                            match *right.tree {
-                                Tree::Leaf(_k, _v) => {
-                                    unreachable!()
-                                },
-                                Tree::Branch2(right_left, right_right) => {
-                                    (
-                                        RemoveResult::Underflow(
-                                            Node::branch3(new_left, right_left, right_right)
-                                        ),
-                                        result
-                                    )
-                                }
-                                Tree::Branch3(right_left, right_middle, right_right) => {
-                                    (
-                                        RemoveResult::SameDepth(
-                                            Node::branch4(
-                                                new_left,
-                                                right_left,
-                                                right_middle,
-                                                right_right,
-                                            )
-                                        ),
-                                        result
-                                    )
-                                },
-                            }
-                        },
-                    }
-                } else {
-                    match right.remove(key) {
-                        (RemoveResult::Empty, result) => {
-                            (RemoveResult::Underflow(left), result)
-                        },
-                        (RemoveResult::SameDepth(new_right), result) => {
-                            (RemoveResult::SameDepth(Node::branch2(left, new_right)), result)
-                        },
-                        (RemoveResult::Underflow(new_right), result) => {
-                            match *left.tree {
-                                Tree::Leaf(_k, _v) => {
-                                    unreachable!()
-                                },
-                                Tree::Branch2(left_left, left_right) => {
-                                    (
-                                        RemoveResult::Underflow(
-                                            Node::branch3(left_left, left_right, new_right)
-                                        ),
-                                        result
-                                    )
-                                }
-                                Tree::Branch3(left_left, left_middle, left_right) => {
-                                    (
-                                        RemoveResult::SameDepth(
-                                            Node::branch4(
-                                                left_left,
-                                                left_middle,
-                                                left_right,
-                                                new_right,
-                                            )
-                                        ),
-                                        result
-                                    )
-                                },
-                            }
-                        },
-                    }
-                }
-            },
-            Tree::Branch3(left, middle, right) => {
-                if key < &right.min_key {
-                    match left.remove(key) {
-                        (RemoveResult::Empty, result) => {
-                            (
-                                RemoveResult::SameDepth(
-                                    Node::branch2(middle, right)
-                                ),
-                                result
-                            )
-                        },
-                        (RemoveResult::SameDepth(new_left), result) => {
-                            (
-                                RemoveResult::SameDepth(
-                                    Node::branch3(new_left, middle, right)
-                                ),
-                                result
-                            )
-                        },
-                        (RemoveResult::Underflow(new_left), result) => {
-                            (
-                                RemoveResult::SameDepth(
-                                    Node::merge1(new_left, middle, right)
-                                ),
-                                result
-                            )
-                        }
-                    }
-                } else if key < &middle.min_key {
-                    match middle.remove(key) {
-                        (RemoveResult::Empty, result) => {
-                            (
-                                RemoveResult::SameDepth(
-                                    Node::branch2(left, right)
-                                ),
-                                result
-                            )
-                        },
-                        (RemoveResult::SameDepth(new_middle), result) => {
-                            (
-                                RemoveResult::SameDepth(
-                                    Node::branch3(left, new_middle, right)
-                                ),
-                                result
-                            )
-                        },
-                        (RemoveResult::Underflow(new_middle), result) => {
-                            (
-                                RemoveResult::SameDepth(
-                                    Node::merge2(left, new_middle, right)
-                                ),
-                                result
-                            )
-                        }
-                    }
-                } else {
-                    match right.remove(key) {
-                        (RemoveResult::Empty, result) => {
-                            (
-                                RemoveResult::SameDepth(
-                                    Node::branch2(left, middle)
-                                ),
-                                result
-                            )
-                        },
-                        (RemoveResult::SameDepth(new_right), result) => {
-                            (
-                                RemoveResult::SameDepth(
-                                    Node::branch3(left, middle, new_right)
-                                ),
-                                result
-                            )
-                        },
-                        (RemoveResult::Underflow(new_right), result) => {
-                            (
-                                RemoveResult::SameDepth(
-                                    Node::merge3(left, middle, new_right)
-                                ),
-                                result
-                            )
-                        }
-                    }
-                }
-            },
+        if self.leaf[self.leaf.len() - 1].0.borrow() >= k {
+            self.slot = self.leaf.partition_point(|(key, _v)| key.borrow() < k);
+            return Some(self);
         }
-    }
 
-    fn insert(self, key: K, value: V) -> InsertResult<K, V> {
-        match *self.tree {
-            Tree::Leaf(lk, lv) => {
-                let mut left = (key, value);
-                let mut right = (lk, lv);
+        let focus = loop {
+            let context = self.stack.pop()?;
 
-                if right.0 < left.0 {
-                    mem::swap(&mut left, &mut right);
-                }
+            let bounded = match context.node.tree.as_ref() {
+                Tree::Branch2(_left, right) => right.min_key().borrow() > k,
+                Tree::Branch3(_left, _middle, right) => right.min_key().borrow() > k,
+                Tree::Leaf(_) => unreachable!(),
+            };
 
-                InsertResult::Overflow(
-                    Node::singleton(left.0, left.1),
-                    Node::singleton(right.0, right.1),
-                )
-            },
-            Tree::Branch2(left, right) => {
-                if key < right.min_key {
-                    match left.insert(key, value) {
-                        InsertResult::SameDepth(new_left) => {
-                            InsertResult::SameDepth(
-                                Node::branch2(new_left, right)
-                            )
-                        },
-                        InsertResult::Overflow(new_left, middle) => {
-                            InsertResult::SameDepth(
-                                Node::branch3(new_left, middle, right)
-                            )
-                        },
-                    }
-                } else {
-                    match right.insert(key, value) {
-                        InsertResult::SameDepth(new_right) => {
-                            InsertResult::SameDepth(
-                                Node::branch2(left, new_right)
-                            )
-                        },
-                        InsertResult::Overflow(middle, new_right) => {
-                            InsertResult::SameDepth(
-                                Node::branch3(left, middle, new_right)
-                            )
-                        },
-                    }
-                }
-            },
-            Tree::Branch3(left, middle, right) => {
-                if key < middle.min_key {
-                    match left.insert(key, value) {
-                        InsertResult::SameDepth(new_left) => {
-                            InsertResult::SameDepth(
-                                Node::branch3(new_left, middle, right)
-                            )
-                        },
-                        InsertResult::Overflow(new_left, new_middle) => {
-                            InsertResult::Overflow(
-                                Node::branch2(new_left, new_middle),
-                                Node::branch2(middle, right),
-                            )
-                        },
-                    }
-                } else if key < right.min_key {
-                    match middle.insert(key, value) {
-                        InsertResult::SameDepth(new_middle) => {
-                            InsertResult::SameDepth(
-                                Node::branch3(left, new_middle, right)
-                            )
-                        },
-                        InsertResult::Overflow(l_middle, r_middle) => {
-                            InsertResult::Overflow(
-                                Node::branch2(left, l_middle),
-                                Node::branch2(r_middle, right),
-                            )
-                        },
-                    }
-                } else {
-                    match right.insert(key, value) {
-                        InsertResult::SameDepth(new_right) => {
-                            InsertResult::SameDepth(
-                                Node::branch3(left, middle, new_right)
-                            )
-                        },
-                        InsertResult::Overflow(new_middle, new_right) => {
-                            InsertResult::Overflow(
-                                Node::branch2(left, middle),
-                                Node::branch2(new_middle, new_right),
-                            )
-                        },
-                    }
-                }
-            },
-        }
-    }
-}
+            if bounded || self.stack.is_empty() {
+                break context.node;
+            }
+        };
 
-impl<'a, K: Ord + Clone, V> TreeZipper<'a, K, V> {
-    pub fn into_focus(self) -> (&'a K, &'a V) {
-        self.focus
-    }
+        self.descend_to(focus, k);
 
-    pub fn focus(&self) -> (&K, &V) {
-        (&self.focus.0, &self.focus.1)
+        if self.focused().0.borrow() < k {
+            self.advance(1)
+        } else {
+            Some(self)
+        }
     }
 
-    pub fn advance_to(mut self, k: &K) -> Option<TreeZipper<'a, K, V>> {
-        if self.focus.0 >= k {
+    pub fn advance(mut self, mut n: usize) -> Option<TreeZipper<'a, K, V>> {
+        let after = self.leaf.len() - self.slot;
+        if n < after {
+            self.slot += n;
             return Some(self);
         }
 
+        // `n` now counts on from the first entry past the focused leaf.
+        n -= after;
+
         let mut focus = loop {
             match self.stack.pop() {
-                None => { return None; },
+                None => return None,
                 Some(context) => {
                     match context.context {
                         TreeContext::Branch2Left => {
@@ -552,8 +2086,10 @@ impl<'a, K: Ord + Clone, V> TreeZipper<'a, K, V> {
                                     node: context.node,
                                 });
 
-                                if &right.min_key >= k {
+                                if n < right.size {
                                     break right;
+                                } else {
+                                    n -= right.size;
                                 }
                             } else {
                                 unreachable!()
@@ -567,8 +2103,10 @@ impl<'a, K: Ord + Clone, V> TreeZipper<'a, K, V> {
                                     node: context.node,
                                 });
 
-                                if &middle.min_key >= k {
+                                if n < middle.size {
                                     break middle;
+                                } else {
+                                    n -= middle.size;
                                 }
                             } else {
                                 unreachable!()
@@ -581,8 +2119,10 @@ impl<'a, K: Ord + Clone, V> TreeZipper<'a, K, V> {
                                     node: context.node,
                                 });
 
-                                if &right.min_key >= k {
+                                if n < right.size {
                                     break right;
+                                } else {
+                                    n -= right.size;
                                 }
                             } else {
                                 unreachable!()
@@ -590,93 +2130,209 @@ impl<'a, K: Ord + Clone, V> TreeZipper<'a, K, V> {
                         },
                         TreeContext::Branch3Right => {},
                     }
+                },
+            }
+        };
+
+        loop {
+            match focus.tree.as_ref() {
+                Tree::Branch2(left, right) => {
+                    if n < left.size {
+                        self.stack.push(NodeContext {
+                            context: TreeContext::Branch2Left,
+                            node: focus,
+                        });
+
+                        focus = left;
+                    } else {
+                        n -= left.size;
+
+                        self.stack.push(NodeContext {
+                            context: TreeContext::Branch2Right,
+                            node: focus,
+                        });
+
+                        focus = right;
+                    }
+                },
+                Tree::Branch3(left, middle, right) => {
+                    if n < left.size {
+                        self.stack.push(NodeContext {
+                            context: TreeContext::Branch3Left,
+                            node: focus,
+                        });
+
+                        focus = left;
+                    } else {
+                        n -= left.size;
+
+                        if n < middle.size {
+                            self.stack.push(NodeContext {
+                                context: TreeContext::Branch3Middle,
+                                node: focus,
+                            });
+
+                            focus = middle;
+                        } else {
+                            n -= middle.size;
+
+                            self.stack.push(NodeContext {
+                                context: TreeContext::Branch3Right,
+                                node: focus,
+                            });
+
+                            focus = right;
+                        }
+                    }
+                },
+                Tree::Leaf(entries) => {
+                    self.leaf = entries;
+                    self.slot = n;
+                    break;
                 }
             }
+        }
+
+        Some(self)
+    }
+
+    /// Moves back to the last entry with a key of at most `k`, the mirror
+    /// image of `advance_to`. Returns `None` if every key is greater.
+    pub fn retreat_to<Q: ?Sized + Ord>(mut self, k: &Q) -> Option<TreeZipper<'a, K, V>>
+    where
+        K: Borrow<Q>,
+    {
+        if self.focused().0.borrow() <= k {
+            return Some(self);
+        }
+
+        if self.leaf[0].0.borrow() <= k {
+            self.slot = self.leaf.partition_point(|(key, _v)| key.borrow() <= k) - 1;
+            return Some(self);
+        }
+
+        // The lowest ancestor starting at or before `k` holds the target.
+        let focus = loop {
+            let context = self.stack.pop()?;
+
+            if context.node.min_key().borrow() <= k {
+                break context.node;
+            }
         };
 
+        self.descend_to(focus, k);
+        Some(self)
+    }
+
+    /// The next entry, as `advance(1)`.
+    pub fn next(self) -> Option<TreeZipper<'a, K, V>> {
+        self.advance(1)
+    }
+
+    /// The previous entry, as `retreat(1)`.
+    pub fn prev(self) -> Option<TreeZipper<'a, K, V>> {
+        self.retreat(1)
+    }
+
+    // Descends from `focus`, whose first key is at most `k`, to the last
+    // entry under it with a key of at most `k`.
+    fn descend_to<Q: ?Sized + Ord>(&mut self, mut focus: &'a Node<K, V>, k: &Q)
+    where
+        K: Borrow<Q>,
+    {
         loop {
             match focus.tree.as_ref() {
                 Tree::Branch2(left, right) => {
-                    if &left.min_key >= k {
+                    if right.min_key().borrow() <= k {
                         self.stack.push(NodeContext {
-                            context: TreeContext::Branch2Left,
-                            node: &focus,
+                            context: TreeContext::Branch2Right,
+                            node: focus,
                         });
 
-                        focus = &left;
+                        focus = right;
                     } else {
                         self.stack.push(NodeContext {
-                            context: TreeContext::Branch2Right,
-                            node: &focus,
+                            context: TreeContext::Branch2Left,
+                            node: focus,
                         });
 
-                        focus = &right;
+                        focus = left;
                     }
                 },
                 Tree::Branch3(left, middle, right) => {
-                    if &left.min_key >= k {
+                    if right.min_key().borrow() <= k {
                         self.stack.push(NodeContext {
-                            context: TreeContext::Branch3Left,
-                            node: &focus,
+                            context: TreeContext::Branch3Right,
+                            node: focus,
                         });
 
-                        focus = &left;
-                    } else if &middle.min_key >= k {
+                        focus = right;
+                    } else if middle.min_key().borrow() <= k {
                         self.stack.push(NodeContext {
                             context: TreeContext::Branch3Middle,
-                            node: &focus,
+                            node: focus,
                         });
 
-                        focus = &middle;
+                        focus = middle;
                     } else {
                         self.stack.push(NodeContext {
-                            context: TreeContext::Branch3Right,
-                            node: &focus,
+                            context: TreeContext::Branch3Left,
+                            node: focus,
                         });
 
-                        focus = &right;
+                        focus = left;
                     }
                 },
-                Tree::Leaf(key, value) => {
-                    self.focus = (&key, &value);
+                Tree::Leaf(entries) => {
+                    self.leaf = entries;
+                    self.slot = entries.partition_point(|(key, _v)| key.borrow() <= k) - 1;
                     break;
                 }
             }
         }
+    }
 
-        Some(self)
+    /// Moves forward for a positive `delta` and back for a negative one.
+    pub fn move_by(self, delta: isize) -> Option<TreeZipper<'a, K, V>> {
+        if delta < 0 {
+            self.retreat(delta.unsigned_abs())
+        } else {
+            self.advance(delta as usize)
+        }
     }
 
-    pub fn advance(mut self, mut n: usize) -> Option<TreeZipper<'a, K, V>> {
-        if n == 0 {
+    /// Moves back by `n` entries, the mirror image of `advance`.
+    pub fn retreat(mut self, mut n: usize) -> Option<TreeZipper<'a, K, V>> {
+        if n <= self.slot {
+            self.slot -= n;
             return Some(self);
-        } else {
-            n -= 1;
         }
 
+        n -= self.slot + 1;
+
         let mut focus = loop {
             match self.stack.pop() {
                 None => return None,
                 Some(context) => {
                     match context.context {
-                        TreeContext::Branch2Left => {
-                            if let Tree::Branch2(_left, right) = context.node.tree.as_ref() {
+                        TreeContext::Branch2Right => {
+                            if let Tree::Branch2(left, _right) = context.node.tree.as_ref() {
                                 self.stack.push(NodeContext {
-                                    context: TreeContext::Branch2Right,
+                                    context: TreeContext::Branch2Left,
                                     node: context.node,
                                 });
 
-                                if n < right.size {
-                                    break right;
+                                if n < left.size {
+                                    break left;
                                 } else {
-                                    n -= right.size;
+                                    n -= left.size;
                                 }
                             } else {
                                 unreachable!()
                             }
                         },
-                        TreeContext::Branch2Right => {},
-                        TreeContext::Branch3Left => {
+                        TreeContext::Branch2Left => {},
+                        TreeContext::Branch3Right => {
                             if let Tree::Branch3(_left, middle, _right) = context.node.tree.as_ref() {
                                 self.stack.push(NodeContext {
                                     context: TreeContext::Branch3Middle,
@@ -693,80 +2349,82 @@ impl<'a, K: Ord + Clone, V> TreeZipper<'a, K, V> {
                             }
                         },
                         TreeContext::Branch3Middle => {
-                            if let Tree::Branch3(_left, _middle, right) = context.node.tree.as_ref() {
+                            if let Tree::Branch3(left, _middle, _right) = context.node.tree.as_ref() {
                                 self.stack.push(NodeContext {
-                                    context: TreeContext::Branch3Right,
+                                    context: TreeContext::Branch3Left,
                                     node: context.node,
                                 });
 
-                                if n < right.size {
-                                    break right;
+                                if n < left.size {
+                                    break left;
                                 } else {
-                                    n -= right.size;
+                                    n -= left.size;
                                 }
                             } else {
                                 unreachable!()
                             }
                         },
-                        TreeContext::Branch3Right => {},
+                        TreeContext::Branch3Left => {},
                     }
                 },
             }
         };
 
+        // `n` now counts back from the last entry under `focus`.
         loop {
             match focus.tree.as_ref() {
                 Tree::Branch2(left, right) => {
-                    if n < left.size {
+                    if n < right.size {
                         self.stack.push(NodeContext {
-                            context: TreeContext::Branch2Left,
-                            node: &focus,
+                            context: TreeContext::Branch2Right,
+                            node: focus,
                         });
 
-                        focus = &left;
+                        focus = right;
                     } else {
-                        n -= left.size;
+                        n -= right.size;
 
                         self.stack.push(NodeContext {
-                            context: TreeContext::Branch2Right,
-                            node: &focus,
+                            context: TreeContext::Branch2Left,
+                            node: focus,
                         });
 
-                        focus = &right;
+                        focus = left;
                     }
                 },
                 Tree::Branch3(left, middle, right) => {
-                    if n < left.size {
+                    if n < right.size {
                         self.stack.push(NodeContext {
-                            context: TreeContext::Branch3Left,
-                            node: &focus,
+                            context: TreeContext::Branch3Right,
+                            node: focus,
                         });
 
-                        focus = &left;
+                        focus = right;
                     } else {
-                        n -= left.size;
+                        n -= right.size;
 
                         if n < middle.size {
                             self.stack.push(NodeContext {
                                 context: TreeContext::Branch3Middle,
-                                node: &focus,
+                                node: focus,
                             });
 
-                            focus = &middle;
+                            focus = middle;
                         } else {
                             n -= middle.size;
 
                             self.stack.push(NodeContext {
-                                context: TreeContext::Branch3Right,
-                                node: &focus,
+                                context: TreeContext::Branch3Left,
+                                node: focus,
                             });
 
-                            focus = &right;
+                            focus = left;
                         }
                     }
                 },
-                Tree::Leaf(key, value) => {
-                    self.focus = (&key, &value);
+                Tree::Leaf(entries) => {
+                    self.leaf = entries;
+                    self.slot = entries.len() - 1 - n;
                     break;
                 }
             }
@@ -775,3 +2433,439 @@ impl<'a, K: Ord + Clone, V> TreeZipper<'a, K, V> {
         Some(self)
     }
 }
+
+impl<K: Ord, V> IntoIterator for IndexableSortedMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter {
+            remaining: self.len(),
+            entries: self.into_entries(),
+        }
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a IndexableSortedMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Iter<'a, K, V> {
+        self.iter()
+    }
+}
+
+impl<'a, K: Ord, V> IntoIterator for &'a mut IndexableSortedMap<K, V> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        self.iter_mut()
+    }
+}
+
+impl<K: Ord, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        let entry = self.entries.next()?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K: Ord, V> DoubleEndedIterator for IntoIter<K, V> {
+    fn next_back(&mut self) -> Option<(K, V)> {
+        let entry = self.entries.next_back()?;
+        self.remaining -= 1;
+        Some(entry)
+    }
+}
+
+impl<K: Ord, V> ExactSizeIterator for IntoIter<K, V> {}
+
+impl<'a, K: Ord, V> Iterator for IterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((k, v)) = self.front.next() {
+                self.remaining -= 1;
+                return Some((k, v));
+            }
+
+            let Some(node) = self.nodes.pop_front() else {
+                let (k, v) = self.back.next()?;
+                self.remaining -= 1;
+                return Some((k, v));
+            };
+            node.checkpoint = 0;
+
+            match tree_mut(&mut node.tree) {
+                Tree::Leaf(entries) => {
+                    self.front = entries.iter_mut();
+                },
+                Tree::Branch2(left, right) => {
+                    self.nodes.push_front(right);
+                    self.nodes.push_front(left);
+                },
+                Tree::Branch3(left, middle, right) => {
+                    self.nodes.push_front(right);
+                    self.nodes.push_front(middle);
+                    self.nodes.push_front(left);
+                },
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K: Ord, V> DoubleEndedIterator for IterMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((k, v)) = self.back.next_back() {
+                self.remaining -= 1;
+                return Some((k, v));
+            }
+
+            let Some(node) = self.nodes.pop_back() else {
+                let (k, v) = self.front.next_back()?;
+                self.remaining -= 1;
+                return Some((k, v));
+            };
+            node.checkpoint = 0;
+
+            match tree_mut(&mut node.tree) {
+                Tree::Leaf(entries) => {
+                    self.back = entries.iter_mut();
+                },
+                Tree::Branch2(left, right) => {
+                    self.nodes.push_back(left);
+                    self.nodes.push_back(right);
+                },
+                Tree::Branch3(left, middle, right) => {
+                    self.nodes.push_back(left);
+                    self.nodes.push_back(middle);
+                    self.nodes.push_back(right);
+                },
+            }
+        }
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for IterMut<'a, K, V> {}
+
+impl<K: Ord, V> Iterator for IntoEntries<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        loop {
+            if let Some(entry) = self.front.pop() {
+                return Some(entry);
+            }
+
+            let Some(node) = self.nodes.pop_front() else {
+                // Only what the back end left of its leaf remains.
+                mem::swap(&mut self.front, &mut self.back);
+                self.front.reverse();
+                return self.front.pop();
+            };
+
+            match self.pool.take(node.tree) {
+                Tree::Leaf(mut entries) => {
+                    entries.reverse();
+                    let emptied = mem::replace(&mut self.front, entries);
+                    self.pool.recycle(emptied);
+                },
+                Tree::Branch2(left, right) => {
+                    self.nodes.push_front(right);
+                    self.nodes.push_front(left);
+                },
+                Tree::Branch3(left, middle, right) => {
+                    self.nodes.push_front(right);
+                    self.nodes.push_front(middle);
+                    self.nodes.push_front(left);
+                },
+            }
+        }
+    }
+}
+
+// Whatever has not been visited is dismantled into the pool.
+impl<K: Ord, V> Drop for IntoEntries<K, V> {
+    fn drop(&mut self) {
+        self.pool.recycle(mem::take(&mut self.front));
+        self.pool.recycle(mem::take(&mut self.back));
+
+        for node in mem::take(&mut self.nodes) {
+            self.pool.dismantle(node);
+        }
+    }
+}
+
+impl<K: Ord, V> DoubleEndedIterator for IntoEntries<K, V> {
+    fn next_back(&mut self) -> Option<(K, V)> {
+        loop {
+            if let Some(entry) = self.back.pop() {
+                return Some(entry);
+            }
+
+            let Some(node) = self.nodes.pop_back() else {
+                // Only what the front end left of its leaf remains.
+                mem::swap(&mut self.front, &mut self.back);
+                self.back.reverse();
+                return self.back.pop();
+            };
+
+            match self.pool.take(node.tree) {
+                Tree::Leaf(entries) => {
+                    let emptied = mem::replace(&mut self.back, entries);
+                    self.pool.recycle(emptied);
+                },
+                Tree::Branch2(left, right) => {
+                    self.nodes.push_back(left);
+                    self.nodes.push_back(right);
+                },
+                Tree::Branch3(left, middle, right) => {
+                    self.nodes.push_back(left);
+                    self.nodes.push_back(middle);
+                    self.nodes.push_back(right);
+                },
+            }
+        }
+    }
+}
+
+impl<'a, K: Ord, V, G: PartialEq, F: FnMut(&K) -> G> Iterator for GroupBy<'a, K, V, G, F> {
+    type Item = (G, Group<'a, K, V>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.zipper.take()?;
+        let group = match self.next_group.take() {
+            None => (self.f)(start.focused().0),
+            Some(group) => group,
+        };
+
+        let mut remaining = 1;
+        let mut zipper = start.clone().advance(1);
+
+        while let Some(z) = zipper {
+            let next_group = (self.f)(z.focused().0);
+
+            if next_group == group {
+                remaining += 1;
+                zipper = z.advance(1);
+            } else {
+                self.zipper = Some(z);
+                self.next_group = Some(next_group);
+                break;
+            }
+        }
+
+        Some((group, Group { zipper: Some(start), remaining }))
+    }
+}
+
+impl<'a, K: Ord, V> Iterator for Group<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let zipper = self.zipper.take()?;
+        let focus = zipper.focused();
+
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            self.zipper = zipper.advance(1);
+        }
+
+        Some(focus)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for Group<'a, K, V> {}
+
+impl<'a, K: Ord, V> Iterator for StepByIndex<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let zipper = self.zipper.take()?;
+        let focus = zipper.focused();
+
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            self.zipper = zipper.advance(self.step);
+        }
+
+        Some(focus)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for StepByIndex<'a, K, V> {}
+
+impl<'a, K: Ord, V> Iterator for Windows<'a, K, V> {
+    type Item = Group<'a, K, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let start = self.zipper.take()?;
+
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            self.zipper = start.clone().advance(1);
+        }
+
+        Some(Group { zipper: Some(start), remaining: self.width })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for Windows<'a, K, V> {}
+
+impl<'a, K: Ord, V> Iterator for Range<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let zipper = self.front.take()?;
+        let focus = zipper.focused();
+
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            self.front = zipper.advance(1);
+        }
+
+        Some(focus)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K: Ord, V> DoubleEndedIterator for Range<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+
+        let zipper = self.back.take()?;
+        let focus = zipper.focused();
+
+        self.remaining -= 1;
+        if self.remaining > 0 {
+            self.back = zipper.retreat(1);
+        }
+
+        Some(focus)
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for Range<'a, K, V> {}
+
+impl<'a, K: Ord, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.range.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<'a, K: Ord, V> DoubleEndedIterator for Iter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.range.next_back()
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for Iter<'a, K, V> {}
+
+impl<'a, K: Ord, V> Iterator for Keys<'a, K, V> {
+    type Item = &'a K;
+
+    fn next(&mut self) -> Option<&'a K> {
+        self.iter.next().map(|(k, _v)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, K: Ord, V> DoubleEndedIterator for Keys<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a K> {
+        self.iter.next_back().map(|(k, _v)| k)
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for Keys<'a, K, V> {}
+
+impl<'a, K: Ord, V> Iterator for Values<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.iter.next().map(|(_k, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, K: Ord, V> DoubleEndedIterator for Values<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a V> {
+        self.iter.next_back().map(|(_k, v)| v)
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for Values<'a, K, V> {}
+
+impl<'a, K: Ord, V> Iterator for ValuesMut<'a, K, V> {
+    type Item = &'a mut V;
+
+    fn next(&mut self) -> Option<&'a mut V> {
+        self.iter.next().map(|(_k, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, K: Ord, V> DoubleEndedIterator for ValuesMut<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a mut V> {
+        self.iter.next_back().map(|(_k, v)| v)
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for ValuesMut<'a, K, V> {}
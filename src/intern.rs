@@ -0,0 +1,136 @@
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// A shared string key. Interned copies of the same text point at one
+/// allocation, so the keys of many maps cost a reference count rather
+/// than a `String`, and equal keys compare by pointer.
+#[derive(Clone)]
+pub struct InternedStr(Arc<str>);
+
+/// A set of interned strings. Most users can rely on the process-wide one
+/// behind `InternedStr::new`; a separate interner keeps an unrelated
+/// vocabulary from growing the global table.
+#[derive(Default)]
+pub struct Interner {
+    strings: Mutex<HashSet<Arc<str>>>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn intern(&self, s: &str) -> InternedStr {
+        let mut strings = self.strings.lock().unwrap();
+
+        if let Some(existing) = strings.get(s) {
+            return InternedStr(existing.clone());
+        }
+
+        let string: Arc<str> = Arc::from(s);
+        strings.insert(string.clone());
+        InternedStr(string)
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forgets every string no longer referenced outside the interner.
+    pub fn purge(&self) {
+        self.strings.lock().unwrap().retain(|s| Arc::strong_count(s) > 1);
+    }
+}
+
+impl InternedStr {
+    /// Interns `s` in the process-wide interner.
+    pub fn new(s: &str) -> Self {
+        InternedStr::global().intern(s)
+    }
+
+    pub fn global() -> &'static Interner {
+        static GLOBAL: OnceLock<Interner> = OnceLock::new();
+        GLOBAL.get_or_init(Interner::new)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+// Strings from different interners may be equal without sharing an
+// allocation, so a pointer mismatch falls back to comparing the text.
+impl PartialEq for InternedStr {
+    fn eq(&self, other: &Self) -> bool {
+        self.ptr_eq(other) || self.0 == other.0
+    }
+}
+
+impl Eq for InternedStr {}
+
+impl PartialOrd for InternedStr {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for InternedStr {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self.ptr_eq(other) {
+            Ordering::Equal
+        } else {
+            self.0.cmp(&other.0)
+        }
+    }
+}
+
+impl Hash for InternedStr {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl Deref for InternedStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for InternedStr {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for InternedStr {
+    fn from(s: &str) -> Self {
+        InternedStr::new(s)
+    }
+}
+
+impl fmt::Debug for InternedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+impl fmt::Display for InternedStr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
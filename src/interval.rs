@@ -0,0 +1,144 @@
+use std::ops;
+
+use crate::{AugmentedIter, AugmentedSearch, AugmentedSortedMap, Measure};
+
+/// A map from half-open intervals `[start, end)` to values, ordered by
+/// start and then end, that finds every interval containing a point or
+/// overlapping a range in O(k log n) for k results. Each subtree keeps the
+/// largest end point within it, so subtrees that end too early to reach
+/// the query are skipped whole.
+pub struct IntervalMap<K: Ord + Clone, V> {
+    map: AugmentedSortedMap<(K, K), V, MaxEnd>,
+}
+
+// Summarizes a subtree by the largest end point of its intervals.
+struct MaxEnd;
+
+impl<K: Ord + Clone, V> Measure<(K, K), V> for MaxEnd {
+    type Summary = Option<K>;
+
+    fn measure(key: &(K, K), _value: &V) -> Option<K> {
+        Some(key.1.clone())
+    }
+
+    fn combine(left: &Option<K>, right: &Option<K>) -> Option<K> {
+        left.clone().max(right.clone())
+    }
+
+    fn empty() -> Option<K> {
+        None
+    }
+}
+
+// Whether a subtree's largest end point reaches far enough to matter.
+type Reaches<'a, K> = Box<dyn FnMut(&Option<K>) -> bool + 'a>;
+
+pub struct Overlapping<'a, K: Ord + Clone, V> {
+    search: AugmentedSearch<'a, (K, K), V, MaxEnd, Reaches<'a, K>>,
+}
+
+pub struct IntervalIter<'a, K: Ord + Clone, V> {
+    iter: AugmentedIter<'a, (K, K), V, MaxEnd>,
+}
+
+impl<K: Ord + Clone, V> Default for IntervalMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone, V> IntervalMap<K, V> {
+    pub fn new() -> Self {
+        IntervalMap { map: AugmentedSortedMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Inserts an interval, returning the value it displaced if the same
+    /// interval was already present.
+    ///
+    /// Panics if the interval is empty.
+    pub fn insert(&mut self, interval: ops::Range<K>, value: V) -> Option<V> {
+        assert!(interval.start < interval.end, "interval is empty");
+        self.map.insert((interval.start, interval.end), value)
+    }
+
+    pub fn get(&self, interval: &ops::Range<K>) -> Option<&V> {
+        self.map.lookup(&bounds(interval))
+    }
+
+    pub fn remove(&mut self, interval: &ops::Range<K>) -> Option<V> {
+        self.map.remove(&bounds(interval))
+    }
+
+    pub fn index(&self, i: usize) -> Option<(ops::Range<&K>, &V)> {
+        self.map.index(i).map(|((start, end), v)| (start..end, v))
+    }
+
+    /// Iterates over the intervals containing `point`, in order.
+    pub fn stab(&self, point: &K) -> Overlapping<'_, K, V> {
+        let end = self.map.partition_point(|(start, _end)| start <= point);
+        let point = point.clone();
+        self.search(end, Box::new(move |max_end| max_end.as_ref().is_some_and(|max_end| max_end > &point)))
+    }
+
+    /// Iterates over the intervals that share at least one point with
+    /// `range`, in order.
+    pub fn overlapping(&self, range: ops::Range<K>) -> Overlapping<'_, K, V> {
+        let end = if range.start < range.end {
+            self.map.partition_point(|(start, _end)| start < &range.end)
+        } else {
+            0
+        };
+        let from = range.start;
+        self.search(end, Box::new(move |max_end| max_end.as_ref().is_some_and(|max_end| max_end > &from)))
+    }
+
+    pub fn iter(&self) -> IntervalIter<'_, K, V> {
+        IntervalIter { iter: self.map.iter() }
+    }
+
+    // Searches the intervals starting before position `end` for those
+    // that `reaches` says extend far enough.
+    fn search<'a>(&'a self, end: usize, reaches: Reaches<'a, K>) -> Overlapping<'a, K, V> {
+        Overlapping { search: self.map.search_by_index(0..end, reaches) }
+    }
+}
+
+fn bounds<K: Clone>(interval: &ops::Range<K>) -> (K, K) {
+    (interval.start.clone(), interval.end.clone())
+}
+
+impl<'a, K: Ord + Clone, V> Iterator for Overlapping<'a, K, V> {
+    type Item = (ops::Range<&'a K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.search.next().map(|((start, end), v)| (start..end, v))
+    }
+}
+
+impl<'a, K: Ord + Clone, V> Iterator for IntervalIter<'a, K, V> {
+    type Item = (ops::Range<&'a K>, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|((start, end), v)| (start..end, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, K: Ord + Clone, V> DoubleEndedIterator for IntervalIter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(|((start, end), v)| (start..end, v))
+    }
+}
+
+impl<'a, K: Ord + Clone, V> ExactSizeIterator for IntervalIter<'a, K, V> {}
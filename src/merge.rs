@@ -0,0 +1,368 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::mem;
+use std::vec;
+
+use crate::{IndexableSortedMap, NodePool, SortedMapBuilder};
+
+/// The entries `insert_all` rejected because their keys were already
+/// present, in the order they were supplied.
+pub struct Conflicts<K, V> {
+    entries: Vec<(K, V)>,
+}
+
+// The next entry of one input. Ordered so that `BinaryHeap`, a max-heap,
+// yields the smallest key first and, among equal keys, the earliest input.
+struct Head<K, V> {
+    key: K,
+    value: V,
+    source: usize,
+}
+
+impl<K: Ord, V> PartialEq for Head<K, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<K: Ord, V> Eq for Head<K, V> {}
+
+impl<K: Ord, V> PartialOrd for Head<K, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord, V> Ord for Head<K, V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.key.cmp(&self.key).then(other.source.cmp(&self.source))
+    }
+}
+
+impl<K, V> Conflicts<K, V> {
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, (K, V)> {
+        self.entries.iter()
+    }
+
+    pub fn into_vec(self) -> Vec<(K, V)> {
+        self.entries
+    }
+}
+
+impl<K, V> IntoIterator for Conflicts<K, V> {
+    type Item = (K, V);
+    type IntoIter = vec::IntoIter<(K, V)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.entries.into_iter()
+    }
+}
+
+impl<K: Ord, V> IndexableSortedMap<K, V> {
+    /// Inserts every entry whose key is not yet present and hands back the
+    /// rest. A key repeated within `entries` is kept at its first
+    /// occurrence. Batches that are large relative to the map are merged
+    /// with it in a single pass instead of being inserted one by one.
+    pub fn insert_all<I: IntoIterator<Item = (K, V)>>(&mut self, entries: I) -> Conflicts<K, V> {
+        let mut batch: Vec<(usize, K, V)> = entries
+            .into_iter()
+            .enumerate()
+            .map(|(i, (k, v))| (i, k, v))
+            .collect();
+
+        if batch.len() * 8 < self.len() {
+            let mut entries = Vec::new();
+
+            for (_i, k, v) in batch {
+                if self.key_position(&k).is_ok() {
+                    entries.push((k, v));
+                } else {
+                    self.insert(k, v);
+                }
+            }
+
+            return Conflicts { entries };
+        }
+
+        structural_span!("insert_all", len = self.len(), batch = batch.len());
+
+        // A stable sort keeps repeated keys in input order.
+        batch.sort_by(|a, b| a.1.cmp(&b.1));
+
+        let mut existing = self.take_entries().peekable();
+        let mut builder = SortedMapBuilder::with_pool(mem::replace(&mut self.pool, NodePool::new()));
+        let mut conflicts = Vec::new();
+
+        for (i, k, v) in batch {
+            while let Some((ek, ev)) = existing.next_if(|(ek, _ev)| ek < &k) {
+                builder.push_unchecked(ek, ev);
+            }
+
+            let present = matches!(existing.peek(), Some((ek, _ev)) if ek == &k);
+            if present || builder.last_key() == Some(&k) {
+                conflicts.push((i, k, v));
+            } else {
+                builder.push_unchecked(k, v);
+            }
+        }
+
+        for (ek, ev) in existing {
+            builder.push_unchecked(ek, ev);
+        }
+
+        *self = builder.finish();
+
+        conflicts.sort_by_key(|conflict| conflict.0);
+        Conflicts {
+            entries: conflicts.into_iter().map(|(_i, k, v)| (k, v)).collect(),
+        }
+    }
+
+    /// Moves every entry of `other` into `self`, leaving `other` empty. As
+    /// with `BTreeMap::append`, a key in both keeps `other`'s value. If the
+    /// two maps' key ranges don't overlap, the trees are joined along their
+    /// spines in O(log n).
+    pub fn append(&mut self, other: &mut Self) {
+        self.append_with(other, |_k, _ours, theirs| theirs);
+    }
+
+    /// Like `append`, but a key in both maps gets the value `resolve`
+    /// makes of `self`'s value and `other`'s, in that order. The two maps
+    /// are merged in a single pass, however their key ranges interleave.
+    pub fn append_with<F: FnMut(&K, V, V) -> V>(&mut self, other: &mut Self, mut resolve: F) {
+        if self.try_concat(other) {
+            return;
+        }
+
+        structural_span!("append", len = self.len(), other = other.len());
+
+        let mut ours = self.take_entries().peekable();
+        let mut theirs = other.take_entries().peekable();
+        let mut builder = SortedMapBuilder::with_pool(mem::replace(&mut self.pool, NodePool::new()));
+
+        loop {
+            let order = match (ours.peek(), theirs.peek()) {
+                (None, None) => break,
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some((a, _a)), Some((b, _b))) => a.cmp(b),
+            };
+
+            match order {
+                Ordering::Less => {
+                    let (k, v) = ours.next().unwrap();
+                    builder.push_unchecked(k, v);
+                },
+                Ordering::Greater => {
+                    let (k, v) = theirs.next().unwrap();
+                    builder.push_unchecked(k, v);
+                },
+                Ordering::Equal => {
+                    let (k, ours_value) = ours.next().unwrap();
+                    let (_k, theirs_value) = theirs.next().unwrap();
+                    let v = resolve(&k, ours_value, theirs_value);
+                    builder.push_unchecked(k, v);
+                },
+            }
+        }
+
+        *self = builder.finish();
+    }
+
+    /// Combines two maps, a key in both getting the value `resolve` makes
+    /// of `self`'s value and `other`'s, in that order. The smaller map is
+    /// taken apart at its root, the larger split at the same keys, and the
+    /// pairs of pieces merged recursively and joined back together, so maps
+    /// that interleave little merge in well under linear time.
+    pub fn merge_with<F: FnMut(&K, V, V) -> V>(self, other: Self, mut resolve: F) -> Self {
+        structural_span!("merge_with", len = self.len(), other = other.len());
+        let lineage = self.lineage;
+        union(self, other, &mut resolve).continuing(lineage)
+    }
+
+    /// Reconciles two maps derived from a common `base`. For each key, a
+    /// side that left the base entry untouched takes the other side's
+    /// version, and identical changes on both sides are taken as is. Only
+    /// when the sides changed the entry differently is `resolve` called,
+    /// with the base, ours and theirs versions, any of which may be absent;
+    /// it returns the merged value, or `None` to drop the key.
+    pub fn merge3<F>(base: &Self, ours: &Self, theirs: &Self, mut resolve: F) -> Self
+    where
+        K: Clone,
+        V: Clone + PartialEq,
+        F: FnMut(&K, Option<&V>, Option<&V>, Option<&V>) -> Option<V>,
+    {
+        structural_span!("merge3", base = base.len(), ours = ours.len(), theirs = theirs.len());
+
+        let mut base_entries = base.iter().peekable();
+        let mut our_entries = ours.iter().peekable();
+        let mut their_entries = theirs.iter().peekable();
+
+        let mut builder = SortedMapBuilder::with_pool(ours.pool.sibling());
+
+        loop {
+            let key = [base_entries.peek(), our_entries.peek(), their_entries.peek()]
+                .into_iter()
+                .flatten()
+                .map(|&(k, _v)| k)
+                .min();
+
+            let Some(key) = key else {
+                break;
+            };
+
+            let b = base_entries.next_if(|&(k, _v)| k == key).map(|(_k, v)| v);
+            let o = our_entries.next_if(|&(k, _v)| k == key).map(|(_k, v)| v);
+            let t = their_entries.next_if(|&(k, _v)| k == key).map(|(_k, v)| v);
+
+            let merged = if o == t || t == b {
+                o.cloned()
+            } else if o == b {
+                t.cloned()
+            } else {
+                resolve(key, b, o, t)
+            };
+
+            if let Some(value) = merged {
+                builder.push_unchecked(key.clone(), value);
+            }
+        }
+
+        builder.finish()
+    }
+
+    /// Merges any number of maps into one. When several maps hold the same
+    /// key, `resolve` folds their values together in the order the maps
+    /// were given.
+    pub fn merge_all<I, F>(maps: I, resolve: F) -> Self
+    where
+        I: IntoIterator<Item = Self>,
+        F: FnMut(&K, V, V) -> V,
+    {
+        let maps: Vec<Self> = maps.into_iter().collect();
+        let builder = match maps.first() {
+            Some(map) => SortedMapBuilder::with_pool(map.pool.sibling()),
+            None => SortedMapBuilder::new(),
+        };
+
+        Self::merge_sorted_into(builder, maps.into_iter().map(IndexableSortedMap::into_entries), resolve)
+    }
+
+    /// Like `merge_all`, but over iterators of entries in strictly
+    /// ascending key order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an input yields a key smaller than one it yielded before.
+    pub fn merge_sorted_iters<I, J, F>(inputs: I, resolve: F) -> Self
+    where
+        I: IntoIterator<Item = J>,
+        J: IntoIterator<Item = (K, V)>,
+        F: FnMut(&K, V, V) -> V,
+    {
+        Self::merge_sorted_into(SortedMapBuilder::new(), inputs, resolve)
+    }
+
+    fn merge_sorted_into<I, J, F>(mut builder: SortedMapBuilder<K, V>, inputs: I, mut resolve: F) -> Self
+    where
+        I: IntoIterator<Item = J>,
+        J: IntoIterator<Item = (K, V)>,
+        F: FnMut(&K, V, V) -> V,
+    {
+        let mut inputs: Vec<J::IntoIter> = inputs.into_iter().map(IntoIterator::into_iter).collect();
+        structural_span!("merge_all", inputs = inputs.len());
+        let mut heads = BinaryHeap::with_capacity(inputs.len());
+
+        for (source, input) in inputs.iter_mut().enumerate() {
+            if let Some((key, value)) = input.next() {
+                heads.push(Head { key, value, source });
+            }
+        }
+
+        let mut pending: Option<(K, V)> = None;
+
+        while let Some(Head { key, value, source }) = heads.pop() {
+            if let Some((next_key, next_value)) = inputs[source].next() {
+                heads.push(Head { key: next_key, value: next_value, source });
+            }
+
+            pending = match pending {
+                Some((pending_key, pending_value)) if pending_key == key => {
+                    let value = resolve(&key, pending_value, value);
+                    Some((key, value))
+                },
+                Some((pending_key, pending_value)) => {
+                    if builder.push(pending_key, pending_value).is_err() {
+                        panic!("merge inputs must be in ascending key order");
+                    }
+                    Some((key, value))
+                },
+                None => Some((key, value)),
+            };
+        }
+
+        if let Some((key, value)) = pending {
+            if builder.push(key, value).is_err() {
+                panic!("merge inputs must be in ascending key order");
+            }
+        }
+
+        builder.finish()
+    }
+}
+
+pub(crate) fn union<K, V, F>(mut ours: IndexableSortedMap<K, V>, mut theirs: IndexableSortedMap<K, V>, resolve: &mut F) -> IndexableSortedMap<K, V>
+where
+    K: Ord,
+    F: FnMut(&K, V, V) -> V,
+{
+    if ours.try_concat(&mut theirs) {
+        return ours;
+    }
+
+    if theirs.len() == 1 {
+        let (k, v) = theirs.pop_first().unwrap();
+        let v = match ours.remove(&k) {
+            Some(ours_value) => resolve(&k, ours_value, v),
+            None => v,
+        };
+        ours.insert(k, v);
+        return ours;
+    }
+
+    if ours.len() == 1 {
+        let (k, v) = ours.pop_first().unwrap();
+        let v = match theirs.remove(&k) {
+            Some(theirs_value) => resolve(&k, v, theirs_value),
+            None => v,
+        };
+        theirs.insert(k, v);
+        return theirs;
+    }
+
+    let mut result = ours.empty_like();
+
+    let (ours, theirs) = if theirs.len() <= ours.len() {
+        let theirs = theirs.into_children();
+        (ours.split_like(&theirs), theirs)
+    } else {
+        let ours = ours.into_children();
+        let theirs = theirs.split_like(&ours);
+        (ours, theirs)
+    };
+
+    for (ours, theirs) in ours.into_iter().zip(theirs) {
+        let mut part = union(ours, theirs, resolve);
+        result.concat(&mut part);
+    }
+
+    result
+}
@@ -0,0 +1,145 @@
+use std::ops;
+
+use crate::{IndexableSortedMap, Iter, Range};
+
+/// A sorted map that keeps every value inserted under a key, and still
+/// allows lookup by index over all of them. Values under the same key stay
+/// in insertion order, so the positions of existing entries only shift when
+/// something is inserted or removed before them.
+///
+/// Each entry is stored under its key and an insertion sequence number,
+/// which makes every stored key distinct.
+pub struct IndexableSortedMultiMap<K: Ord + Clone, V> {
+    map: IndexableSortedMap<(K, u64), V>,
+    next_sequence: u64,
+}
+
+pub struct MultiValues<'a, K: Ord + Clone, V> {
+    range: Range<'a, (K, u64), V>,
+}
+
+pub struct MultiIter<'a, K: Ord + Clone, V> {
+    iter: Iter<'a, (K, u64), V>,
+}
+
+impl<K: Ord + Clone, V> Default for IndexableSortedMultiMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone, V> IndexableSortedMultiMap<K, V> {
+    pub fn new() -> Self {
+        IndexableSortedMultiMap {
+            map: IndexableSortedMap::new(),
+            next_sequence: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Adds an entry after any others with the same key.
+    pub fn insert(&mut self, key: K, value: V) {
+        let sequence = self.next_sequence;
+        self.next_sequence += 1;
+        self.map.insert((key, sequence), value);
+    }
+
+    pub fn index(&self, i: usize) -> Option<(&K, &V)> {
+        self.map.index(i).map(|((k, _sequence), v)| (k, v))
+    }
+
+    /// The values under `key`, in insertion order.
+    pub fn get_all(&self, key: &K) -> MultiValues<'_, K, V> {
+        MultiValues { range: self.map.range_by_index(self.equal_range(key)) }
+    }
+
+    pub fn count(&self, key: &K) -> usize {
+        self.equal_range(key).len()
+    }
+
+    /// The positions of the entries under `key`, which is empty, at the
+    /// position `key` would be inserted at, if there are none.
+    pub fn equal_range(&self, key: &K) -> ops::Range<usize> {
+        let (start, end) = bounds(key);
+        let start = match self.map.rank(&start) {
+            Ok(i) | Err(i) => i,
+        };
+        let end = match self.map.rank(&end) {
+            Ok(i) | Err(i) => i,
+        };
+
+        start..end
+    }
+
+    /// Removes every entry under `key`, returning their values in
+    /// insertion order. The run is cut out of the tree and the two sides
+    /// joined, in O(log n) plus the number removed.
+    pub fn remove_all(&mut self, key: &K) -> Vec<V> {
+        let (start, end) = bounds(key);
+        let mut removed = self.map.split_off(&start);
+        let mut after = removed.split_off(&end);
+        self.map.concat(&mut after);
+
+        removed.into_iter().map(|(_key, v)| v).collect()
+    }
+
+    pub fn remove_at(&mut self, i: usize) -> Option<(K, V)> {
+        self.map.remove_at(i).map(|((k, _sequence), v)| (k, v))
+    }
+
+    pub fn iter(&self) -> MultiIter<'_, K, V> {
+        MultiIter { iter: self.map.iter() }
+    }
+}
+
+// Stored keys either side of every entry under `key`.
+fn bounds<K: Clone>(key: &K) -> ((K, u64), (K, u64)) {
+    ((key.clone(), 0), (key.clone(), u64::MAX))
+}
+
+impl<'a, K: Ord + Clone, V> Iterator for MultiValues<'a, K, V> {
+    type Item = &'a V;
+
+    fn next(&mut self) -> Option<&'a V> {
+        self.range.next().map(|(_key, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<'a, K: Ord + Clone, V> DoubleEndedIterator for MultiValues<'a, K, V> {
+    fn next_back(&mut self) -> Option<&'a V> {
+        self.range.next_back().map(|(_key, v)| v)
+    }
+}
+
+impl<'a, K: Ord + Clone, V> ExactSizeIterator for MultiValues<'a, K, V> {}
+
+impl<'a, K: Ord + Clone, V> Iterator for MultiIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|((k, _sequence), v)| (k, v))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, K: Ord + Clone, V> DoubleEndedIterator for MultiIter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(|((k, _sequence), v)| (k, v))
+    }
+}
+
+impl<'a, K: Ord + Clone, V> ExactSizeIterator for MultiIter<'a, K, V> {}
@@ -0,0 +1,602 @@
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::ops::{Bound, RangeBounds};
+use std::ptr::NonNull;
+use std::sync::Arc;
+
+/// An immutable map whose updates return a new version. Each version
+/// copies only the path it changed and shares every other node, through
+/// `Arc`, with the version it came from, so old versions stay valid and
+/// cloning a map is O(1).
+pub struct PersistentIndexableSortedMap<K: Ord, V> {
+    root: Option<PersistentNode<K, V>>,
+}
+
+// The subtrees not yet visited, in order. The first `front` entries under
+// them and the last `back` lie outside the range.
+pub struct PersistentRange<'a, K: Ord, V> {
+    nodes: VecDeque<&'a PersistentNode<K, V>>,
+    front: usize,
+    back: usize,
+    remaining: usize,
+}
+
+pub(crate) enum PersistentTree<K: Ord, V> {
+    Leaf(K, V),
+    Branch2(PersistentNode<K, V>, PersistentNode<K, V>),
+    Branch3(PersistentNode<K, V>, PersistentNode<K, V>, PersistentNode<K, V>),
+}
+
+pub(crate) struct PersistentNode<K: Ord, V> {
+    // The key of the leftmost leaf below, which is the subtree's smallest.
+    // Trees are never changed once built, so it stays put for as long as
+    // `tree` is alive.
+    first: NonNull<K>,
+    pub(crate) size: usize,
+    pub(crate) tree: Arc<PersistentTree<K, V>>,
+}
+
+// SAFETY: `first` points into the node's own subtree, so the node is
+// exactly as safe to send or share as the entries it owns.
+unsafe impl<K: Ord + Send + Sync, V: Send + Sync> Send for PersistentNode<K, V> {}
+unsafe impl<K: Ord + Send + Sync, V: Send + Sync> Sync for PersistentNode<K, V> {}
+
+pub(crate) enum InsertResult<K: Ord, V> {
+    SameDepth(PersistentNode<K, V>),
+    Overflow(PersistentNode<K, V>, PersistentNode<K, V>),
+}
+
+pub(crate) enum RemoveResult<K: Ord, V> {
+    SameDepth(PersistentNode<K, V>),
+    Underflow(PersistentNode<K, V>),
+    Empty,
+}
+
+type Node<K, V> = PersistentNode<K, V>;
+
+impl<K: Ord, V> Clone for PersistentIndexableSortedMap<K, V> {
+    fn clone(&self) -> Self {
+        PersistentIndexableSortedMap { root: self.root.clone() }
+    }
+}
+
+impl<K: Ord, V> Default for PersistentIndexableSortedMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> PersistentIndexableSortedMap<K, V> {
+    pub fn new() -> Self {
+        PersistentIndexableSortedMap { root: None }
+    }
+
+    pub fn len(&self) -> usize {
+        self.root.as_ref().map_or(0, |node| node.size)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn lookup(&self, key: &K) -> Option<&V> {
+        self.root.as_ref()?.lookup(key)
+    }
+
+    pub fn index(&self, i: usize) -> Option<(&K, &V)> {
+        self.root.as_ref()?.index(i)
+    }
+
+    pub fn rank(&self, key: &K) -> Result<usize, usize> {
+        match &self.root {
+            None => Err(0),
+            Some(node) => node.rank(key),
+        }
+    }
+
+    /// Iterates over every entry in key order.
+    pub fn iter(&self) -> PersistentRange<'_, K, V> {
+        self.range(..)
+    }
+
+    /// Iterates over the entries whose keys lie in `range`, from either
+    /// end. Subtrees wholly before or after the range are skipped by their
+    /// sizes rather than visited.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> PersistentRange<'_, K, V> {
+        let start = match range.start_bound() {
+            Bound::Unbounded => 0,
+            Bound::Included(k) => match self.rank(k) {
+                Ok(i) | Err(i) => i,
+            },
+            Bound::Excluded(k) => match self.rank(k) {
+                Ok(i) => i + 1,
+                Err(i) => i,
+            },
+        };
+
+        let end = match range.end_bound() {
+            Bound::Unbounded => self.len(),
+            Bound::Included(k) => match self.rank(k) {
+                Ok(i) => i + 1,
+                Err(i) => i,
+            },
+            Bound::Excluded(k) => match self.rank(k) {
+                Ok(i) | Err(i) => i,
+            },
+        };
+
+        let end = end.max(start);
+
+        PersistentRange {
+            nodes: self.root.iter().collect(),
+            front: start,
+            back: self.len() - end,
+            remaining: end - start,
+        }
+    }
+
+    /// A version with `key` mapped to `value`, replacing any existing
+    /// entry for it.
+    pub fn insert(&self, key: K, value: V) -> Self {
+        let root = match &self.root {
+            None => Node::singleton(key, value),
+            Some(node) => match node.insert(key, value).0 {
+                InsertResult::SameDepth(new_node) => new_node,
+                InsertResult::Overflow(left, right) => Node::branch2(left, right),
+            },
+        };
+
+        PersistentIndexableSortedMap { root: Some(root) }
+    }
+
+    /// A version without `key`. If `key` is absent, this is just a clone.
+    pub fn remove(&self, key: &K) -> Self {
+        let Some((result, _value)) = self.root.as_ref().and_then(|node| node.remove(key)) else {
+            return self.clone();
+        };
+
+        let root = match result {
+            RemoveResult::SameDepth(new_node) | RemoveResult::Underflow(new_node) => Some(new_node),
+            RemoveResult::Empty => None,
+        };
+
+        PersistentIndexableSortedMap { root }
+    }
+}
+
+impl<K: Ord, V> Clone for PersistentNode<K, V> {
+    fn clone(&self) -> Self {
+        PersistentNode {
+            first: self.first,
+            size: self.size,
+            tree: self.tree.clone(),
+        }
+    }
+}
+
+impl<K: Ord, V> PersistentNode<K, V> {
+    fn from_tree(tree: PersistentTree<K, V>) -> Node<K, V> {
+        let tree = Arc::new(tree);
+        let (first, size) = match tree.as_ref() {
+            PersistentTree::Leaf(k, _v) => (NonNull::from(k), 1),
+            PersistentTree::Branch2(left, right) => (left.first, left.size + right.size),
+            PersistentTree::Branch3(left, middle, right) => (left.first, left.size + middle.size + right.size),
+        };
+
+        PersistentNode { first, size, tree }
+    }
+
+    fn min_key(&self) -> &K {
+        // SAFETY: `first` points at the key of the leftmost leaf in this
+        // subtree, which `&self` keeps alive through `tree`.
+        unsafe { self.first.as_ref() }
+    }
+
+    pub(crate) fn singleton(key: K, value: V) -> Node<K, V> {
+        Node::from_tree(PersistentTree::Leaf(key, value))
+    }
+
+    pub(crate) fn branch2(left: Node<K, V>, right: Node<K, V>) -> Node<K, V> {
+        Node::from_tree(PersistentTree::Branch2(left, right))
+    }
+
+    fn branch3(left: Node<K, V>, middle: Node<K, V>, right: Node<K, V>) -> Node<K, V> {
+        Node::from_tree(PersistentTree::Branch3(left, middle, right))
+    }
+
+    fn branch4(a: Node<K, V>, b: Node<K, V>, c: Node<K, V>, d: Node<K, V>) -> Node<K, V> {
+        Node::branch2(Node::branch2(a, b), Node::branch2(c, d))
+    }
+
+    fn branch5(a: Node<K, V>, b: Node<K, V>, c: Node<K, V>, d: Node<K, V>, e: Node<K, V>) -> Node<K, V> {
+        Node::branch2(Node::branch2(a, b), Node::branch3(c, d, e))
+    }
+
+    fn branch6(a: Node<K, V>, b: Node<K, V>, c: Node<K, V>, d: Node<K, V>, e: Node<K, V>, f: Node<K, V>) -> Node<K, V> {
+        Node::branch2(Node::branch3(a, b, c), Node::branch3(d, e, f))
+    }
+
+    fn branch7(a: Node<K, V>, b: Node<K, V>, c: Node<K, V>, d: Node<K, V>, e: Node<K, V>, f: Node<K, V>, g: Node<K, V>) -> Node<K, V> {
+        Node::branch3(Node::branch2(a, b), Node::branch2(c, d), Node::branch3(e, f, g))
+    }
+
+    // The children of a branch, shared rather than copied.
+    fn children(&self) -> Vec<Node<K, V>> {
+        match self.tree.as_ref() {
+            PersistentTree::Leaf(_k, _v) => unreachable!(),
+            PersistentTree::Branch2(a, b) => vec![a.clone(), b.clone()],
+            PersistentTree::Branch3(a, b, c) => vec![a.clone(), b.clone(), c.clone()],
+        }
+    }
+
+    // Rebuilds a parent from an ordered run of 5 to 7 same-height nodes.
+    fn regroup(nodes: Vec<Node<K, V>>) -> Node<K, V> {
+        let count = nodes.len();
+        let mut nodes = nodes.into_iter();
+        let mut next = || nodes.next().unwrap();
+
+        match count {
+            5 => Node::branch5(next(), next(), next(), next(), next()),
+            6 => Node::branch6(next(), next(), next(), next(), next(), next()),
+            7 => Node::branch7(next(), next(), next(), next(), next(), next(), next()),
+            _ => unreachable!(),
+        }
+    }
+
+    pub(crate) fn lookup(&self, key: &K) -> Option<&V> {
+        let mut node = self;
+
+        loop {
+            match node.tree.as_ref() {
+                PersistentTree::Leaf(k, v) => {
+                    return if k == key { Some(v) } else { None };
+                },
+                PersistentTree::Branch2(left, right) => {
+                    node = if key < right.min_key() { left } else { right };
+                },
+                PersistentTree::Branch3(left, middle, right) => {
+                    node = if key < middle.min_key() {
+                        left
+                    } else if key < right.min_key() {
+                        middle
+                    } else {
+                        right
+                    };
+                },
+            }
+        }
+    }
+
+    pub(crate) fn index(&self, mut i: usize) -> Option<(&K, &V)> {
+        if i >= self.size {
+            return None;
+        }
+
+        let mut node = self;
+
+        loop {
+            match node.tree.as_ref() {
+                PersistentTree::Leaf(k, v) => {
+                    return Some((k, v));
+                },
+                PersistentTree::Branch2(left, right) => {
+                    if i < left.size {
+                        node = left;
+                    } else {
+                        i -= left.size;
+                        node = right;
+                    }
+                },
+                PersistentTree::Branch3(left, middle, right) => {
+                    if i < left.size {
+                        node = left;
+                    } else if i < left.size + middle.size {
+                        i -= left.size;
+                        node = middle;
+                    } else {
+                        i -= left.size + middle.size;
+                        node = right;
+                    }
+                },
+            }
+        }
+    }
+
+    pub(crate) fn rank(&self, key: &K) -> Result<usize, usize> {
+        let mut node = self;
+        let mut offset = 0;
+
+        loop {
+            match node.tree.as_ref() {
+                PersistentTree::Leaf(k, _v) => {
+                    return match key.cmp(k) {
+                        Ordering::Less => Err(offset),
+                        Ordering::Equal => Ok(offset),
+                        Ordering::Greater => Err(offset + 1),
+                    };
+                },
+                PersistentTree::Branch2(left, right) => {
+                    if key < right.min_key() {
+                        node = left;
+                    } else {
+                        offset += left.size;
+                        node = right;
+                    }
+                },
+                PersistentTree::Branch3(left, middle, right) => {
+                    if key < middle.min_key() {
+                        node = left;
+                    } else if key < right.min_key() {
+                        offset += left.size;
+                        node = middle;
+                    } else {
+                        offset += left.size + middle.size;
+                        node = right;
+                    }
+                },
+            }
+        }
+    }
+
+    // Returns the path-copied replacement for this node; every subtree off
+    // the search path is shared with `self`. An existing entry for `key` is
+    // replaced, and its value, which still lives in the old version, is
+    // returned alongside.
+    pub(crate) fn insert(&self, key: K, value: V) -> (InsertResult<K, V>, Option<&V>) {
+        match self.tree.as_ref() {
+            PersistentTree::Leaf(k, v) => {
+                match key.cmp(k) {
+                    Ordering::Equal => (InsertResult::SameDepth(Node::singleton(key, value)), Some(v)),
+                    Ordering::Less => (InsertResult::Overflow(Node::singleton(key, value), self.clone()), None),
+                    Ordering::Greater => (InsertResult::Overflow(self.clone(), Node::singleton(key, value)), None),
+                }
+            },
+            PersistentTree::Branch2(left, right) => {
+                if &key < right.min_key() {
+                    let (result, old) = left.insert(key, value);
+                    let result = match result {
+                        InsertResult::SameDepth(new_left) => {
+                            InsertResult::SameDepth(Node::branch2(new_left, right.clone()))
+                        },
+                        InsertResult::Overflow(new_left, middle) => {
+                            InsertResult::SameDepth(Node::branch3(new_left, middle, right.clone()))
+                        },
+                    };
+
+                    (result, old)
+                } else {
+                    let (result, old) = right.insert(key, value);
+                    let result = match result {
+                        InsertResult::SameDepth(new_right) => {
+                            InsertResult::SameDepth(Node::branch2(left.clone(), new_right))
+                        },
+                        InsertResult::Overflow(middle, new_right) => {
+                            InsertResult::SameDepth(Node::branch3(left.clone(), middle, new_right))
+                        },
+                    };
+
+                    (result, old)
+                }
+            },
+            PersistentTree::Branch3(left, middle, right) => {
+                if &key < middle.min_key() {
+                    let (result, old) = left.insert(key, value);
+                    let result = match result {
+                        InsertResult::SameDepth(new_left) => {
+                            InsertResult::SameDepth(Node::branch3(new_left, middle.clone(), right.clone()))
+                        },
+                        InsertResult::Overflow(new_left, new_middle) => {
+                            InsertResult::Overflow(
+                                Node::branch2(new_left, new_middle),
+                                Node::branch2(middle.clone(), right.clone()),
+                            )
+                        },
+                    };
+
+                    (result, old)
+                } else if &key < right.min_key() {
+                    let (result, old) = middle.insert(key, value);
+                    let result = match result {
+                        InsertResult::SameDepth(new_middle) => {
+                            InsertResult::SameDepth(Node::branch3(left.clone(), new_middle, right.clone()))
+                        },
+                        InsertResult::Overflow(l_middle, r_middle) => {
+                            InsertResult::Overflow(
+                                Node::branch2(left.clone(), l_middle),
+                                Node::branch2(r_middle, right.clone()),
+                            )
+                        },
+                    };
+
+                    (result, old)
+                } else {
+                    let (result, old) = right.insert(key, value);
+                    let result = match result {
+                        InsertResult::SameDepth(new_right) => {
+                            InsertResult::SameDepth(Node::branch3(left.clone(), middle.clone(), new_right))
+                        },
+                        InsertResult::Overflow(new_middle, new_right) => {
+                            InsertResult::Overflow(
+                                Node::branch2(left.clone(), middle.clone()),
+                                Node::branch2(new_middle, new_right),
+                            )
+                        },
+                    };
+
+                    (result, old)
+                }
+            },
+        }
+    }
+
+    // `None` when `key` is absent, in which case nothing was copied.
+    // Otherwise the replacement for this node and the removed value, which
+    // still lives in the old version.
+    pub(crate) fn remove(&self, key: &K) -> Option<(RemoveResult<K, V>, &V)> {
+        match self.tree.as_ref() {
+            PersistentTree::Leaf(k, v) => {
+                if k == key {
+                    Some((RemoveResult::Empty, v))
+                } else {
+                    None
+                }
+            },
+            PersistentTree::Branch2(left, right) => {
+                if key < right.min_key() {
+                    let (result, value) = left.remove(key)?;
+
+                    let result = match result {
+                        RemoveResult::Empty => RemoveResult::Underflow(right.clone()),
+                        RemoveResult::SameDepth(new_left) => {
+                            RemoveResult::SameDepth(Node::branch2(new_left, right.clone()))
+                        },
+                        RemoveResult::Underflow(new_left) => {
+                            let mut nodes = vec![new_left];
+                            nodes.extend(right.children());
+                            Node::rebalance(nodes)
+                        },
+                    };
+
+                    Some((result, value))
+                } else {
+                    let (result, value) = right.remove(key)?;
+
+                    let result = match result {
+                        RemoveResult::Empty => RemoveResult::Underflow(left.clone()),
+                        RemoveResult::SameDepth(new_right) => {
+                            RemoveResult::SameDepth(Node::branch2(left.clone(), new_right))
+                        },
+                        RemoveResult::Underflow(new_right) => {
+                            let mut nodes = left.children();
+                            nodes.push(new_right);
+                            Node::rebalance(nodes)
+                        },
+                    };
+
+                    Some((result, value))
+                }
+            },
+            PersistentTree::Branch3(left, middle, right) => {
+                let (position, child) = if key < middle.min_key() {
+                    (0, left)
+                } else if key < right.min_key() {
+                    (1, middle)
+                } else {
+                    (2, right)
+                };
+
+                let (result, value) = child.remove(key)?;
+
+                let mut siblings = vec![left.clone(), middle.clone(), right.clone()];
+                siblings.remove(position);
+
+                let result = match result {
+                    RemoveResult::Empty => {
+                        let right = siblings.pop().unwrap();
+                        let left = siblings.pop().unwrap();
+                        RemoveResult::SameDepth(Node::branch2(left, right))
+                    },
+                    RemoveResult::SameDepth(new_child) => {
+                        siblings.insert(position, new_child);
+                        let right = siblings.pop().unwrap();
+                        let middle = siblings.pop().unwrap();
+                        let left = siblings.pop().unwrap();
+                        RemoveResult::SameDepth(Node::branch3(left, middle, right))
+                    },
+                    RemoveResult::Underflow(new_child) => {
+                        let (before, after) = siblings.split_at(position);
+                        let mut nodes: Vec<Node<K, V>> = before.iter().flat_map(Node::children).collect();
+                        nodes.push(new_child);
+                        nodes.extend(after.iter().flat_map(Node::children));
+                        RemoveResult::SameDepth(Node::regroup(nodes))
+                    },
+                };
+
+                Some((result, value))
+            },
+        }
+    }
+
+    // Combines an underflowed child with the children of its only sibling.
+    fn rebalance(nodes: Vec<Node<K, V>>) -> RemoveResult<K, V> {
+        let mut nodes = nodes.into_iter();
+        let a = nodes.next().unwrap();
+        let b = nodes.next().unwrap();
+        let c = nodes.next().unwrap();
+
+        match nodes.next() {
+            None => RemoveResult::Underflow(Node::branch3(a, b, c)),
+            Some(d) => RemoveResult::SameDepth(Node::branch4(a, b, c, d)),
+        }
+    }
+}
+
+impl<'a, K: Ord, V> Iterator for PersistentRange<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.remaining > 0 {
+            let node = self.nodes.pop_front()?;
+
+            if node.size <= self.front {
+                self.front -= node.size;
+                continue;
+            }
+
+            match node.tree.as_ref() {
+                PersistentTree::Leaf(k, v) => {
+                    self.remaining -= 1;
+                    return Some((k, v));
+                },
+                PersistentTree::Branch2(left, right) => {
+                    self.nodes.push_front(right);
+                    self.nodes.push_front(left);
+                },
+                PersistentTree::Branch3(left, middle, right) => {
+                    self.nodes.push_front(right);
+                    self.nodes.push_front(middle);
+                    self.nodes.push_front(left);
+                },
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<'a, K: Ord, V> DoubleEndedIterator for PersistentRange<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        while self.remaining > 0 {
+            let node = self.nodes.pop_back()?;
+
+            if node.size <= self.back {
+                self.back -= node.size;
+                continue;
+            }
+
+            match node.tree.as_ref() {
+                PersistentTree::Leaf(k, v) => {
+                    self.remaining -= 1;
+                    return Some((k, v));
+                },
+                PersistentTree::Branch2(left, right) => {
+                    self.nodes.push_back(left);
+                    self.nodes.push_back(right);
+                },
+                PersistentTree::Branch3(left, middle, right) => {
+                    self.nodes.push_back(left);
+                    self.nodes.push_back(middle);
+                    self.nodes.push_back(right);
+                },
+            }
+        }
+
+        None
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for PersistentRange<'a, K, V> {}
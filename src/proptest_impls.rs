@@ -0,0 +1,43 @@
+use proptest::arbitrary::{any_with, Arbitrary};
+use proptest::collection::{vec, SizeRange};
+use proptest::strategy::{BoxedStrategy, Strategy};
+
+use crate::{IndexableSortedMap, IndexableSortedSet};
+
+// Maps and sets are built by inserting the generated entries one at a
+// time, rather than by a bulk build, so that the trees drawn take the
+// uneven shapes that edits leave behind. Shrinking removes entries.
+
+impl<K: Arbitrary + Ord + 'static, V: Arbitrary + 'static> Arbitrary for IndexableSortedMap<K, V> {
+    type Parameters = (SizeRange, K::Parameters, V::Parameters);
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with((size, key, value): Self::Parameters) -> Self::Strategy {
+        vec(any_with::<(K, V)>((key, value)), size)
+            .prop_map(|entries| {
+                let mut map = IndexableSortedMap::new();
+                for (k, v) in entries {
+                    map.insert(k, v);
+                }
+                map
+            })
+            .boxed()
+    }
+}
+
+impl<T: Arbitrary + Ord + 'static> Arbitrary for IndexableSortedSet<T> {
+    type Parameters = (SizeRange, T::Parameters);
+    type Strategy = BoxedStrategy<Self>;
+
+    fn arbitrary_with((size, element): Self::Parameters) -> Self::Strategy {
+        vec(any_with::<T>(element), size)
+            .prop_map(|elements| {
+                let mut set = IndexableSortedSet::new();
+                for x in elements {
+                    set.insert(x);
+                }
+                set
+            })
+            .boxed()
+    }
+}
@@ -0,0 +1,127 @@
+use pyo3::exceptions::{PyIndexError, PyKeyError, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyInt, PyString};
+
+use crate::IndexableSortedMap;
+
+// Python keys may be ints or strs. Ints sort before strs, so a map mixing
+// the two still has a total order.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum PyKey {
+    Int(i64),
+    Str(String),
+}
+
+impl PyKey {
+    fn extract(key: &Bound<'_, PyAny>) -> PyResult<PyKey> {
+        if key.is_instance_of::<PyInt>() {
+            Ok(PyKey::Int(key.extract()?))
+        } else if key.is_instance_of::<PyString>() {
+            Ok(PyKey::Str(key.extract()?))
+        } else {
+            Err(PyTypeError::new_err("keys must be int or str"))
+        }
+    }
+
+    fn to_object<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        match self {
+            PyKey::Int(i) => Ok(i.into_pyobject(py)?.into_any()),
+            PyKey::Str(s) => Ok(s.into_pyobject(py)?.into_any()),
+        }
+    }
+}
+
+#[pyclass(name = "IndexableSortedMap")]
+pub struct PyIndexableSortedMap {
+    map: IndexableSortedMap<PyKey, Py<PyAny>>,
+}
+
+#[pymethods]
+impl PyIndexableSortedMap {
+    #[new]
+    fn new() -> Self {
+        PyIndexableSortedMap { map: IndexableSortedMap::new() }
+    }
+
+    fn __len__(&self) -> usize {
+        self.map.len()
+    }
+
+    fn __contains__(&self, key: &Bound<'_, PyAny>) -> PyResult<bool> {
+        Ok(self.map.lookup(&PyKey::extract(key)?).is_some())
+    }
+
+    fn __getitem__(&self, py: Python<'_>, key: &Bound<'_, PyAny>) -> PyResult<Py<PyAny>> {
+        match self.map.lookup(&PyKey::extract(key)?) {
+            None => Err(PyKeyError::new_err(key.clone().unbind())),
+            Some(value) => Ok(value.clone_ref(py)),
+        }
+    }
+
+    fn __setitem__(&mut self, key: &Bound<'_, PyAny>, value: Py<PyAny>) -> PyResult<()> {
+        let key = PyKey::extract(key)?;
+        self.map.insert(key, value);
+        Ok(())
+    }
+
+    fn __delitem__(&mut self, key: &Bound<'_, PyAny>) -> PyResult<()> {
+        match self.map.remove(&PyKey::extract(key)?) {
+            None => Err(PyKeyError::new_err(key.clone().unbind())),
+            Some(_value) => Ok(()),
+        }
+    }
+
+    #[pyo3(signature = (key, default = None))]
+    fn get(&self, py: Python<'_>, key: &Bound<'_, PyAny>, default: Option<Py<PyAny>>) -> PyResult<Option<Py<PyAny>>> {
+        match self.map.lookup(&PyKey::extract(key)?) {
+            None => Ok(default),
+            Some(value) => Ok(Some(value.clone_ref(py))),
+        }
+    }
+
+    // The entry at position `i` as a `(key, value)` tuple. Negative
+    // positions count from the end, as with Python sequences.
+    fn index<'py>(&self, py: Python<'py>, i: isize) -> PyResult<(Bound<'py, PyAny>, Py<PyAny>)> {
+        let position = if i < 0 {
+            self.map.len().checked_sub(i.unsigned_abs())
+        } else {
+            Some(i as usize)
+        };
+
+        match position.and_then(|position| self.map.index(position)) {
+            None => Err(PyIndexError::new_err("index out of range")),
+            Some((key, value)) => Ok((key.to_object(py)?, value.clone_ref(py))),
+        }
+    }
+
+    fn rank(&self, key: &Bound<'_, PyAny>) -> PyResult<usize> {
+        match self.map.rank(&PyKey::extract(key)?) {
+            Ok(i) => Ok(i),
+            Err(_i) => Err(PyKeyError::new_err(key.clone().unbind())),
+        }
+    }
+
+    // The position `key` has or would be inserted at.
+    fn bisect(&self, key: &Bound<'_, PyAny>) -> PyResult<usize> {
+        match self.map.rank(&PyKey::extract(key)?) {
+            Ok(i) | Err(i) => Ok(i),
+        }
+    }
+
+    fn keys<'py>(&self, py: Python<'py>) -> PyResult<Vec<Bound<'py, PyAny>>> {
+        let mut keys = Vec::with_capacity(self.map.len());
+        let mut zipper = self.map.zipper();
+
+        while let Some(z) = zipper {
+            keys.push(z.focus().0.to_object(py)?);
+            zipper = z.advance(1);
+        }
+
+        Ok(keys)
+    }
+}
+
+#[pymodule]
+fn indexable_sorted_map(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyIndexableSortedMap>()
+}
@@ -0,0 +1,453 @@
+use std::collections::VecDeque;
+
+use rayon::iter::plumbing::{bridge, Consumer, Producer, ProducerCallback, UnindexedConsumer};
+use rayon::iter::{IndexedParallelIterator, IntoParallelIterator, ParallelIterator};
+
+use crate::merge::union;
+use crate::{tree_mut, IndexableSortedMap, IntoEntries, IntoIter, IterMut, Node, NodePool, OutOfOrder, Range, SortedInputError, SortedMapBuilder, Tree};
+
+// Below this many entries, work is done on the current thread.
+const SEQUENTIAL_LEN: usize = 4096;
+
+// Parallel iterators hand out runs of positions. A shared run seeks to its
+// ends through the size annotations; a mutable or owned run is a list of
+// disjoint subtrees between the entries left of two leaves, and splitting
+// it breaks up only the subtree the split point falls in, down to the leaf
+// it cuts.
+
+pub struct ParIter<'a, K: Ord, V> {
+    map: &'a IndexableSortedMap<K, V>,
+}
+
+pub struct ParIterMut<'a, K: Ord, V> {
+    map: &'a mut IndexableSortedMap<K, V>,
+}
+
+pub struct ParIntoIter<K: Ord, V> {
+    map: IndexableSortedMap<K, V>,
+}
+
+struct RangeProducer<'a, K: Ord, V> {
+    map: &'a IndexableSortedMap<K, V>,
+    start: usize,
+    end: usize,
+}
+
+struct IterMutProducer<'a, K: Ord, V> {
+    front: &'a mut [(K, V)],
+    nodes: VecDeque<&'a mut Node<K, V>>,
+    back: &'a mut [(K, V)],
+    len: usize,
+}
+
+struct IntoIterProducer<K: Ord, V> {
+    front: Vec<(K, V)>,
+    nodes: VecDeque<Node<K, V>>,
+    back: Vec<(K, V)>,
+    len: usize,
+    pool: NodePool<K, V>,
+}
+
+impl<K: Ord + Sync, V: Sync> IndexableSortedMap<K, V> {
+    pub fn par_iter(&self) -> ParIter<'_, K, V> {
+        ParIter { map: self }
+    }
+}
+
+impl<K: Ord + Send + Sync, V: Send> IndexableSortedMap<K, V> {
+    /// The parallel counterpart of `iter_mut`.
+    pub fn par_iter_mut(&mut self) -> ParIterMut<'_, K, V> {
+        #[cfg(feature = "cow")]
+        self.unshare_all();
+
+        ParIterMut { map: self }
+    }
+}
+
+impl<K: Ord + Send, V: Send> IndexableSortedMap<K, V> {
+    /// Builds a map from entries in strictly ascending key order, like
+    /// `from_sorted_iter`, with runs of the input built on separate threads
+    /// and then joined along their spines in O(log n) each. Fails at the
+    /// first entry that is out of order.
+    pub fn from_sorted_iter_par<I>(entries: I) -> Result<Self, SortedInputError>
+    where
+        I: IntoParallelIterator<Item = (K, V)>,
+        I::Iter: IndexedParallelIterator,
+    {
+        let entries = entries.into_par_iter();
+        let len = entries.len();
+        structural_span!("build_par", len = len);
+
+        let chunk_size = len.div_ceil(rayon::current_num_threads() * 4).max(SEQUENTIAL_LEN);
+        let chunks: Vec<(SortedMapBuilder<K, V>, Option<SortedInputError>)> = entries
+            .fold_chunks(chunk_size, || (SortedMapBuilder::new(), None), build_chunk)
+            .collect();
+
+        let mut map = IndexableSortedMap::new();
+
+        for (i, (builder, error)) in chunks.into_iter().enumerate() {
+            let start = i * chunk_size;
+            let mut chunk = builder.finish();
+
+            if let (Some((last, _)), Some((first, _))) = (map.last_key_value(), chunk.first_key_value()) {
+                if first <= last {
+                    let position = start;
+                    return Err(if first == last {
+                        SortedInputError::Duplicate { position }
+                    } else {
+                        SortedInputError::Descending { position }
+                    });
+                }
+            }
+
+            if let Some(error) = error {
+                return Err(match error {
+                    SortedInputError::Duplicate { position } => SortedInputError::Duplicate { position: start + position },
+                    SortedInputError::Descending { position } => SortedInputError::Descending { position: start + position },
+                });
+            }
+
+            map.concat(&mut chunk);
+        }
+
+        Ok(map)
+    }
+
+    /// Combines two maps like `merge_with`, merging the pairs of pieces
+    /// on separate threads.
+    pub fn par_merge<F: Fn(&K, V, V) -> V + Sync>(self, other: Self, resolve: F) -> Self {
+        structural_span!("par_merge", len = self.len(), other = other.len());
+        let lineage = self.lineage;
+        par_union(self, other, &resolve).continuing(lineage)
+    }
+}
+
+// Pushes an entry onto a run's builder, or records where the run first
+// went out of order and skips the rest of it.
+fn build_chunk<K: Ord, V>(
+    (mut builder, error): (SortedMapBuilder<K, V>, Option<SortedInputError>),
+    (k, v): (K, V),
+) -> (SortedMapBuilder<K, V>, Option<SortedInputError>) {
+    if error.is_some() {
+        return (builder, error);
+    }
+
+    let error = match builder.push(k, v) {
+        Ok(()) => None,
+        Err(OutOfOrder { key, .. }) => {
+            let position = builder.len();
+            Some(if Some(&key) == builder.last_key() {
+                SortedInputError::Duplicate { position }
+            } else {
+                SortedInputError::Descending { position }
+            })
+        },
+    };
+
+    (builder, error)
+}
+
+fn par_union<K, V, F>(ours: IndexableSortedMap<K, V>, theirs: IndexableSortedMap<K, V>, resolve: &F) -> IndexableSortedMap<K, V>
+where
+    K: Ord + Send,
+    V: Send,
+    F: Fn(&K, V, V) -> V + Sync,
+{
+    if ours.len().min(theirs.len()) <= 1 || ours.len() + theirs.len() <= SEQUENTIAL_LEN {
+        return union(ours, theirs, &mut |k: &K, a, b| resolve(k, a, b));
+    }
+
+    let mut result = ours.empty_like();
+
+    let (ours, theirs) = if theirs.len() <= ours.len() {
+        let theirs = theirs.into_children();
+        (ours.split_like(&theirs), theirs)
+    } else {
+        let ours = ours.into_children();
+        let theirs = theirs.split_like(&ours);
+        (ours, theirs)
+    };
+
+    let parts: Vec<IndexableSortedMap<K, V>> = ours
+        .into_par_iter()
+        .zip(theirs)
+        .map(|(ours, theirs)| par_union(ours, theirs, resolve))
+        .collect();
+
+    for mut part in parts {
+        result.concat(&mut part);
+    }
+
+    result
+}
+
+impl<'a, K: Ord + Sync, V: Sync> IntoParallelIterator for &'a IndexableSortedMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type Iter = ParIter<'a, K, V>;
+
+    fn into_par_iter(self) -> ParIter<'a, K, V> {
+        self.par_iter()
+    }
+}
+
+impl<'a, K: Ord + Send + Sync, V: Send> IntoParallelIterator for &'a mut IndexableSortedMap<K, V> {
+    type Item = (&'a K, &'a mut V);
+    type Iter = ParIterMut<'a, K, V>;
+
+    fn into_par_iter(self) -> ParIterMut<'a, K, V> {
+        self.par_iter_mut()
+    }
+}
+
+impl<K: Ord + Send, V: Send> IntoParallelIterator for IndexableSortedMap<K, V> {
+    type Item = (K, V);
+    type Iter = ParIntoIter<K, V>;
+
+    fn into_par_iter(self) -> ParIntoIter<K, V> {
+        ParIntoIter { map: self }
+    }
+}
+
+impl<'a, K: Ord + Sync, V: Sync> ParallelIterator for ParIter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn drive_unindexed<C: UnindexedConsumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.map.len())
+    }
+}
+
+impl<'a, K: Ord + Sync, V: Sync> IndexedParallelIterator for ParIter<'a, K, V> {
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn drive<C: Consumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn with_producer<CB: ProducerCallback<Self::Item>>(self, callback: CB) -> CB::Output {
+        callback.callback(RangeProducer {
+            map: self.map,
+            start: 0,
+            end: self.map.len(),
+        })
+    }
+}
+
+impl<'a, K: Ord + Send + Sync, V: Send> ParallelIterator for ParIterMut<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+
+    fn drive_unindexed<C: UnindexedConsumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.map.len())
+    }
+}
+
+impl<'a, K: Ord + Send + Sync, V: Send> IndexedParallelIterator for ParIterMut<'a, K, V> {
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn drive<C: Consumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn with_producer<CB: ProducerCallback<Self::Item>>(self, callback: CB) -> CB::Output {
+        callback.callback(IterMutProducer {
+            front: &mut [],
+            len: self.map.len(),
+            nodes: self.map.root.iter_mut().collect(),
+            back: &mut [],
+        })
+    }
+}
+
+impl<K: Ord + Send, V: Send> ParallelIterator for ParIntoIter<K, V> {
+    type Item = (K, V);
+
+    fn drive_unindexed<C: UnindexedConsumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn opt_len(&self) -> Option<usize> {
+        Some(self.map.len())
+    }
+}
+
+impl<K: Ord + Send, V: Send> IndexedParallelIterator for ParIntoIter<K, V> {
+    fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    fn drive<C: Consumer<Self::Item>>(self, consumer: C) -> C::Result {
+        bridge(self, consumer)
+    }
+
+    fn with_producer<CB: ProducerCallback<Self::Item>>(mut self, callback: CB) -> CB::Output {
+        callback.callback(IntoIterProducer {
+            front: Vec::new(),
+            len: self.map.len(),
+            nodes: self.map.root.take().into_iter().collect(),
+            back: Vec::new(),
+            pool: self.map.pool.sibling(),
+        })
+    }
+}
+
+impl<'a, K: Ord + Sync, V: Sync> Producer for RangeProducer<'a, K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Range<'a, K, V>;
+
+    fn into_iter(self) -> Range<'a, K, V> {
+        self.map.range_between(self.start, self.end)
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self) {
+        let mid = self.start + index;
+        let left = RangeProducer { end: mid, ..self };
+        let right = RangeProducer { start: mid, ..self };
+        (left, right)
+    }
+}
+
+impl<'a, K: Ord + Send + Sync, V: Send> Producer for IterMutProducer<'a, K, V> {
+    type Item = (&'a K, &'a mut V);
+    type IntoIter = IterMut<'a, K, V>;
+
+    fn into_iter(self) -> IterMut<'a, K, V> {
+        IterMut {
+            front: self.front.iter_mut(),
+            nodes: self.nodes,
+            back: self.back.iter_mut(),
+            remaining: self.len,
+        }
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self) {
+        let IterMutProducer { front, nodes: mut right, back, len } = self;
+
+        if index <= front.len() {
+            let (head, tail) = front.split_at_mut(index);
+            let left = IterMutProducer { front: head, nodes: VecDeque::new(), back: &mut [], len: index };
+            let right = IterMutProducer { front: tail, nodes: right, back, len: len - index };
+            return (left, right);
+        }
+
+        let mut left = VecDeque::new();
+        let mut taken = front.len();
+
+        while taken < index {
+            let Some(node) = right.pop_front() else {
+                let (head, tail) = back.split_at_mut(index - taken);
+                let left = IterMutProducer { front, nodes: left, back: head, len: index };
+                let right = IterMutProducer { front: tail, nodes: right, back: &mut [], len: len - index };
+                return (left, right);
+            };
+
+            if taken + node.size <= index {
+                taken += node.size;
+                left.push_back(node);
+                continue;
+            }
+
+            node.checkpoint = 0;
+            match tree_mut(&mut node.tree) {
+                Tree::Leaf(entries) => {
+                    let (head, tail) = entries.split_at_mut(index - taken);
+                    let left = IterMutProducer { front, nodes: left, back: head, len: index };
+                    let right = IterMutProducer { front: tail, nodes: right, back, len: len - index };
+                    return (left, right);
+                },
+                Tree::Branch2(a, b) => {
+                    right.push_front(b);
+                    right.push_front(a);
+                },
+                Tree::Branch3(a, b, c) => {
+                    right.push_front(c);
+                    right.push_front(b);
+                    right.push_front(a);
+                },
+            }
+        }
+
+        let left = IterMutProducer { front, nodes: left, back: &mut [], len: index };
+        let right = IterMutProducer { front: &mut [], nodes: right, back, len: len - index };
+        (left, right)
+    }
+}
+
+impl<K: Ord + Send, V: Send> Producer for IntoIterProducer<K, V> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(mut self) -> IntoIter<K, V> {
+        self.front.reverse();
+        IntoIter {
+            entries: IntoEntries {
+                front: self.front,
+                nodes: self.nodes,
+                back: self.back,
+                pool: self.pool,
+            },
+            remaining: self.len,
+        }
+    }
+
+    fn split_at(self, index: usize) -> (Self, Self) {
+        let IntoIterProducer { mut front, nodes: mut right, mut back, len, mut pool } = self;
+
+        if index <= front.len() {
+            let tail = front.split_off(index);
+            let left = IntoIterProducer { front, nodes: VecDeque::new(), back: Vec::new(), len: index, pool: pool.sibling() };
+            let right = IntoIterProducer { front: tail, nodes: right, back, len: len - index, pool };
+            return (left, right);
+        }
+
+        let mut left = VecDeque::new();
+        let mut taken = front.len();
+
+        while taken < index {
+            let Some(node) = right.pop_front() else {
+                let tail = back.split_off(index - taken);
+                let left = IntoIterProducer { front, nodes: left, back, len: index, pool: pool.sibling() };
+                let right = IntoIterProducer { front: tail, nodes: right, back: Vec::new(), len: len - index, pool };
+                return (left, right);
+            };
+
+            if taken + node.size <= index {
+                taken += node.size;
+                left.push_back(node);
+                continue;
+            }
+
+            match pool.take(node.tree) {
+                Tree::Leaf(mut entries) => {
+                    let tail = entries.split_off(index - taken);
+                    let left = IntoIterProducer { front, nodes: left, back: entries, len: index, pool: pool.sibling() };
+                    let right = IntoIterProducer { front: tail, nodes: right, back, len: len - index, pool };
+                    return (left, right);
+                },
+                Tree::Branch2(a, b) => {
+                    right.push_front(b);
+                    right.push_front(a);
+                },
+                Tree::Branch3(a, b, c) => {
+                    right.push_front(c);
+                    right.push_front(b);
+                    right.push_front(a);
+                },
+            }
+        }
+
+        let left = IntoIterProducer { front, nodes: left, back: Vec::new(), len: index, pool: pool.sibling() };
+        let right = IntoIterProducer { front: Vec::new(), nodes: right, back, len: len - index, pool };
+        (left, right)
+    }
+}
@@ -0,0 +1,161 @@
+use std::marker::PhantomData;
+use std::sync::atomic::Ordering;
+use std::sync::Mutex;
+
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+
+use crate::persistent::{InsertResult, PersistentNode, RemoveResult};
+
+/// A map whose readers never take a lock. Writers are serialized; each
+/// one copies the path it modifies, publishes the new root with a single
+/// atomic store and retires the old root through epoch-based reclamation,
+/// so a reader holding an `RcuSnapshot` keeps seeing a consistent version.
+pub struct RcuIndexableSortedMap<K: Ord, V> {
+    root: Atomic<PersistentNode<K, V>>,
+    writer: Mutex<()>,
+}
+
+pub struct RcuSnapshot<'a, K: Ord, V> {
+    _guard: Guard,
+    root: *const PersistentNode<K, V>,
+    _map: PhantomData<&'a RcuIndexableSortedMap<K, V>>,
+}
+
+impl<K: Ord, V> Default for RcuIndexableSortedMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> RcuIndexableSortedMap<K, V> {
+    pub fn new() -> Self {
+        RcuIndexableSortedMap {
+            root: Atomic::null(),
+            writer: Mutex::new(()),
+        }
+    }
+
+    pub fn snapshot(&self) -> RcuSnapshot<'_, K, V> {
+        let guard = epoch::pin();
+        let root = self.root.load(Ordering::Acquire, &guard).as_raw();
+
+        RcuSnapshot {
+            _guard: guard,
+            root,
+            _map: PhantomData,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshot().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshot().is_empty()
+    }
+}
+
+impl<K: Ord + Send + 'static, V: Send + 'static> RcuIndexableSortedMap<K, V> {
+    /// Inserts an entry, returning a copy of the value it displaced if
+    /// `key` was already present. The displaced value itself stays in the
+    /// old version for any reader still looking at it.
+    pub fn insert(&self, key: K, value: V) -> Option<V> where V: Clone {
+        let _writer = self.writer.lock().expect("writer lock poisoned");
+        let guard = epoch::pin();
+        let current = self.root.load(Ordering::Acquire, &guard);
+
+        // SAFETY: the root is only retired by writers, and we hold the
+        // writer lock while the guard keeps it alive.
+        let (new_root, old) = match unsafe { current.as_ref() } {
+            None => (PersistentNode::singleton(key, value), None),
+            Some(node) => {
+                let (result, old) = node.insert(key, value);
+                let new_root = match result {
+                    InsertResult::SameDepth(new_node) => new_node,
+                    InsertResult::Overflow(left, right) => PersistentNode::branch2(left, right),
+                };
+                (new_root, old.cloned())
+            },
+        };
+
+        self.publish(Owned::new(new_root).into_shared(&guard), current, &guard);
+        old
+    }
+
+    pub fn remove(&self, key: &K) -> Option<V> where V: Clone {
+        let _writer = self.writer.lock().expect("writer lock poisoned");
+        let guard = epoch::pin();
+        let current = self.root.load(Ordering::Acquire, &guard);
+
+        // SAFETY: as in `insert`.
+        let node = unsafe { current.as_ref() }?;
+        let (result, value) = node.remove(key)?;
+        let value = value.clone();
+
+        let new_root = match result {
+            RemoveResult::SameDepth(new_node) => Owned::new(new_node).into_shared(&guard),
+            RemoveResult::Underflow(new_node) => Owned::new(new_node).into_shared(&guard),
+            RemoveResult::Empty => Shared::null(),
+        };
+
+        self.publish(new_root, current, &guard);
+        Some(value)
+    }
+
+    fn publish<'g>(
+        &self,
+        new_root: Shared<'g, PersistentNode<K, V>>,
+        old_root: Shared<'g, PersistentNode<K, V>>,
+        guard: &'g Guard,
+    ) {
+        self.root.store(new_root, Ordering::Release);
+
+        if !old_root.is_null() {
+            // SAFETY: the old root is unreachable from `self.root`, and
+            // readers that loaded it are pinned in an earlier epoch.
+            unsafe { guard.defer_destroy(old_root) };
+        }
+    }
+}
+
+impl<K: Ord, V> Drop for RcuIndexableSortedMap<K, V> {
+    fn drop(&mut self) {
+        // SAFETY: `&mut self` rules out outstanding snapshots.
+        unsafe {
+            let root = self.root.load(Ordering::Relaxed, epoch::unprotected());
+            if !root.is_null() {
+                drop(root.into_owned());
+            }
+        }
+    }
+}
+
+impl<'a, K: Ord, V> RcuSnapshot<'a, K, V> {
+    fn root(&self) -> Option<&PersistentNode<K, V>> {
+        // SAFETY: the pinned guard keeps the root loaded at creation alive.
+        unsafe { self.root.as_ref() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.root().map_or(0, |node| node.size)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_null()
+    }
+
+    pub fn lookup(&self, key: &K) -> Option<&V> {
+        self.root()?.lookup(key)
+    }
+
+    pub fn index(&self, i: usize) -> Option<(&K, &V)> {
+        self.root()?.index(i)
+    }
+
+    pub fn rank(&self, key: &K) -> Result<usize, usize> {
+        match self.root() {
+            None => Err(0),
+            Some(node) => node.rank(key),
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use std::ops::RangeBounds;
+
+use crate::{IndexableSortedMap, Range};
+
+/// Where a `SeparatedIndexableSortedMap` keeps its values. The tree holds
+/// only the handles a store gives out, so leaves stay small however large
+/// the values are.
+pub trait ValueStore<V> {
+    type Handle;
+
+    fn store(&mut self, value: V) -> Self::Handle;
+    fn get<'a>(&'a self, handle: &'a Self::Handle) -> &'a V;
+    fn get_mut<'a>(&'a mut self, handle: &'a mut Self::Handle) -> &'a mut V;
+    fn take(&mut self, handle: Self::Handle) -> V;
+}
+
+/// Gives each value its own allocation, the handle being the box itself.
+#[derive(Default)]
+pub struct BoxStore;
+
+/// Keeps values side by side in one growable buffer, reusing the slots of
+/// removed values. The handle is a slot number.
+pub struct SlabStore<V> {
+    slots: Vec<Option<V>>,
+    free: Vec<usize>,
+}
+
+/// A map that keeps its values out of line, in `S`, and its keys and
+/// value handles in the tree. Worth it when values are large enough that
+/// storing them inline would spread a leaf over many cache lines; lookups
+/// then touch a value only once its key has been found.
+pub struct SeparatedIndexableSortedMap<K: Ord, V, S: ValueStore<V> = BoxStore> {
+    pub(crate) map: IndexableSortedMap<K, S::Handle>,
+    store: S,
+}
+
+pub struct SeparatedIter<'a, K: Ord, V, S: ValueStore<V>> {
+    range: Range<'a, K, S::Handle>,
+    store: &'a S,
+}
+
+impl<V> ValueStore<V> for BoxStore {
+    type Handle = Box<V>;
+
+    fn store(&mut self, value: V) -> Box<V> {
+        Box::new(value)
+    }
+
+    fn get<'a>(&'a self, handle: &'a Box<V>) -> &'a V {
+        handle
+    }
+
+    fn get_mut<'a>(&'a mut self, handle: &'a mut Box<V>) -> &'a mut V {
+        handle
+    }
+
+    fn take(&mut self, handle: Box<V>) -> V {
+        *handle
+    }
+}
+
+impl<V> Default for SlabStore<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> SlabStore<V> {
+    pub fn new() -> Self {
+        SlabStore { slots: Vec::new(), free: Vec::new() }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        SlabStore { slots: Vec::with_capacity(capacity), free: Vec::new() }
+    }
+}
+
+// Handles are only ever made by `store` and consumed by `take`, so a
+// handle always names an occupied slot.
+impl<V> ValueStore<V> for SlabStore<V> {
+    type Handle = usize;
+
+    fn store(&mut self, value: V) -> usize {
+        match self.free.pop() {
+            Some(slot) => {
+                self.slots[slot] = Some(value);
+                slot
+            },
+            None => {
+                self.slots.push(Some(value));
+                self.slots.len() - 1
+            },
+        }
+    }
+
+    fn get<'a>(&'a self, handle: &'a usize) -> &'a V {
+        self.slots[*handle].as_ref().expect("handle to a vacant slot")
+    }
+
+    fn get_mut<'a>(&'a mut self, handle: &'a mut usize) -> &'a mut V {
+        self.slots[*handle].as_mut().expect("handle to a vacant slot")
+    }
+
+    fn take(&mut self, handle: usize) -> V {
+        let value = self.slots[handle].take().expect("handle to a vacant slot");
+        self.free.push(handle);
+        value
+    }
+}
+
+impl<K: Ord, V, S: ValueStore<V> + Default> Default for SeparatedIndexableSortedMap<K, V, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V, S: ValueStore<V> + Default> SeparatedIndexableSortedMap<K, V, S> {
+    pub fn new() -> Self {
+        Self::with_store(S::default())
+    }
+}
+
+impl<K: Ord, V, S: ValueStore<V>> SeparatedIndexableSortedMap<K, V, S> {
+    /// An empty map keeping its values in `store`, which should itself be
+    /// empty.
+    pub fn with_store(store: S) -> Self {
+        SeparatedIndexableSortedMap { map: IndexableSortedMap::new(), store }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn lookup(&self, key: &K) -> Option<&V> {
+        let handle = self.map.lookup(key)?;
+        Some(self.store.get(handle))
+    }
+
+    pub fn lookup_mut(&mut self, key: &K) -> Option<&mut V> {
+        let handle = self.map.lookup_mut(key)?;
+        Some(self.store.get_mut(handle))
+    }
+
+    pub fn index(&self, i: usize) -> Option<(&K, &V)> {
+        let (k, handle) = self.map.index(i)?;
+        Some((k, self.store.get(handle)))
+    }
+
+    pub fn rank(&self, key: &K) -> Result<usize, usize> {
+        self.map.rank(key)
+    }
+
+    /// Inserts an entry. Replacing a value writes the new one over the
+    /// old in the store, keeping its handle.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.map.lookup_mut(&key) {
+            Some(handle) => Some(std::mem::replace(self.store.get_mut(handle), value)),
+            None => {
+                let handle = self.store.store(value);
+                self.map.insert(key, handle);
+                None
+            },
+        }
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let handle = self.map.remove(key)?;
+        Some(self.store.take(handle))
+    }
+
+    pub fn remove_at(&mut self, i: usize) -> Option<(K, V)> {
+        let (k, handle) = self.map.remove_at(i)?;
+        Some((k, self.store.take(handle)))
+    }
+
+    /// Rebuilds the tree as `IndexableSortedMap::compact` does. Only the
+    /// handles move; the values stay where the store put them.
+    pub fn compact(&mut self) {
+        self.map.compact();
+    }
+
+    pub fn iter(&self) -> SeparatedIter<'_, K, V, S> {
+        SeparatedIter { range: self.map.range_by_index(..), store: &self.store }
+    }
+
+    /// Iterates over the entries whose keys lie in `range`, from either
+    /// end.
+    pub fn range<R: RangeBounds<K>>(&self, range: R) -> SeparatedIter<'_, K, V, S> {
+        SeparatedIter { range: self.map.range(range), store: &self.store }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+}
+
+impl<'a, K: Ord, V: 'a, S: ValueStore<V>> Iterator for SeparatedIter<'a, K, V, S> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.range.next().map(|(k, handle)| (k, self.store.get(handle)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<'a, K: Ord, V: 'a, S: ValueStore<V>> DoubleEndedIterator for SeparatedIter<'a, K, V, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.range.next_back().map(|(k, handle)| (k, self.store.get(handle)))
+    }
+}
+
+impl<'a, K: Ord, V: 'a, S: ValueStore<V>> ExactSizeIterator for SeparatedIter<'a, K, V, S> {}
@@ -0,0 +1,103 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+
+use crate::IndexableSortedMap;
+
+impl<K: Ord + Serialize, V: Serialize> Serialize for IndexableSortedMap<K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+
+        for (k, v) in self.iter() {
+            map.serialize_entry(k, v)?;
+        }
+
+        map.end()
+    }
+}
+
+// Keys and values are deserialized with the input's lifetime, so a map of
+// `&'de str` or `&'de [u8]` borrows straight from the buffer.
+struct MapVisitor<K, V> {
+    marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<'de, K, V> Visitor<'de> for MapVisitor<K, V>
+where
+    K: Ord + Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    type Value = IndexableSortedMap<K, V>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
+        let mut entries: Vec<(K, V)> = Vec::with_capacity(access.size_hint().unwrap_or(0).min(4096));
+
+        while let Some(entry) = access.next_entry()? {
+            entries.push(entry);
+        }
+
+        // Serialized maps are normally already in order, but a `BTreeMap`
+        // would accept any order and let the last repeated key win.
+        Ok(IndexableSortedMap::from_unsorted(entries))
+    }
+}
+
+// Collects entries into a buffer the caller owns, so that the ones read
+// before an error outlive it.
+struct LenientVisitor<'a, K, V> {
+    entries: &'a mut Vec<(K, V)>,
+}
+
+impl<'de, 'a, K, V> Visitor<'de> for LenientVisitor<'a, K, V>
+where
+    K: Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a map")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<(), A::Error> {
+        while let Some(entry) = access.next_entry()? {
+            self.entries.push(entry);
+        }
+
+        Ok(())
+    }
+}
+
+impl<K: Ord, V> IndexableSortedMap<K, V> {
+    /// Like `deserialize`, but for damaged input such as a partially
+    /// written file: the entries read before the first one that fails to
+    /// decode are kept, and the error is returned alongside them instead of
+    /// in place of them. A format can't resume after an error, so that
+    /// entry and everything after it are lost.
+    pub fn deserialize_lenient<'de, D>(deserializer: D) -> (Self, Option<D::Error>)
+    where
+        D: Deserializer<'de>,
+        K: Deserialize<'de>,
+        V: Deserialize<'de>,
+    {
+        let mut entries = Vec::new();
+        let error = deserializer.deserialize_map(LenientVisitor { entries: &mut entries }).err();
+        (IndexableSortedMap::from_unsorted(entries), error)
+    }
+}
+
+impl<'de, K, V> Deserialize<'de> for IndexableSortedMap<K, V>
+where
+    K: Ord + Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(MapVisitor { marker: PhantomData })
+    }
+}
@@ -0,0 +1,299 @@
+use std::borrow::Borrow;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::{IndexableSortedMap, IntoIter, Iter};
+
+/// A sorted set that also allows lookup by index, as a map to `()`. The set
+/// operations work on whole subtrees: the smaller set is taken apart at its
+/// root, the larger split at the same elements, and the pieces combined
+/// recursively and joined back together, in O(m log(n/m)) for sets of
+/// sizes m <= n.
+pub struct IndexableSortedSet<T: Ord> {
+    map: IndexableSortedMap<T, ()>,
+}
+
+pub struct SetIter<'a, T: Ord> {
+    iter: Iter<'a, T, ()>,
+}
+
+pub struct SetIntoIter<T: Ord> {
+    iter: IntoIter<T, ()>,
+}
+
+impl<T: Ord> Default for IndexableSortedSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Ord> IndexableSortedSet<T> {
+    pub fn new() -> Self {
+        IndexableSortedSet { map: IndexableSortedMap::new() }
+    }
+
+    /// An empty set that takes its nodes from `arena`.
+    #[cfg(feature = "arena")]
+    pub fn new_in(arena: &crate::NodeArena<T, ()>) -> Self {
+        IndexableSortedSet { map: IndexableSortedMap::new_in(arena) }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Adds `value`, returning whether it was newly added.
+    pub fn insert(&mut self, value: T) -> bool {
+        self.map.insert(value, ()).is_none()
+    }
+
+    pub fn contains<Q: ?Sized + Ord>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.map.lookup(value).is_some()
+    }
+
+    /// Removes `value`, returning whether it was present.
+    pub fn remove<Q: ?Sized + Ord>(&mut self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+    {
+        self.map.remove(value).is_some()
+    }
+
+    pub fn index(&self, i: usize) -> Option<&T> {
+        self.map.index(i).map(|(k, _v)| k)
+    }
+
+    pub fn rank<Q: ?Sized + Ord>(&self, value: &Q) -> Result<usize, usize>
+    where
+        T: Borrow<Q>,
+    {
+        self.map.rank(value)
+    }
+
+    pub fn first(&self) -> Option<&T> {
+        self.map.first_key_value().map(|(k, _v)| k)
+    }
+
+    pub fn last(&self) -> Option<&T> {
+        self.map.last_key_value().map(|(k, _v)| k)
+    }
+
+    pub fn pop_first(&mut self) -> Option<T> {
+        self.map.pop_first().map(|(k, _v)| k)
+    }
+
+    pub fn pop_last(&mut self) -> Option<T> {
+        self.map.pop_last().map(|(k, _v)| k)
+    }
+
+    pub fn iter(&self) -> SetIter<'_, T> {
+        SetIter { iter: self.map.iter() }
+    }
+
+    /// Every element of either set.
+    pub fn union(self, other: Self) -> Self {
+        IndexableSortedSet { map: self.map.merge_with(other.map, |_k, (), ()| ()) }
+    }
+
+    /// The elements of both sets.
+    pub fn intersection(self, other: Self) -> Self {
+        IndexableSortedSet { map: intersection(self.map, other.map) }
+    }
+
+    /// The elements of `self` that are not in `other`.
+    pub fn difference(self, other: Self) -> Self {
+        IndexableSortedSet { map: difference(self.map, other.map) }
+    }
+
+    pub fn as_map(&self) -> &IndexableSortedMap<T, ()> {
+        &self.map
+    }
+
+    pub fn into_map(self) -> IndexableSortedMap<T, ()> {
+        self.map
+    }
+}
+
+type Map<T> = IndexableSortedMap<T, ()>;
+
+// Whether every element of one set lies below every element of the other.
+fn disjoint_ranges<T: Ord>(a: &Map<T>, b: &Map<T>) -> bool {
+    match (a.first_key_value(), a.last_key_value(), b.first_key_value(), b.last_key_value()) {
+        (Some((a_first, _)), Some((a_last, _)), Some((b_first, _)), Some((b_last, _))) => {
+            a_last < b_first || b_last < a_first
+        },
+        _ => true,
+    }
+}
+
+// Takes the smaller set apart at its root and splits the larger at the
+// same elements, pairing up the pieces in order as (from `a`, from `b`).
+fn pieces<T: Ord>(a: Map<T>, b: Map<T>) -> Vec<(Map<T>, Map<T>)> {
+    if b.len() <= a.len() {
+        let b = b.into_children();
+        let a = a.split_like(&b);
+        a.into_iter().zip(b).collect()
+    } else {
+        let a = a.into_children();
+        let b = b.split_like(&a);
+        a.into_iter().zip(b).collect()
+    }
+}
+
+fn concat<T: Ord>(mut result: Map<T>, parts: impl IntoIterator<Item = Map<T>>) -> Map<T> {
+    for mut part in parts {
+        result.concat(&mut part);
+    }
+
+    result
+}
+
+fn intersection<T: Ord>(a: Map<T>, b: Map<T>) -> Map<T> {
+    if disjoint_ranges(&a, &b) {
+        return a.empty_like();
+    }
+
+    if a.len() == 1 || b.len() == 1 {
+        let (single, other) = if a.len() == 1 { (a, b) } else { (b, a) };
+        let key = single.first_key_value().unwrap().0;
+        return if other.lookup(key).is_some() { single } else { single.empty_like() };
+    }
+
+    concat(a.empty_like(), pieces(a, b).into_iter().map(|(a, b)| intersection(a, b)))
+}
+
+fn difference<T: Ord>(mut a: Map<T>, b: Map<T>) -> Map<T> {
+    if disjoint_ranges(&a, &b) {
+        return a;
+    }
+
+    if b.len() == 1 {
+        a.remove(b.first_key_value().unwrap().0);
+        return a;
+    }
+
+    if a.len() == 1 {
+        let key = a.first_key_value().unwrap().0;
+        return if b.lookup(key).is_some() { a.empty_like() } else { a };
+    }
+
+    concat(a.empty_like(), pieces(a, b).into_iter().map(|(a, b)| difference(a, b)))
+}
+
+impl<T: Ord + Clone> Clone for IndexableSortedSet<T>
+where
+    IndexableSortedMap<T, ()>: Clone,
+{
+    fn clone(&self) -> Self {
+        IndexableSortedSet { map: self.map.clone() }
+    }
+}
+
+impl<T: Ord + fmt::Debug> fmt::Debug for IndexableSortedSet<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl<T: Ord> PartialEq for IndexableSortedSet<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.map == other.map
+    }
+}
+
+impl<T: Ord> Eq for IndexableSortedSet<T> {}
+
+impl<T: Ord> PartialOrd for IndexableSortedSet<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> Ord for IndexableSortedSet<T> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.map.cmp(&other.map)
+    }
+}
+
+impl<T: Ord + Hash> Hash for IndexableSortedSet<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.map.hash(state);
+    }
+}
+
+impl<T: Ord> FromIterator<T> for IndexableSortedSet<T> {
+    fn from_iter<I: IntoIterator<Item = T>>(values: I) -> Self {
+        IndexableSortedSet { map: values.into_iter().map(|value| (value, ())).collect() }
+    }
+}
+
+impl<T: Ord> Extend<T> for IndexableSortedSet<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, values: I) {
+        self.map.extend(values.into_iter().map(|value| (value, ())));
+    }
+}
+
+impl<T: Ord> IntoIterator for IndexableSortedSet<T> {
+    type Item = T;
+    type IntoIter = SetIntoIter<T>;
+
+    fn into_iter(self) -> SetIntoIter<T> {
+        SetIntoIter { iter: self.map.into_iter() }
+    }
+}
+
+impl<'a, T: Ord> IntoIterator for &'a IndexableSortedSet<T> {
+    type Item = &'a T;
+    type IntoIter = SetIter<'a, T>;
+
+    fn into_iter(self) -> SetIter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<'a, T: Ord> Iterator for SetIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        self.iter.next().map(|(k, _v)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, T: Ord> DoubleEndedIterator for SetIter<'a, T> {
+    fn next_back(&mut self) -> Option<&'a T> {
+        self.iter.next_back().map(|(k, _v)| k)
+    }
+}
+
+impl<'a, T: Ord> ExactSizeIterator for SetIter<'a, T> {}
+
+impl<T: Ord> Iterator for SetIntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.iter.next().map(|(k, _v)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<T: Ord> DoubleEndedIterator for SetIntoIter<T> {
+    fn next_back(&mut self) -> Option<T> {
+        self.iter.next_back().map(|(k, _v)| k)
+    }
+}
+
+impl<T: Ord> ExactSizeIterator for SetIntoIter<T> {}
@@ -0,0 +1,154 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::IndexableSortedMap;
+
+/// Splits the keyspace at fixed boundaries into independently locked
+/// shards. Positional queries (`index`, `rank`, `len`) combine per-shard
+/// sizes, so they are exact when no writer is racing with them and
+/// otherwise reflect some interleaving of the concurrent updates.
+pub struct ShardedIndexableSortedMap<K: Ord, V> {
+    boundaries: Vec<K>,
+    shards: Vec<RwLock<IndexableSortedMap<K, V>>>,
+    // Fenwick tree over the shard lengths, 1-based.
+    sizes: Vec<AtomicUsize>,
+}
+
+impl<K: Ord, V> ShardedIndexableSortedMap<K, V> {
+    pub fn new(boundaries: Vec<K>) -> Self {
+        assert!(
+            boundaries.windows(2).all(|w| w[0] < w[1]),
+            "shard boundaries must be strictly ascending",
+        );
+
+        let shard_count = boundaries.len() + 1;
+
+        ShardedIndexableSortedMap {
+            boundaries,
+            shards: (0..shard_count).map(|_| RwLock::new(IndexableSortedMap::new())).collect(),
+            sizes: (0..=shard_count).map(|_| AtomicUsize::new(0)).collect(),
+        }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    pub fn shard_of(&self, key: &K) -> usize {
+        self.boundaries.partition_point(|boundary| boundary <= key)
+    }
+
+    pub fn read_shard(&self, shard: usize) -> RwLockReadGuard<'_, IndexableSortedMap<K, V>> {
+        self.shards[shard].read().expect("shard lock poisoned")
+    }
+
+    fn write_shard(&self, shard: usize) -> RwLockWriteGuard<'_, IndexableSortedMap<K, V>> {
+        self.shards[shard].write().expect("shard lock poisoned")
+    }
+
+    pub fn len(&self) -> usize {
+        self.prefix(self.shard_count())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let shard = self.shard_of(&key);
+        let mut map = self.write_shard(shard);
+
+        let old = map.insert(key, value);
+        if old.is_none() {
+            self.grow(shard, 1);
+        }
+        old
+    }
+
+    pub fn remove(&self, key: &K) -> Option<V> {
+        let shard = self.shard_of(key);
+        let mut map = self.write_shard(shard);
+
+        let result = map.remove(key);
+        if result.is_some() {
+            self.shrink(shard, 1);
+        }
+        result
+    }
+
+    pub fn rank(&self, key: &K) -> Result<usize, usize> {
+        let shard = self.shard_of(key);
+        let map = self.read_shard(shard);
+        let offset = self.prefix(shard);
+
+        match map.rank(key) {
+            Ok(i) => Ok(offset + i),
+            Err(i) => Err(offset + i),
+        }
+    }
+
+    fn grow(&self, shard: usize, n: usize) {
+        let mut i = shard + 1;
+        while i < self.sizes.len() {
+            self.sizes[i].fetch_add(n, Ordering::AcqRel);
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    fn shrink(&self, shard: usize, n: usize) {
+        let mut i = shard + 1;
+        while i < self.sizes.len() {
+            self.sizes[i].fetch_sub(n, Ordering::AcqRel);
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    // Total length of the shards before `shard`.
+    fn prefix(&self, shard: usize) -> usize {
+        let mut total = 0;
+        let mut i = shard;
+        while i > 0 {
+            total += self.sizes[i].load(Ordering::Acquire);
+            i -= i & i.wrapping_neg();
+        }
+        total
+    }
+
+    // The shard holding global position `index`, and that position's
+    // offset within the shard.
+    fn locate(&self, index: usize) -> Option<(usize, usize)> {
+        let shard_count = self.shard_count();
+        let mut shard = 0;
+        let mut remaining = index;
+        let mut step = shard_count.next_power_of_two();
+
+        while step > 0 {
+            if shard + step <= shard_count {
+                let size = self.sizes[shard + step].load(Ordering::Acquire);
+                if size <= remaining {
+                    shard += step;
+                    remaining -= size;
+                }
+            }
+            step >>= 1;
+        }
+
+        if shard < shard_count {
+            Some((shard, remaining))
+        } else {
+            None
+        }
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> ShardedIndexableSortedMap<K, V> {
+    pub fn lookup(&self, key: &K) -> Option<V> {
+        self.read_shard(self.shard_of(key)).lookup(key).cloned()
+    }
+
+    pub fn index(&self, i: usize) -> Option<(K, V)> {
+        let (shard, offset) = self.locate(i)?;
+        let map = self.read_shard(shard);
+        map.index(offset).map(|(k, v)| (k.clone(), v.clone()))
+    }
+}
@@ -0,0 +1,328 @@
+use std::borrow::Borrow;
+use std::mem;
+use std::ops::RangeBounds;
+
+use crate::{IndexableSortedMap, InsertResult, IntoIter, Node, NodePool, Tree};
+
+// A subtree along with its height, leaves being at height 0. Splitting and
+// joining work on these so that heights never need to be measured again.
+type Piece<K, V> = Option<(Node<K, V>, usize)>;
+
+impl<K: Ord, V> IndexableSortedMap<K, V> {
+    /// Moves every entry with a key of at least `key` into a new map,
+    /// leaving the rest in `self`. The tree is cut along the search path
+    /// for `key` and each side reassembled from the subtrees hanging off
+    /// it, in O(log n).
+    pub fn split_off<Q: ?Sized + Ord>(&mut self, key: &Q) -> Self
+    where
+        K: Borrow<Q>,
+    {
+        let at = match self.key_position(key) {
+            Ok(i) | Err(i) => i,
+        };
+
+        self.split_off_at(at)
+    }
+
+    /// Splits the map into its first `at` entries and the rest, the same
+    /// way as `split_off`. An `at` past the end leaves the second map
+    /// empty.
+    pub fn split_at_index(mut self, at: usize) -> (Self, Self) {
+        let rest = self.split_off_at(at);
+        (self, rest)
+    }
+
+    /// Removes the entries whose keys lie in `range`, yielding them in key
+    /// order. The run is cut out with two splits and the remaining sides
+    /// joined, so the entries left behind cost O(log n) in total.
+    pub fn drain_range<Q: ?Sized + Ord, R: RangeBounds<Q>>(&mut self, range: R) -> IntoIter<K, V>
+    where
+        K: Borrow<Q>,
+    {
+        let (start, end) = self.range_positions(&range);
+        let mut drained = self.split_off_at(start);
+        let mut after = drained.split_off_at(end.saturating_sub(start));
+        self.concat(&mut after);
+
+        drained.into_iter()
+    }
+
+    // If every key of one map is less than every key of the other, joins
+    // `other` into `self` along their facing spines in O(log n), leaving
+    // `other` empty. Otherwise leaves both alone and returns false.
+    pub(crate) fn try_concat(&mut self, other: &mut Self) -> bool {
+        #[cfg(feature = "cow")]
+        self.pool.adopt(&other.pool);
+
+        let (Some((first, _)), Some((last, _))) = (self.first_key_value(), self.last_key_value()) else {
+            mem::swap(&mut self.root, &mut other.root);
+            return true;
+        };
+
+        let (Some((other_first, _)), Some((other_last, _))) = (other.first_key_value(), other.last_key_value()) else {
+            return true;
+        };
+
+        let ours_first = if last < other_first {
+            true
+        } else if other_last < first {
+            false
+        } else {
+            return false;
+        };
+
+        structural_span!("concat", len = self.len(), other = other.len());
+
+        let ours = piece(self.root.take());
+        let theirs = piece(other.root.take());
+        let (low, high) = if ours_first { (ours, theirs) } else { (theirs, ours) };
+        self.root = join(&mut self.pool, low, high).map(|(node, _)| node);
+        #[cfg(feature = "heatmap")]
+        self.heat.reshaped();
+        true
+    }
+
+    // Joins `other`, whose keys must all be greater than those of `self`,
+    // onto the end of `self`, leaving `other` empty. Panics if the two
+    // overlap, rather than lose either side's entries.
+    pub(crate) fn concat(&mut self, other: &mut Self) {
+        let joined = self.try_concat(other);
+        assert!(joined, "concatenated maps must not overlap");
+    }
+
+    // Breaks the map into one map per child of its root, or into halves if
+    // the root is a leaf of several entries.
+    pub(crate) fn into_children(mut self) -> Vec<Self> {
+        let Some(root) = self.root.take() else {
+            return Vec::new();
+        };
+
+        let children = match self.pool.take(root.tree) {
+            Tree::Leaf(mut entries) if entries.len() > 1 => {
+                let mut rest = self.pool.buffer();
+                rest.extend(entries.drain(entries.len() / 2..));
+                vec![self.pool.leaf(entries), self.pool.leaf(rest)]
+            },
+            Tree::Leaf(entries) => vec![self.pool.leaf(entries)],
+            Tree::Branch2(left, right) => vec![left, right],
+            Tree::Branch3(left, middle, right) => vec![left, middle, right],
+        };
+
+        children
+            .into_iter()
+            .map(|node| {
+                let mut map = IndexableSortedMap::with_pool(self.pool.sibling());
+                map.root = Some(node);
+                map
+            })
+            .collect()
+    }
+
+    // Splits the map at the first key of each of `maps` after the first,
+    // giving one piece per map.
+    pub(crate) fn split_like(mut self, maps: &[Self]) -> Vec<Self> {
+        let mut pieces = Vec::with_capacity(maps.len());
+
+        for map in maps.iter().skip(1).rev() {
+            if let Some((key, _)) = map.first_key_value() {
+                pieces.push(self.split_off(key));
+            }
+        }
+
+        pieces.push(self);
+        pieces.reverse();
+        pieces
+    }
+
+    // Moves the entries from position `at` onwards into a new map.
+    fn split_off_at(&mut self, at: usize) -> Self {
+        structural_span!("split", len = self.len(), at = at);
+
+        let mut other = IndexableSortedMap::with_pool(self.pool.sibling());
+
+        if let Some((root, height)) = piece(self.root.take()) {
+            let (left, right) = split(&mut self.pool, root, height, at);
+            self.root = left.map(|(node, _)| node);
+            other.root = right.map(|(node, _)| node);
+            #[cfg(feature = "heatmap")]
+            self.heat.reshaped();
+        }
+
+        other
+    }
+}
+
+impl<K: Ord, V> Node<K, V> {
+    fn height(&self) -> usize {
+        let mut node = self;
+        let mut height = 0;
+
+        while let Tree::Branch2(left, _) | Tree::Branch3(left, _, _) = node.tree.as_ref() {
+            node = left;
+            height += 1;
+        }
+
+        height
+    }
+}
+
+// Splits `node` into the entries before position `at` and the rest. The
+// children off the path are grouped into at most one node per side, which
+// are then joined with the halves of the child the cut passes through.
+// Each join costs the difference in heights, and these telescope, so the
+// whole split is O(height).
+fn split<K: Ord, V>(
+    pool: &mut NodePool<K, V>,
+    node: Node<K, V>,
+    height: usize,
+    at: usize,
+) -> (Piece<K, V>, Piece<K, V>) {
+    if at == 0 {
+        return (None, Some((node, height)));
+    }
+
+    if at >= node.size {
+        return (Some((node, height)), None);
+    }
+
+    let children = match pool.take(node.tree) {
+        Tree::Leaf(mut entries) => {
+            let mut rest = pool.buffer();
+            rest.extend(entries.drain(at..));
+            return (Some((pool.leaf(entries), 0)), Some((pool.leaf(rest), 0)));
+        },
+        Tree::Branch2(left, right) => vec![left, right],
+        Tree::Branch3(left, middle, right) => vec![left, middle, right],
+    };
+
+    let mut before = Vec::new();
+    let mut offset = 0;
+    let mut children = children.into_iter();
+
+    for child in children.by_ref() {
+        if at < offset + child.size {
+            let (left, right) = split(pool, child, height - 1, at - offset);
+            let after: Vec<_> = children.collect();
+
+            let before = group(pool, before, height - 1);
+            let after = group(pool, after, height - 1);
+
+            return (join(pool, before, left), join(pool, right, after));
+        }
+
+        offset += child.size;
+        before.push(child);
+    }
+
+    unreachable!("the cut lies within the node")
+}
+
+fn piece<K: Ord, V>(root: Option<Node<K, V>>) -> Piece<K, V> {
+    root.map(|node| {
+        let height = node.height();
+        (node, height)
+    })
+}
+
+// Gathers up to three siblings of the given height into a single piece.
+fn group<K: Ord, V>(pool: &mut NodePool<K, V>, mut nodes: Vec<Node<K, V>>, height: usize) -> Piece<K, V> {
+    match nodes.len() {
+        0 => None,
+        1 => Some((nodes.pop().unwrap(), height)),
+        2 => {
+            let right = nodes.pop().unwrap();
+            let left = nodes.pop().unwrap();
+            Some((pool.branch2(left, right), height + 1))
+        },
+        _ => {
+            let right = nodes.pop().unwrap();
+            let middle = nodes.pop().unwrap();
+            let left = nodes.pop().unwrap();
+            Some((pool.branch3(left, middle, right), height + 1))
+        },
+    }
+}
+
+// Joins two pieces, every key of `left` being less than every key of
+// `right`, by hanging the shorter one off the facing spine of the taller
+// and splitting upwards as for an insertion.
+fn join<K: Ord, V>(pool: &mut NodePool<K, V>, left: Piece<K, V>, right: Piece<K, V>) -> Piece<K, V> {
+    let ((left, left_height), (right, right_height)) = match (left, right) {
+        (None, piece) | (piece, None) => return piece,
+        (Some(left), Some(right)) => (left, right),
+    };
+
+    let height = left_height.max(right_height);
+    let result = if left_height >= right_height {
+        join_right(pool, left, left_height, right, right_height)
+    } else {
+        join_left(pool, left, left_height, right, right_height)
+    };
+
+    match result {
+        InsertResult::SameDepth(node) => Some((node, height)),
+        InsertResult::Overflow(left, right) => Some((pool.branch2(left, right), height + 1)),
+    }
+}
+
+// Hangs `right` off the right spine of the taller (or equal) `left`.
+fn join_right<K: Ord, V>(
+    pool: &mut NodePool<K, V>,
+    left: Node<K, V>,
+    left_height: usize,
+    right: Node<K, V>,
+    right_height: usize,
+) -> InsertResult<K, V> {
+    if left_height == right_height {
+        return InsertResult::Overflow(left, right);
+    }
+
+    match pool.take(left.tree) {
+        Tree::Leaf(..) => unreachable!("a leaf is never taller"),
+        Tree::Branch2(a, b) => {
+            match join_right(pool, b, left_height - 1, right, right_height) {
+                InsertResult::SameDepth(b) => InsertResult::SameDepth(pool.branch2(a, b)),
+                InsertResult::Overflow(b, c) => InsertResult::SameDepth(pool.branch3(a, b, c)),
+            }
+        },
+        Tree::Branch3(a, b, c) => {
+            match join_right(pool, c, left_height - 1, right, right_height) {
+                InsertResult::SameDepth(c) => InsertResult::SameDepth(pool.branch3(a, b, c)),
+                InsertResult::Overflow(c, d) => {
+                    InsertResult::Overflow(pool.branch2(a, b), pool.branch2(c, d))
+                },
+            }
+        },
+    }
+}
+
+// Hangs `left` off the left spine of the taller `right`.
+fn join_left<K: Ord, V>(
+    pool: &mut NodePool<K, V>,
+    left: Node<K, V>,
+    left_height: usize,
+    right: Node<K, V>,
+    right_height: usize,
+) -> InsertResult<K, V> {
+    if left_height == right_height {
+        return InsertResult::Overflow(left, right);
+    }
+
+    match pool.take(right.tree) {
+        Tree::Leaf(..) => unreachable!("a leaf is never taller"),
+        Tree::Branch2(b, c) => {
+            match join_left(pool, left, left_height, b, right_height - 1) {
+                InsertResult::SameDepth(b) => InsertResult::SameDepth(pool.branch2(b, c)),
+                InsertResult::Overflow(a, b) => InsertResult::SameDepth(pool.branch3(a, b, c)),
+            }
+        },
+        Tree::Branch3(b, c, d) => {
+            match join_left(pool, left, left_height, b, right_height - 1) {
+                InsertResult::SameDepth(b) => InsertResult::SameDepth(pool.branch3(b, c, d)),
+                InsertResult::Overflow(a, b) => {
+                    InsertResult::Overflow(pool.branch2(a, b), pool.branch2(c, d))
+                },
+            }
+        },
+    }
+}
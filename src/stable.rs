@@ -0,0 +1,19 @@
+use crate::{BoxStore, IndexableSortedMap, SeparatedIndexableSortedMap};
+
+/// A map whose values never move while they are in it. Each value gets
+/// its own allocation, and rebalancing, bulk rebuilds and `compact` only
+/// move the pointers to them. The address of a value, as returned by
+/// `lookup` or `index`, stays valid until that entry is removed or the map
+/// is dropped. Replacing a value with `insert` writes it in place, at the
+/// same address.
+pub type StableIndexableSortedMap<K, V> = SeparatedIndexableSortedMap<K, V, BoxStore>;
+
+impl<K: Ord, V> SeparatedIndexableSortedMap<K, V, BoxStore> {
+    pub fn as_map(&self) -> &IndexableSortedMap<K, Box<V>> {
+        &self.map
+    }
+
+    pub fn into_inner(self) -> IndexableSortedMap<K, Box<V>> {
+        self.map
+    }
+}
@@ -0,0 +1,117 @@
+use std::ops::{Bound, RangeBounds};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use futures_core::Stream;
+
+use crate::{IndexableSortedMap, TreeZipper};
+
+pub struct EntryStream<'a, K: Ord + Clone, V: Clone> {
+    zipper: Option<TreeZipper<'a, K, V>>,
+    end: Bound<K>,
+    batch_size: usize,
+}
+
+pub struct IntoEntryStream<K: Ord, V> {
+    entries: crate::IntoEntries<K, V>,
+    batch_size: usize,
+}
+
+impl<K: Ord + Clone, V: Clone> IndexableSortedMap<K, V> {
+    pub fn stream(&self, batch_size: usize) -> EntryStream<'_, K, V> {
+        self.stream_range(.., batch_size)
+    }
+
+    pub fn stream_range<R: RangeBounds<K>>(&self, range: R, batch_size: usize) -> EntryStream<'_, K, V> {
+        assert!(batch_size > 0, "batch_size must be non-zero");
+
+        let zipper = match range.start_bound() {
+            Bound::Unbounded => self.zipper(),
+            Bound::Included(k) => self.zipper().and_then(|z| z.advance_to(k)),
+            Bound::Excluded(k) => {
+                self.zipper().and_then(|z| z.advance_to(k)).and_then(|z| {
+                    if z.focus().0 == k {
+                        z.advance(1)
+                    } else {
+                        Some(z)
+                    }
+                })
+            },
+        };
+
+        EntryStream {
+            zipper,
+            end: range.end_bound().cloned(),
+            batch_size,
+        }
+    }
+}
+
+impl<K: Ord, V> IndexableSortedMap<K, V> {
+    pub fn into_stream(self, batch_size: usize) -> IntoEntryStream<K, V> {
+        assert!(batch_size > 0, "batch_size must be non-zero");
+
+        IntoEntryStream {
+            entries: self.into_entries(),
+            batch_size,
+        }
+    }
+}
+
+impl<'a, K: Ord + Clone, V: Clone> EntryStream<'a, K, V> {
+    fn in_range(&self, k: &K) -> bool {
+        match &self.end {
+            Bound::Unbounded => true,
+            Bound::Included(end) => k <= end,
+            Bound::Excluded(end) => k < end,
+        }
+    }
+}
+
+impl<'a, K: Ord + Clone, V: Clone> Unpin for EntryStream<'a, K, V> {}
+
+impl<'a, K: Ord + Clone, V: Clone> Stream for EntryStream<'a, K, V> {
+    type Item = Vec<(K, V)>;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut batch = Vec::new();
+
+        while batch.len() < self.batch_size {
+            let zipper = match self.zipper.take() {
+                None => break,
+                Some(zipper) => zipper,
+            };
+
+            let (k, v) = zipper.focus();
+            if !self.in_range(k) {
+                break;
+            }
+
+            batch.push((k.clone(), v.clone()));
+            self.zipper = zipper.advance(1);
+        }
+
+        if batch.is_empty() {
+            Poll::Ready(None)
+        } else {
+            Poll::Ready(Some(batch))
+        }
+    }
+}
+
+impl<K: Ord, V> Unpin for IntoEntryStream<K, V> {}
+
+impl<K: Ord, V> Stream for IntoEntryStream<K, V> {
+    type Item = Vec<(K, V)>;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let batch_size = self.batch_size;
+        let batch: Vec<(K, V)> = self.entries.by_ref().take(batch_size).collect();
+
+        if batch.is_empty() {
+            Poll::Ready(None)
+        } else {
+            Poll::Ready(Some(batch))
+        }
+    }
+}
@@ -0,0 +1,173 @@
+//! Serialization that records the tree's exact shape along with its
+//! entries, so the deserialized map is node-for-node identical to the
+//! original rather than rebuilt into canonical shape. Use it directly, or
+//! on a field with `#[serde(with = "indexable_sorted_map::structured")]`.
+//!
+//! The encoding is a pair: the arity of every node in pre-order (0 for a
+//! leaf, followed by its number of entries, and 2 or 3 for a branch), then
+//! the entries in key order.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeSeq, SerializeTuple, Serializer};
+
+use crate::{IndexableSortedMap, Node, NodePool, Tree, LEAF_CAPACITY};
+
+pub fn serialize<K, V, S>(map: &IndexableSortedMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    K: Ord + Serialize,
+    V: Serialize,
+    S: Serializer,
+{
+    let mut tuple = serializer.serialize_tuple(2)?;
+    tuple.serialize_element(&Shape(map))?;
+    tuple.serialize_element(&Entries(map))?;
+    tuple.end()
+}
+
+pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<IndexableSortedMap<K, V>, D::Error>
+where
+    K: Ord + Deserialize<'de>,
+    V: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_tuple(2, StructuredVisitor { marker: PhantomData })
+}
+
+struct Shape<'a, K: Ord, V>(&'a IndexableSortedMap<K, V>);
+
+struct Entries<'a, K: Ord, V>(&'a IndexableSortedMap<K, V>);
+
+impl<'a, K: Ord, V> Serialize for Shape<'a, K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(None)?;
+        let mut stack: Vec<&Node<K, V>> = self.0.root.iter().collect();
+
+        while let Some(node) = stack.pop() {
+            match node.tree.as_ref() {
+                Tree::Leaf(entries) => {
+                    seq.serialize_element(&0u8)?;
+                    seq.serialize_element(&(entries.len() as u8))?;
+                },
+                Tree::Branch2(left, right) => {
+                    seq.serialize_element(&2u8)?;
+                    stack.extend([right, left]);
+                },
+                Tree::Branch3(left, middle, right) => {
+                    seq.serialize_element(&3u8)?;
+                    stack.extend([right, middle, left]);
+                },
+            }
+        }
+
+        seq.end()
+    }
+}
+
+impl<'a, K: Ord + Serialize, V: Serialize> Serialize for Entries<'a, K, V> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+
+        for entry in self.0.iter() {
+            seq.serialize_element(&entry)?;
+        }
+
+        seq.end()
+    }
+}
+
+struct StructuredVisitor<K, V> {
+    marker: PhantomData<fn() -> (K, V)>,
+}
+
+impl<'de, K, V> Visitor<'de> for StructuredVisitor<K, V>
+where
+    K: Ord + Deserialize<'de>,
+    V: Deserialize<'de>,
+{
+    type Value = IndexableSortedMap<K, V>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a tree shape followed by its entries")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let shape: Vec<u8> = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let entries: Vec<(K, V)> = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+        if !entries.windows(2).all(|pair| pair[0].0 < pair[1].0) {
+            return Err(de::Error::custom("entries are not in strictly ascending key order"));
+        }
+
+        // A tree of n leaves is at most log2(n) deep, which bounds the
+        // recursion however the shape is crafted.
+        let max_height = (usize::BITS - entries.len().leading_zeros()) as usize;
+
+        let mut map = IndexableSortedMap::new();
+        let mut shape = shape.into_iter();
+        let mut entries = entries.into_iter();
+
+        if shape.len() > 0 {
+            let (root, _height) = build(&mut shape, &mut entries, &mut map.pool, max_height).map_err(de::Error::custom)?;
+            map.root = Some(root);
+        }
+
+        if shape.len() > 0 || entries.len() > 0 {
+            return Err(de::Error::custom("the tree shape does not match the number of entries"));
+        }
+
+        Ok(map)
+    }
+}
+
+// Rebuilds one subtree from its pre-order arities, returning it with its
+// height so that unbalanced shapes can be rejected.
+fn build<K: Ord, V>(
+    shape: &mut impl Iterator<Item = u8>,
+    entries: &mut impl Iterator<Item = (K, V)>,
+    pool: &mut NodePool<K, V>,
+    max_height: usize,
+) -> Result<(Node<K, V>, usize), &'static str> {
+    let mismatch = "the tree shape does not match the number of entries";
+
+    match shape.next().ok_or(mismatch)? {
+        0 => {
+            let len = shape.next().ok_or(mismatch)? as usize;
+            if len == 0 || len > LEAF_CAPACITY {
+                return Err("a leaf holds too few or too many entries");
+            }
+
+            let mut leaf = pool.buffer();
+            leaf.extend(entries.take(len));
+            if leaf.len() < len {
+                return Err(mismatch);
+            }
+
+            Ok((pool.leaf(leaf), 0))
+        },
+        arity @ (2 | 3) => {
+            let max_height = max_height.checked_sub(1).ok_or("the tree shape is too deep")?;
+            let (left, height) = build(shape, entries, pool, max_height)?;
+            let (middle, middle_height) = build(shape, entries, pool, max_height)?;
+
+            if arity == 2 {
+                if middle_height != height {
+                    return Err("the tree shape is unbalanced");
+                }
+
+                Ok((pool.branch2(left, middle), height + 1))
+            } else {
+                let (right, right_height) = build(shape, entries, pool, max_height)?;
+
+                if middle_height != height || right_height != height {
+                    return Err("the tree shape is unbalanced");
+                }
+
+                Ok((pool.branch3(left, middle, right), height + 1))
+            }
+        },
+        _ => Err("node arities must be 0, 2 or 3"),
+    }
+}
@@ -0,0 +1,127 @@
+use std::fmt;
+use std::ptr::NonNull;
+
+use crate::{IndexableSortedMap, Node, Tree, LEAF_CAPACITY};
+
+/// The first structural fault `check_invariants` found. `position` is the
+/// index of the first entry in the offending subtree or leaf, counting the
+/// entries laid out before it as they are in the tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvariantError {
+    /// A node's cached size differs from the number of entries below it.
+    WrongSize { position: usize, cached: usize, actual: usize },
+    /// A node's cached smallest key isn't the first entry below it.
+    StaleMinKey { position: usize },
+    /// The entry at `position` isn't greater than the one before it.
+    OutOfOrder { position: usize },
+    /// A leaf holds no entries, or more than a leaf may.
+    LeafSize { position: usize, len: usize },
+    /// A leaf lies at a different depth from the leftmost one.
+    UnevenDepth { position: usize, depth: usize, expected: usize },
+}
+
+// What the walk carries from one leaf to the next.
+struct Audit<'a, K> {
+    position: usize,
+    last_key: Option<&'a K>,
+    leaf_depth: Option<usize>,
+}
+
+impl<K: Ord, V> IndexableSortedMap<K, V> {
+    /// Walks the whole tree checking that it is well formed: every node's
+    /// size and smallest key match what lies below it, keys ascend
+    /// strictly, leaves are neither empty nor overfull, and all leaves sit
+    /// at the same depth. This is O(n), meant for tests and fuzzing.
+    pub fn check_invariants(&self) -> Result<(), InvariantError> {
+        let Some(root) = &self.root else {
+            return Ok(());
+        };
+
+        let mut audit = Audit {
+            position: 0,
+            last_key: None,
+            leaf_depth: None,
+        };
+        audit.node(root, 0).map(|_first| ())
+    }
+}
+
+impl<'a, K: Ord> Audit<'a, K> {
+    // Checks a subtree, returning a pointer to its actual first entry.
+    fn node<V>(&mut self, node: &'a Node<K, V>, depth: usize) -> Result<NonNull<(K, V)>, InvariantError> {
+        let start = self.position;
+        let first = match node.tree.as_ref() {
+            Tree::Leaf(entries) => self.leaf(entries, depth)?,
+            Tree::Branch2(left, right) => {
+                let first = self.node(left, depth + 1)?;
+                self.node(right, depth + 1)?;
+                first
+            },
+            Tree::Branch3(left, middle, right) => {
+                let first = self.node(left, depth + 1)?;
+                self.node(middle, depth + 1)?;
+                self.node(right, depth + 1)?;
+                first
+            },
+        };
+
+        let actual = self.position - start;
+        if node.size != actual {
+            return Err(InvariantError::WrongSize {
+                position: start,
+                cached: node.size,
+                actual,
+            });
+        }
+        if node.first != first {
+            return Err(InvariantError::StaleMinKey { position: start });
+        }
+        Ok(first)
+    }
+
+    fn leaf<V>(&mut self, entries: &'a [(K, V)], depth: usize) -> Result<NonNull<(K, V)>, InvariantError> {
+        let position = self.position;
+        if entries.is_empty() || entries.len() > LEAF_CAPACITY {
+            return Err(InvariantError::LeafSize { position, len: entries.len() });
+        }
+
+        let expected = *self.leaf_depth.get_or_insert(depth);
+        if depth != expected {
+            return Err(InvariantError::UnevenDepth { position, depth, expected });
+        }
+
+        for (k, _v) in entries {
+            if self.last_key.is_some_and(|last| last >= k) {
+                return Err(InvariantError::OutOfOrder { position: self.position });
+            }
+            self.last_key = Some(k);
+            self.position += 1;
+        }
+
+        Ok(NonNull::from(&entries[0]))
+    }
+}
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantError::WrongSize { position, cached, actual } => {
+                write!(f, "the subtree at position {} caches size {} but holds {} entries", position, cached, actual)
+            },
+            InvariantError::StaleMinKey { position } => {
+                write!(f, "the subtree at position {} caches a stale smallest key", position)
+            },
+            InvariantError::OutOfOrder { position } => {
+                write!(f, "the entry at position {} is out of order", position)
+            },
+            InvariantError::LeafSize { position, len } => {
+                write!(f, "the leaf at position {} holds {} entries", position, len)
+            },
+            InvariantError::UnevenDepth { position, depth, expected } => {
+                write!(f, "the leaf at position {} is at depth {} rather than {}", position, depth, expected)
+            },
+        }
+    }
+}
+
+impl std::error::Error for InvariantError {}
@@ -0,0 +1,141 @@
+use std::borrow::Borrow;
+use std::mem;
+use std::ops::Bound;
+
+use crate::{AugmentedIter, AugmentedSortedMap, Measure};
+
+/// A sorted map whose entries each carry an integer weight, for finding
+/// the entry that covers a given point of the cumulative weight in
+/// O(log n). The entries lay their weights end to end in key order, so
+/// an entry of weight w covers w consecutive points and one of weight 0
+/// covers none. Each subtree keeps the sum of the weights within it.
+pub struct WeightedSortedMap<K: Ord, V> {
+    map: AugmentedSortedMap<K, (u64, V), WeightSum>,
+}
+
+// Summarizes a subtree by the total weight of its entries.
+struct WeightSum;
+
+impl<K, V> Measure<K, (u64, V)> for WeightSum {
+    type Summary = u64;
+
+    fn measure(_key: &K, (weight, _value): &(u64, V)) -> u64 {
+        *weight
+    }
+
+    fn combine(left: &u64, right: &u64) -> u64 {
+        left + right
+    }
+
+    fn empty() -> u64 {
+        0
+    }
+}
+
+pub struct WeightedIter<'a, K: Ord, V> {
+    iter: AugmentedIter<'a, K, (u64, V), WeightSum>,
+}
+
+impl<K: Ord, V> Default for WeightedSortedMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord, V> WeightedSortedMap<K, V> {
+    pub fn new() -> Self {
+        WeightedSortedMap { map: AugmentedSortedMap::new() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Inserts an entry with the given weight, returning the value it
+    /// displaced if `key` was already present.
+    pub fn insert(&mut self, key: K, value: V, weight: u64) -> Option<V> {
+        self.map.insert(key, (weight, value)).map(|(_weight, value)| value)
+    }
+
+    pub fn lookup<Q: ?Sized + Ord>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+    {
+        self.map.lookup(key).map(|(_weight, value)| value)
+    }
+
+    pub fn weight<Q: ?Sized + Ord>(&self, key: &Q) -> Option<u64>
+    where
+        K: Borrow<Q>,
+    {
+        self.map.lookup(key).map(|(weight, _value)| *weight)
+    }
+
+    /// Changes the weight of the entry under `key`, returning its old
+    /// weight.
+    pub fn set_weight<Q: ?Sized + Ord>(&mut self, key: &Q, weight: u64) -> Option<u64>
+    where
+        K: Borrow<Q>,
+    {
+        self.map.update(key, |(old, _value)| mem::replace(old, weight))
+    }
+
+    pub fn remove<Q: ?Sized + Ord>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+    {
+        self.map.remove(key).map(|(_weight, value)| value)
+    }
+
+    pub fn index(&self, i: usize) -> Option<(&K, &V)> {
+        self.map.index(i).map(|(k, (_weight, value))| (k, value))
+    }
+
+    pub fn total_weight(&self) -> u64 {
+        self.map.summary()
+    }
+
+    /// The total weight of the entries with keys less than `key`, which
+    /// is the first point that `key` covers if it is present.
+    pub fn weight_before<Q: ?Sized + Ord>(&self, key: &Q) -> u64
+    where
+        K: Borrow<Q>,
+    {
+        self.map.query_range::<Q, _>((Bound::Unbounded, Bound::Excluded(key)))
+    }
+
+    /// The entry covering point `w` of the cumulative weight, which is
+    /// `None` if `w` is at least the total weight.
+    pub fn select_by_weight(&self, w: u64) -> Option<(&K, &V)> {
+        self.map.find_prefix(|total| *total > w).map(|(_i, k, (_weight, value))| (k, value))
+    }
+
+    /// Iterates over the entries in key order, with their weights.
+    pub fn iter(&self) -> WeightedIter<'_, K, V> {
+        WeightedIter { iter: self.map.iter() }
+    }
+}
+
+impl<'a, K: Ord, V> Iterator for WeightedIter<'a, K, V> {
+    type Item = (&'a K, &'a V, u64);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next().map(|(k, (weight, value))| (k, value, *weight))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a, K: Ord, V> DoubleEndedIterator for WeightedIter<'a, K, V> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.iter.next_back().map(|(k, (weight, value))| (k, value, *weight))
+    }
+}
+
+impl<'a, K: Ord, V> ExactSizeIterator for WeightedIter<'a, K, V> {}
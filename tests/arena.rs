@@ -0,0 +1,164 @@
+#![cfg(feature = "arena")]
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+use indexable_sorted_map::{IndexableSortedMap, IndexableSortedSet, NodeArena, SortedMapBuilder};
+
+// Counts the leaf vectors allocated on the current thread, so tests
+// running in parallel don't disturb each other's counts. A leaf vector
+// has room for 17 entries, one more than a leaf holds, and nothing else
+// the tests allocate has that layout.
+struct Counting;
+
+thread_local! {
+    static LEAF_VECTORS: Cell<usize> = const { Cell::new(0) };
+}
+
+fn is_leaf_vector(layout: Layout) -> bool {
+    layout == Layout::new::<[(u32, u32); 17]>() || layout == Layout::new::<[(u32, ()); 17]>()
+}
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if is_leaf_vector(layout) {
+            let _ = LEAF_VECTORS.try_with(|count| count.set(count.get() + 1));
+        }
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+const LEN: u32 = 10_000;
+
+fn leaf_vectors<T>(f: impl FnOnce() -> T) -> (T, usize) {
+    let before = LEAF_VECTORS.with(Cell::get);
+    let result = f();
+    (result, LEAF_VECTORS.with(Cell::get) - before)
+}
+
+// Runs `op` on the input `setup` makes, first without an arena and then
+// with one, and checks that the leaves `op` makes come from the arena.
+fn draws_on_arena<I, T>(
+    name: &str,
+    setup: impl Fn(Option<&NodeArena<u32, u32>>) -> I,
+    op: impl Fn(I, Option<&NodeArena<u32, u32>>) -> T,
+) {
+    let input = setup(None);
+    let (_result, plain) = leaf_vectors(|| op(input, None));
+    assert!(plain > 0, "{}: makes no leaves", name);
+
+    // Merging maps whose keys interleave leaves many small leaves, so
+    // the arena is stocked for several times the entries.
+    let arena = NodeArena::with_capacity(LEN as usize * 32);
+    let input = setup(Some(&arena));
+    let (_result, pooled) = leaf_vectors(|| op(input, Some(&arena)));
+    assert_eq!(pooled, 0, "{}: {} leaf vectors allocated despite the arena", name, pooled);
+}
+
+fn map(arena: Option<&NodeArena<u32, u32>>, keys: impl Iterator<Item = u32>) -> IndexableSortedMap<u32, u32> {
+    let mut builder = match arena {
+        Some(arena) => SortedMapBuilder::new_in(arena),
+        None => SortedMapBuilder::new(),
+    };
+    for k in keys {
+        builder.push(k, k).unwrap();
+    }
+    builder.finish()
+}
+
+fn evens(arena: Option<&NodeArena<u32, u32>>) -> IndexableSortedMap<u32, u32> {
+    map(arena, (0..LEN).map(|k| k * 2))
+}
+
+fn odds(arena: Option<&NodeArena<u32, u32>>) -> IndexableSortedMap<u32, u32> {
+    map(arena, (0..LEN).map(|k| k * 2 + 1))
+}
+
+#[test]
+fn bulk_builds_draw_on_the_arena() {
+    draws_on_arena("build", |_arena| (), |(), arena| evens(arena));
+}
+
+#[test]
+fn merged_maps_stay_in_the_arena() {
+    let pair = |arena: Option<&NodeArena<u32, u32>>| (evens(arena), odds(arena));
+    draws_on_arena("merge_with", pair, |(a, b), _arena| a.merge_with(b, |_k, ours, _theirs| ours));
+    draws_on_arena("append", pair, |(mut a, mut b), _arena| {
+        a.append(&mut b);
+        a
+    });
+    draws_on_arena("extend", evens, |mut a, _arena| {
+        a.extend((0..LEN).map(|k| (k * 2 + 1, k)));
+        a
+    });
+}
+
+#[test]
+fn rebuilt_maps_stay_in_the_arena() {
+    draws_on_arena("retain", evens, |mut a, _arena| {
+        a.retain(|k, _v| k % 3 != 0);
+        a
+    });
+    // With `cow` the leaves are only copied once the clone is edited.
+    draws_on_arena("clone", evens, |a, _arena| {
+        let mut b = a.clone();
+        b.values_mut().for_each(|v| *v += 1);
+        (a, b)
+    });
+}
+
+// With the arena's own stock freed, rebuilding can only draw on what
+// emptying the map gave back.
+#[test]
+fn emptied_maps_give_back_to_the_arena() {
+    let emptied = |arena: Option<&NodeArena<u32, u32>>| {
+        let map = evens(arena);
+        if let Some(arena) = arena {
+            arena.shrink_to_fit();
+        }
+        map
+    };
+
+    draws_on_arena("into_iter", emptied, |a, arena| {
+        let keys: Vec<u32> = a.into_iter().map(|(k, _v)| k).collect();
+        map(arena, keys.into_iter())
+    });
+    draws_on_arena("drain", emptied, |mut a, arena| {
+        let keys: Vec<u32> = a.drain().map(|(k, _v)| k).collect();
+        (map(arena, keys.into_iter()), a)
+    });
+}
+
+#[test]
+fn set_algebra_stays_in_the_arena() {
+    let sets = |arena: Option<&NodeArena<u32, ()>>| {
+        let set = |keys: std::ops::Range<u32>| {
+            let mut set = match arena {
+                Some(arena) => IndexableSortedSet::new_in(arena),
+                None => IndexableSortedSet::new(),
+            };
+            for k in keys {
+                set.insert(k);
+            }
+            set
+        };
+        (set(0..LEN), set(LEN / 2..LEN * 2))
+    };
+
+    let (a, b) = sets(None);
+    let (_both, plain) = leaf_vectors(|| a.intersection(b));
+    assert!(plain > 0);
+
+    let arena = NodeArena::with_capacity(LEN as usize * 32);
+    let (a, b) = sets(Some(&arena));
+    let (both, pooled) = leaf_vectors(|| a.intersection(b));
+    assert_eq!(both.len(), LEN as usize / 2);
+    assert_eq!(pooled, 0, "intersection: {} leaf vectors allocated despite the arena", pooled);
+}
@@ -0,0 +1,190 @@
+// Edits an augmented map and the interval and weighted maps built on it
+// alongside a `BTreeMap`, checking every query against a brute-force scan
+// of the oracle. Keys come from a small range so that the trees grow
+// several levels deep while inserts still often replace.
+
+use std::collections::BTreeMap;
+use std::ops::{Bound, RangeBounds};
+
+use indexable_sorted_map::{AugmentedSortedMap, IntervalMap, Measure, WeightedSortedMap};
+use proptest::prelude::*;
+
+const KEYS: u16 = 512;
+
+// The sum and the largest of the values, which between them cover an
+// invertible and a non-invertible fold.
+struct SumMax;
+
+impl Measure<u16, u32> for SumMax {
+    type Summary = (u64, Option<u32>);
+
+    fn measure(_key: &u16, value: &u32) -> Self::Summary {
+        (u64::from(*value), Some(*value))
+    }
+
+    fn combine(left: &Self::Summary, right: &Self::Summary) -> Self::Summary {
+        (left.0 + right.0, left.1.max(right.1))
+    }
+
+    fn empty() -> Self::Summary {
+        (0, None)
+    }
+}
+
+fn fold<'a>(entries: impl IntoIterator<Item = (&'a u16, &'a u32)>) -> (u64, Option<u32>) {
+    entries
+        .into_iter()
+        .fold(SumMax::empty(), |summary, (k, v)| SumMax::combine(&summary, &SumMax::measure(k, v)))
+}
+
+#[derive(Debug, Clone)]
+enum Op {
+    Insert(u16, u32),
+    Remove(u16),
+    Update(u16, u32),
+    QueryRange(Bound<u16>, Bound<u16>),
+    QueryByIndex(usize, usize),
+    FindPrefix(u64),
+    Search(u16, u16, u32),
+}
+
+fn bound() -> impl Strategy<Value = Bound<u16>> {
+    prop_oneof![
+        Just(Bound::Unbounded),
+        (0..KEYS).prop_map(Bound::Included),
+        (0..KEYS).prop_map(Bound::Excluded),
+    ]
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let position = 0..KEYS as usize + 8;
+    prop_oneof![
+        6 => (0..KEYS, 0..1000u32).prop_map(|(k, v)| Op::Insert(k, v)),
+        3 => (0..KEYS).prop_map(Op::Remove),
+        2 => (0..KEYS, 0..1000u32).prop_map(|(k, v)| Op::Update(k, v)),
+        2 => (bound(), bound()).prop_map(|(a, b)| Op::QueryRange(a, b)),
+        1 => (position.clone(), position).prop_map(|(a, b)| Op::QueryByIndex(a, b)),
+        1 => (0..300_000u64).prop_map(Op::FindPrefix),
+        1 => (0..KEYS, 0..KEYS, 0..1000u32).prop_map(|(a, b, v)| Op::Search(a, b, v)),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn summaries_match_a_scan_of_the_oracle(ops in prop::collection::vec(op(), 0..400)) {
+        let mut map: AugmentedSortedMap<u16, u32, SumMax> = AugmentedSortedMap::new();
+        let mut oracle = BTreeMap::new();
+
+        for op in ops {
+            match op {
+                Op::Insert(k, v) => prop_assert_eq!(map.insert(k, v), oracle.insert(k, v)),
+                Op::Remove(k) => prop_assert_eq!(map.remove(&k), oracle.remove(&k)),
+                Op::Update(k, v) => {
+                    let expected = oracle.get_mut(&k).map(|old| std::mem::replace(old, v));
+                    prop_assert_eq!(map.update(&k, |old| std::mem::replace(old, v)), expected);
+                },
+                Op::QueryRange(a, b) => {
+                    let expected = fold(oracle.iter().filter(|(k, _v)| (a, b).contains(*k)));
+                    prop_assert_eq!(map.query_range((a, b)), expected);
+                },
+                Op::QueryByIndex(a, b) => {
+                    let expected = fold(oracle.iter().skip(a).take(b.saturating_sub(a)));
+                    prop_assert_eq!(map.query_by_index(a..b), expected);
+                },
+                Op::FindPrefix(w) => {
+                    let mut total = 0;
+                    let expected = oracle.iter().enumerate().find(|(_i, (_k, v))| {
+                        total += u64::from(**v);
+                        total > w
+                    });
+                    let found = map.find_prefix(|(sum, _max)| *sum > w);
+                    prop_assert_eq!(found, expected.map(|(i, (k, v))| (i, k, v)));
+                },
+                Op::Search(a, b, v) => {
+                    let expected: Vec<_> = oracle.range(a.min(b)..a.max(b)).filter(|(_k, value)| **value >= v).collect();
+                    let found: Vec<_> = map.search(a.min(b)..a.max(b), |(_sum, max)| max.is_some_and(|max| max >= v)).collect();
+                    prop_assert_eq!(found, expected);
+                },
+            }
+
+            prop_assert_eq!(map.len(), oracle.len());
+            prop_assert_eq!(map.summary(), fold(&oracle));
+            prop_assert!(map.iter().eq(oracle.iter()));
+            prop_assert!(map.iter().rev().eq(oracle.iter().rev()));
+        }
+    }
+
+    #[test]
+    fn interval_queries_match_a_scan(
+        intervals in prop::collection::vec((0..200u16, 1..40u16), 0..300),
+        removals in prop::collection::vec((0..200u16, 1..40u16), 0..100),
+        points in prop::collection::vec(0..250u16, 1..20),
+        ranges in prop::collection::vec((0..250u16, 0..40u16), 1..20),
+    ) {
+        let mut map = IntervalMap::new();
+        let mut oracle = BTreeMap::new();
+
+        for (i, &(start, length)) in intervals.iter().enumerate() {
+            prop_assert_eq!(map.insert(start..start + length, i), oracle.insert((start, start + length), i));
+        }
+        for &(start, length) in &removals {
+            prop_assert_eq!(map.remove(&(start..start + length)), oracle.remove(&(start, start + length)));
+        }
+
+        for point in points {
+            let found: Vec<_> = map.stab(&point).map(|(range, v)| ((*range.start, *range.end), *v)).collect();
+            let expected: Vec<_> = oracle
+                .iter()
+                .filter(|((start, end), _v)| *start <= point && point < *end)
+                .map(|(k, v)| (*k, *v))
+                .collect();
+            prop_assert_eq!(found, expected);
+        }
+
+        for (from, length) in ranges {
+            let to = from + length;
+            let found: Vec<_> = map.overlapping(from..to).map(|(range, v)| ((*range.start, *range.end), *v)).collect();
+            let expected: Vec<_> = oracle
+                .iter()
+                .filter(|((start, end), _v)| from < to && *start < to && from < *end)
+                .map(|(k, v)| (*k, *v))
+                .collect();
+            prop_assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn weighted_search_matches_a_scan(
+        entries in prop::collection::vec((0..KEYS, 0..20u64), 0..400),
+        reweights in prop::collection::vec((0..KEYS, 0..20u64), 0..100),
+        points in prop::collection::vec(0..4000u64, 1..20),
+        keys in prop::collection::vec(0..KEYS + 8, 1..20),
+    ) {
+        let mut map = WeightedSortedMap::new();
+        let mut oracle = BTreeMap::new();
+
+        for &(k, weight) in &entries {
+            map.insert(k, (), weight);
+            oracle.insert(k, weight);
+        }
+        for &(k, weight) in &reweights {
+            let expected = oracle.get_mut(&k).map(|old| std::mem::replace(old, weight));
+            prop_assert_eq!(map.set_weight(&k, weight), expected);
+        }
+
+        prop_assert_eq!(map.total_weight(), oracle.values().sum::<u64>());
+
+        for w in points {
+            let mut before = 0;
+            let expected = oracle.iter().find(|(_k, weight)| {
+                before += **weight;
+                before > w
+            });
+            prop_assert_eq!(map.select_by_weight(w).map(|(k, _v)| k), expected.map(|(k, _weight)| k));
+        }
+
+        for key in keys {
+            prop_assert_eq!(map.weight_before(&key), oracle.range(..key).map(|(_k, weight)| weight).sum::<u64>());
+        }
+    }
+}
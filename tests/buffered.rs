@@ -0,0 +1,78 @@
+// Applies random writes and reads to a buffered map and to a `BTreeMap`,
+// checking that they agree. Keys come from a small range so that buffered
+// entries often shadow ones already flushed to the tree.
+
+use std::collections::BTreeMap;
+
+use indexable_sorted_map::BufferedIndexableSortedMap;
+use proptest::prelude::*;
+
+const KEYS: u16 = 256;
+
+#[derive(Debug, Clone)]
+enum Op {
+    Put(u16, u32),
+    Insert(u16, u32),
+    Remove(u16),
+    Lookup(u16),
+    Index(usize),
+    Len,
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        4 => (0..KEYS, any::<u32>()).prop_map(|(k, v)| Op::Put(k, v)),
+        2 => (0..KEYS, any::<u32>()).prop_map(|(k, v)| Op::Insert(k, v)),
+        2 => (0..KEYS).prop_map(Op::Remove),
+        2 => (0..KEYS).prop_map(Op::Lookup),
+        1 => (0..KEYS as usize).prop_map(Op::Index),
+        1 => Just(Op::Len),
+    ]
+}
+
+proptest! {
+    #[test]
+    fn buffered_edits_match_the_oracle(capacity in 1..64usize, ops in prop::collection::vec(op(), 0..1000)) {
+        let mut map = BufferedIndexableSortedMap::with_buffer_capacity(capacity);
+        let mut oracle = BTreeMap::new();
+
+        for op in ops {
+            match op {
+                Op::Put(k, v) => {
+                    map.put(k, v);
+                    oracle.insert(k, v);
+                },
+                Op::Insert(k, v) => prop_assert_eq!(map.insert(k, v), oracle.insert(k, v)),
+                Op::Remove(k) => prop_assert_eq!(map.remove(&k), oracle.remove(&k)),
+                Op::Lookup(k) => prop_assert_eq!(map.lookup(&k), oracle.get(&k)),
+                Op::Index(i) => prop_assert_eq!(map.index(i), oracle.iter().nth(i)),
+                Op::Len => prop_assert_eq!(map.len(), oracle.len()),
+            }
+        }
+
+        let map = map.into_inner();
+        map.check_invariants().map_err(|e| TestCaseError::fail(e.to_string()))?;
+        prop_assert!(map.iter().eq(oracle.iter()));
+    }
+}
+
+#[test]
+fn put_leaves_the_tree_alone_until_the_buffer_fills() {
+    let mut map = BufferedIndexableSortedMap::with_buffer_capacity(4);
+    for k in 0..8u32 {
+        map.put(k, 0);
+    }
+    assert_eq!(map.buffered(), 0);
+
+    // Shadowing every flushed key still only fills the buffer.
+    for k in 0..7u32 {
+        map.put(k, 1);
+    }
+    assert_eq!(map.buffered(), 7);
+    assert_eq!(map.lookup(&3), Some(&1));
+    assert_eq!(map.lookup(&7), Some(&0));
+
+    assert_eq!(map.len(), 8);
+    assert_eq!(map.buffered(), 0);
+    assert_eq!(map.index(6), Some((&6, &1)));
+}
@@ -0,0 +1,109 @@
+use std::collections::HashSet;
+use std::convert::Infallible;
+
+use indexable_sorted_map::{CheckpointSink, IndexableSortedMap};
+
+// A sink that checks every id it is given in a branch or root record
+// refers to a record it already has.
+#[derive(Default)]
+struct Sink {
+    ids: HashSet<u64>,
+}
+
+impl CheckpointSink<u32, u32> for Sink {
+    type Error = Infallible;
+
+    fn leaf(&mut self, id: u64, _key: &u32, _value: &u32) -> Result<(), Infallible> {
+        assert!(self.ids.insert(id), "id {} written twice", id);
+        Ok(())
+    }
+
+    fn branch(&mut self, id: u64, children: &[u64]) -> Result<(), Infallible> {
+        for child in children {
+            assert!(self.ids.contains(child), "branch {} refers to unknown id {}", id, child);
+        }
+        assert!(self.ids.insert(id), "id {} written twice", id);
+        Ok(())
+    }
+
+    fn root(&mut self, root: Option<u64>) -> Result<(), Infallible> {
+        if let Some(root) = root {
+            assert!(self.ids.contains(&root), "root refers to unknown id {}", root);
+        }
+        Ok(())
+    }
+}
+
+fn map(keys: impl Iterator<Item = u32>) -> IndexableSortedMap<u32, u32> {
+    let mut map = IndexableSortedMap::new();
+    for key in keys {
+        map.insert(key, key);
+    }
+    map
+}
+
+#[test]
+fn split_off_maps_are_written_in_full() {
+    let mut a = map(0..10_000);
+    let mut sink_a = Sink::default();
+    a.checkpoint(&mut sink_a).unwrap();
+
+    let mut b = a.split_off(&5000);
+    let mut sink_b = Sink::default();
+    b.checkpoint(&mut sink_b).unwrap();
+    a.checkpoint(&mut sink_a).unwrap();
+
+    // The first half carries on as `b`, the second is new.
+    let (mut b, mut c) = b.split_at_index(1234);
+    b.checkpoint(&mut sink_b).unwrap();
+    c.checkpoint(&mut Sink::default()).unwrap();
+}
+
+#[test]
+fn drained_maps_refer_only_to_their_own_records() {
+    let mut a = map(0..10_000);
+    let mut sink = Sink::default();
+    a.checkpoint(&mut sink).unwrap();
+
+    a.drain_range(2000..7000).for_each(drop);
+    a.checkpoint(&mut sink).unwrap();
+}
+
+#[test]
+fn appended_subtrees_are_written_in_full() {
+    let mut a = map(0..5000);
+    let mut b = map(5000..10_000);
+    let mut sink_a = Sink::default();
+    a.checkpoint(&mut sink_a).unwrap();
+    b.checkpoint(&mut Sink::default()).unwrap();
+
+    a.append(&mut b);
+    a.checkpoint(&mut sink_a).unwrap();
+
+    let mut empty = IndexableSortedMap::new();
+    let mut sink_empty = Sink::default();
+    empty.checkpoint(&mut sink_empty).unwrap();
+    empty.append(&mut a);
+    empty.checkpoint(&mut sink_empty).unwrap();
+}
+
+#[test]
+fn merged_maps_carry_on_the_receivers_checkpoints() {
+    let mut a = map((0..10_000).step_by(2));
+    let mut b = map((1..10_000).step_by(2));
+    let mut sink_a = Sink::default();
+    a.checkpoint(&mut sink_a).unwrap();
+    b.checkpoint(&mut Sink::default()).unwrap();
+
+    // A single entry is merged into the larger map, whichever it is.
+    let mut single = map(std::iter::once(3));
+    let mut sink_single = Sink::default();
+    single.checkpoint(&mut sink_single).unwrap();
+
+    let mut a = a.merge_with(b, |_k, ours, _theirs| ours);
+    a.checkpoint(&mut sink_a).unwrap();
+
+    let mut single = single.merge_with(a, |_k, ours, _theirs| ours);
+    single.checkpoint(&mut sink_single).unwrap();
+}
+
@@ -0,0 +1,63 @@
+#![cfg(feature = "lz4")]
+
+use indexable_sorted_map::{CodecError, DecodeIssue, IndexableSortedMap};
+
+// The magic number, entry count and first frame's header come before the
+// frame's decompressed-size prefix.
+const SIZE_PREFIX: usize = 4 + 8 + 4 + 4;
+
+// A map of one short string key and no value, too small for LZ4 to find
+// matches in, so the encoded entry can be found and damaged in place.
+fn encoded() -> Vec<u8> {
+    let mut map = IndexableSortedMap::new();
+    map.insert("abc".to_string(), ());
+    map.to_bytes().unwrap()
+}
+
+fn with_huge_key_length(mut bytes: Vec<u8>) -> Vec<u8> {
+    let entry = b"\x03\0\0\0\0\0\0\0abc";
+    let at = bytes.windows(entry.len()).position(|w| w == entry).unwrap();
+    bytes[at..at + 8].copy_from_slice(&(1u64 << 40).to_le_bytes());
+    bytes
+}
+
+fn with_huge_frame_size(mut bytes: Vec<u8>) -> Vec<u8> {
+    bytes[SIZE_PREFIX..SIZE_PREFIX + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+    bytes
+}
+
+#[test]
+fn a_corrupt_length_inside_an_entry_is_an_error() {
+    let bytes = with_huge_key_length(encoded());
+    let result = IndexableSortedMap::<String, ()>::from_bytes(&bytes);
+    assert!(matches!(result, Err(CodecError::Corrupt)));
+}
+
+#[test]
+fn a_corrupt_frame_size_is_an_error() {
+    let bytes = with_huge_frame_size(encoded());
+    let result = IndexableSortedMap::<String, ()>::from_bytes(&bytes);
+    assert!(matches!(result, Err(CodecError::Corrupt)));
+}
+
+#[test]
+fn lenient_decoding_reports_a_corrupt_length_as_a_corrupt_frame() {
+    let bytes = with_huge_key_length(encoded());
+    let (map, issues) = IndexableSortedMap::<String, ()>::from_bytes_lenient(&bytes).unwrap();
+    assert!(map.is_empty());
+    assert_eq!(
+        issues,
+        [
+            DecodeIssue::CorruptFrame { frame: 0, entries: 1, recovered: 0 },
+            DecodeIssue::LengthMismatch { expected: 1, recovered: 0 },
+        ]
+    );
+}
+
+#[test]
+fn lenient_decoding_reports_a_corrupt_frame_size_as_a_corrupt_frame() {
+    let bytes = with_huge_frame_size(encoded());
+    let (map, issues) = IndexableSortedMap::<String, ()>::from_bytes_lenient(&bytes).unwrap();
+    assert!(map.is_empty());
+    assert_eq!(issues[0], DecodeIssue::CorruptFrame { frame: 0, entries: 1, recovered: 0 });
+}
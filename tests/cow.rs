@@ -0,0 +1,206 @@
+#![cfg(feature = "cow")]
+
+// Clones maps and edits the clones and originals in any order, checking
+// each against an oracle of its own. With `cow` the clones share nodes
+// until one side edits them, so an edit copying too little would show up
+// in the other map, and one copying wrongly as a malformed tree.
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+
+use indexable_sorted_map::{CheckpointSink, IndexableSortedMap};
+use proptest::prelude::*;
+use proptest::sample::Index;
+
+const KEYS: u16 = 512;
+
+#[derive(Debug, Clone)]
+enum Op {
+    Clone(Index),
+    Drop(Index),
+    Insert(Index, u16, u32),
+    Remove(Index, u16),
+    RemoveAt(Index, Index),
+    LookupMut(Index, u16),
+    IndexMut(Index, Index),
+    Entry(Index, u16, u32),
+    IterMut(Index),
+    Cursor(Index, Index),
+    SplitOff(Index, u16),
+    Append(Index, Index),
+    Checkpoint(Index),
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    let map = any::<Index>;
+    prop_oneof![
+        3 => map().prop_map(Op::Clone),
+        1 => map().prop_map(Op::Drop),
+        6 => (map(), 0..KEYS, any::<u32>()).prop_map(|(m, k, v)| Op::Insert(m, k, v)),
+        4 => (map(), 0..KEYS).prop_map(|(m, k)| Op::Remove(m, k)),
+        1 => (map(), any::<Index>()).prop_map(|(m, i)| Op::RemoveAt(m, i)),
+        2 => (map(), 0..KEYS).prop_map(|(m, k)| Op::LookupMut(m, k)),
+        2 => (map(), any::<Index>()).prop_map(|(m, i)| Op::IndexMut(m, i)),
+        2 => (map(), 0..KEYS, any::<u32>()).prop_map(|(m, k, v)| Op::Entry(m, k, v)),
+        1 => map().prop_map(Op::IterMut),
+        2 => (map(), any::<Index>()).prop_map(|(m, i)| Op::Cursor(m, i)),
+        1 => (map(), 0..KEYS).prop_map(|(m, k)| Op::SplitOff(m, k)),
+        1 => (map(), map()).prop_map(|(m, n)| Op::Append(m, n)),
+        1 => map().prop_map(Op::Checkpoint),
+    ]
+}
+
+struct Discard;
+
+impl CheckpointSink<u16, u32> for Discard {
+    type Error = Infallible;
+
+    fn leaf(&mut self, _id: u64, _key: &u16, _value: &u32) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn branch(&mut self, _id: u64, _children: &[u64]) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn root(&mut self, _root: Option<u64>) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+// Picks a position in a map, or one past its end for an empty map.
+fn position(i: &Index, len: usize) -> usize {
+    i.index(len.max(1))
+}
+
+proptest! {
+    #[test]
+    fn edited_clones_match_their_oracles(
+        start in 0..KEYS as usize,
+        ops in prop::collection::vec(op(), 0..250),
+    ) {
+        let first: IndexableSortedMap<u16, u32> = (0..start as u16).map(|k| (k, u32::from(k))).collect();
+        let mut maps = vec![(first.iter().map(|(k, v)| (*k, *v)).collect::<BTreeMap<_, _>>(), first)];
+
+        for op in ops {
+            let len = maps.len();
+            match op {
+                Op::Clone(m) => {
+                    let (oracle, map) = &maps[m.index(len)];
+                    let copy = (oracle.clone(), map.clone());
+                    maps.push(copy);
+                },
+                Op::Drop(m) => {
+                    if len > 1 {
+                        maps.swap_remove(m.index(len));
+                    }
+                },
+                Op::Insert(m, k, v) => {
+                    let (oracle, map) = &mut maps[m.index(len)];
+                    prop_assert_eq!(map.insert(k, v), oracle.insert(k, v));
+                },
+                Op::Remove(m, k) => {
+                    let (oracle, map) = &mut maps[m.index(len)];
+                    prop_assert_eq!(map.remove(&k), oracle.remove(&k));
+                },
+                Op::RemoveAt(m, i) => {
+                    let (oracle, map) = &mut maps[m.index(len)];
+                    let i = position(&i, map.len());
+                    let expected = oracle.keys().nth(i).copied().map(|k| (k, oracle.remove(&k).unwrap()));
+                    prop_assert_eq!(map.remove_at(i), expected);
+                },
+                Op::LookupMut(m, k) => {
+                    let (oracle, map) = &mut maps[m.index(len)];
+                    if let Some(v) = map.lookup_mut(&k) {
+                        *v = v.wrapping_add(1);
+                    }
+                    if let Some(v) = oracle.get_mut(&k) {
+                        *v = v.wrapping_add(1);
+                    }
+                },
+                Op::IndexMut(m, i) => {
+                    let (oracle, map) = &mut maps[m.index(len)];
+                    let i = position(&i, map.len());
+                    if let Some((_k, v)) = map.index_mut(i) {
+                        *v = v.wrapping_add(1);
+                    }
+                    if let Some((_k, v)) = oracle.iter_mut().nth(i) {
+                        *v = v.wrapping_add(1);
+                    }
+                },
+                Op::Entry(m, k, v) => {
+                    let (oracle, map) = &mut maps[m.index(len)];
+                    map.entry(k).and_modify(|v| *v = v.wrapping_add(1)).or_insert(v);
+                    oracle.entry(k).and_modify(|v| *v = v.wrapping_add(1)).or_insert(v);
+                },
+                Op::IterMut(m) => {
+                    let (oracle, map) = &mut maps[m.index(len)];
+                    map.values_mut().for_each(|v| *v = v.wrapping_add(1));
+                    oracle.values_mut().for_each(|v| *v = v.wrapping_add(1));
+                },
+                Op::Cursor(m, i) => {
+                    // Walks a few entries, bumping each and removing every
+                    // third, so the cursor reaches leaves it didn't start in.
+                    let (oracle, map) = &mut maps[m.index(len)];
+                    let i = position(&i, map.len());
+                    let mut cursor = map.cursor_mut_at(i);
+                    for step in 0..40 {
+                        let Some(k) = cursor.key().copied() else { break };
+                        if step % 3 == 2 {
+                            prop_assert_eq!(cursor.remove_current(), oracle.remove_entry(&k));
+                        } else {
+                            let v = cursor.value_mut().unwrap();
+                            *v = v.wrapping_add(1);
+                            let v = oracle.get_mut(&k).unwrap();
+                            *v = v.wrapping_add(1);
+                            cursor.move_next();
+                        }
+                    }
+                },
+                Op::SplitOff(m, k) => {
+                    let (oracle, map) = &mut maps[m.index(len)];
+                    let piece = (oracle.split_off(&k), map.split_off(&k));
+                    maps.push(piece);
+                },
+                Op::Append(m, n) => {
+                    let (m, n) = (m.index(len), n.index(len));
+                    if m != n {
+                        let (mut oracle, mut map) = maps.swap_remove(n);
+                        let m = if m == maps.len() { n } else { m };
+                        let (ours, theirs) = &mut maps[m];
+                        ours.append(&mut oracle);
+                        theirs.append(&mut map);
+                    }
+                },
+                Op::Checkpoint(m) => {
+                    let (_oracle, map) = &mut maps[m.index(len)];
+                    let Ok(()) = map.checkpoint(&mut Discard);
+                },
+            }
+
+            for (oracle, map) in &maps {
+                map.check_invariants().map_err(|e| TestCaseError::fail(e.to_string()))?;
+                prop_assert!(map.iter().eq(oracle.iter()));
+            }
+        }
+    }
+}
+
+#[test]
+fn a_clone_is_unaffected_by_edits_to_the_original() {
+    let mut map: IndexableSortedMap<u32, String> = (0..10_000).map(|k| (k, k.to_string())).collect();
+    let snapshot = map.clone();
+
+    map.retain(|k, _v| k % 2 == 0);
+    for v in map.values_mut() {
+        v.push('!');
+    }
+    map.insert(20_000, String::from("new"));
+
+    assert_eq!(snapshot.len(), 10_000);
+    assert!(snapshot.iter().all(|(k, v)| *v == k.to_string()));
+    assert_eq!(map.len(), 5_001);
+    assert_eq!(map.lookup(&4), Some(&String::from("4!")));
+    snapshot.check_invariants().unwrap();
+    map.check_invariants().unwrap();
+}
@@ -0,0 +1,108 @@
+// Drives a cursor through random moves and edits, checking its position
+// and focus against a sorted `Vec` at every step and the tree's shape at
+// the end. Keys are spread out so that most insertions beside the focus
+// are in order, and the maps are small enough that edits often cross
+// leaves or reshape the tree.
+
+use indexable_sorted_map::IndexableSortedMap;
+use proptest::prelude::*;
+
+const KEYS: u32 = 100_000;
+
+#[derive(Debug, Clone)]
+enum Op {
+    Next,
+    Prev,
+    MoveTo(u32),
+    InsertBefore(u32),
+    InsertAfter(u32),
+    Remove,
+    Bump,
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        3 => Just(Op::Next),
+        2 => Just(Op::Prev),
+        1 => (0..KEYS).prop_map(Op::MoveTo),
+        2 => (0..KEYS).prop_map(Op::InsertBefore),
+        2 => (0..KEYS).prop_map(Op::InsertAfter),
+        2 => Just(Op::Remove),
+        1 => Just(Op::Bump),
+    ]
+}
+
+fn apply(len: u32, start: usize, ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let mut map: IndexableSortedMap<u32, u32> = (0..len).map(|i| (i * (KEYS / len.max(1)), i)).collect();
+    let mut model: Vec<(u32, u32)> = map.iter().map(|(k, v)| (*k, *v)).collect();
+    let mut position = start.min(model.len());
+    let mut cursor = map.cursor_mut_at(start);
+
+    for op in ops {
+        match op {
+            Op::Next => {
+                prop_assert_eq!(cursor.move_next(), position < model.len());
+                position = (position + 1).min(model.len());
+            },
+            Op::Prev => {
+                prop_assert_eq!(cursor.move_prev(), position > 0);
+                position = position.saturating_sub(1);
+            },
+            Op::MoveTo(k) => {
+                cursor.move_to(&k);
+                position = model.partition_point(|(key, _v)| *key < k);
+            },
+            Op::InsertBefore(k) => {
+                let fits = (position == 0 || model[position - 1].0 < k) && model.get(position).is_none_or(|(key, _v)| k < *key);
+                prop_assert_eq!(cursor.insert_before(k, 0).is_ok(), fits);
+                if fits {
+                    model.insert(position, (k, 0));
+                    position += 1;
+                }
+            },
+            Op::InsertAfter(k) => {
+                let fits = position < model.len()
+                    && model[position].0 < k
+                    && model.get(position + 1).is_none_or(|(key, _v)| k < *key);
+                prop_assert_eq!(cursor.insert_after(k, 0).is_ok(), fits);
+                if fits {
+                    model.insert(position + 1, (k, 0));
+                }
+            },
+            Op::Remove => {
+                let expected = (position < model.len()).then(|| model.remove(position));
+                prop_assert_eq!(cursor.remove_current(), expected);
+            },
+            Op::Bump => {
+                if let Some(v) = cursor.value_mut() {
+                    *v += 1;
+                    model[position].1 += 1;
+                }
+            },
+        }
+
+        prop_assert_eq!(cursor.position(), position);
+        prop_assert_eq!(cursor.focus(), model.get(position).map(|(k, v)| (k, v)));
+    }
+
+    drop(cursor);
+    if let Err(e) = map.check_invariants() {
+        return Err(TestCaseError::fail(e.to_string()));
+    }
+    prop_assert!(map.iter().map(|(k, v)| (*k, *v)).eq(model.iter().copied()));
+    for (i, (k, _v)) in model.iter().enumerate() {
+        prop_assert_eq!(map.rank(k), Ok(i));
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn cursor_edits_match_a_sorted_vec(
+        len in 0..300u32,
+        start in 0..310usize,
+        ops in prop::collection::vec(op(), 0..1000),
+    ) {
+        apply(len, start, ops)?;
+    }
+}
@@ -0,0 +1,156 @@
+// Applies random sequences of edits and queries to the map and to an
+// oracle of a `BTreeMap` for keyed operations and a sorted `Vec` for
+// positional ones, checking that they agree at every step and that the
+// tree stays well formed. Keys come from a small range so that inserts
+// often replace and removals often hit.
+
+use std::collections::BTreeMap;
+
+use indexable_sorted_map::IndexableSortedMap;
+use proptest::prelude::*;
+
+const KEYS: u16 = 512;
+
+#[derive(Debug, Clone)]
+enum Op {
+    Insert(u16, u32),
+    GetOrInsert(u16, u32),
+    Entry(u16, u32),
+    Remove(u16),
+    RemoveAt(usize),
+    Lookup(u16),
+    Index(usize),
+    Rank(u16),
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    // Positions run a little past the end of any map drawn, to cover the
+    // misses.
+    let position = 0..KEYS as usize + 8;
+    prop_oneof![
+        4 => (0..KEYS, any::<u32>()).prop_map(|(k, v)| Op::Insert(k, v)),
+        2 => (0..KEYS, any::<u32>()).prop_map(|(k, v)| Op::GetOrInsert(k, v)),
+        2 => (0..KEYS, any::<u32>()).prop_map(|(k, v)| Op::Entry(k, v)),
+        3 => (0..KEYS).prop_map(Op::Remove),
+        1 => position.clone().prop_map(Op::RemoveAt),
+        1 => (0..KEYS).prop_map(Op::Lookup),
+        1 => position.prop_map(Op::Index),
+        1 => (0..KEYS).prop_map(Op::Rank),
+    ]
+}
+
+struct Oracle {
+    map: BTreeMap<u16, u32>,
+    entries: Vec<(u16, u32)>,
+}
+
+impl Oracle {
+    fn new(map: &IndexableSortedMap<u16, u32>) -> Self {
+        Oracle {
+            map: map.iter().map(|(k, v)| (*k, *v)).collect(),
+            entries: map.iter().map(|(k, v)| (*k, *v)).collect(),
+        }
+    }
+
+    fn rank(&self, key: u16) -> Result<usize, usize> {
+        self.entries.binary_search_by_key(&key, |(k, _v)| *k)
+    }
+
+    fn insert(&mut self, key: u16, value: u32) -> Option<u32> {
+        match self.rank(key) {
+            Ok(i) => self.entries[i].1 = value,
+            Err(i) => self.entries.insert(i, (key, value)),
+        }
+        self.map.insert(key, value)
+    }
+
+    fn get_or_insert(&mut self, key: u16, value: u32) -> (usize, u32) {
+        let i = match self.rank(key) {
+            Ok(i) => i,
+            Err(i) => {
+                self.entries.insert(i, (key, value));
+                self.map.insert(key, value);
+                i
+            },
+        };
+        (i, self.entries[i].1)
+    }
+
+    fn remove(&mut self, key: u16) -> Option<u32> {
+        if let Ok(i) = self.rank(key) {
+            self.entries.remove(i);
+        }
+        self.map.remove(&key)
+    }
+
+    fn remove_at(&mut self, i: usize) -> Option<(u16, u32)> {
+        if i >= self.entries.len() {
+            return None;
+        }
+        let (k, v) = self.entries.remove(i);
+        self.map.remove(&k);
+        Some((k, v))
+    }
+}
+
+fn apply(map: &mut IndexableSortedMap<u16, u32>, ops: Vec<Op>) -> Result<(), TestCaseError> {
+    let mut oracle = Oracle::new(map);
+
+    for op in ops {
+        match op {
+            Op::Insert(k, v) => prop_assert_eq!(map.insert(k, v), oracle.insert(k, v)),
+            Op::GetOrInsert(k, v) => {
+                let (i, value) = map.get_or_insert_index(k, || v);
+                prop_assert_eq!((i, *value), oracle.get_or_insert(k, v));
+            },
+            Op::Entry(k, v) => {
+                // Adds `v` to an occupied entry, or inserts it.
+                let value = *map.entry(k).and_modify(|value| *value = value.wrapping_add(v)).or_insert(v);
+                let expected = oracle.map.get(&k).map_or(v, |value| value.wrapping_add(v));
+                oracle.insert(k, expected);
+                prop_assert_eq!(value, expected);
+            },
+            Op::Remove(k) => prop_assert_eq!(map.remove(&k), oracle.remove(k)),
+            Op::RemoveAt(i) => prop_assert_eq!(map.remove_at(i), oracle.remove_at(i)),
+            Op::Lookup(k) => prop_assert_eq!(map.lookup(&k), oracle.map.get(&k)),
+            Op::Index(i) => {
+                let expected = oracle.entries.get(i).map(|(k, v)| (k, v));
+                prop_assert_eq!(map.index(i), expected);
+            },
+            Op::Rank(k) => prop_assert_eq!(map.rank(&k), oracle.rank(k)),
+        }
+
+        prop_assert_eq!(map.len(), oracle.entries.len());
+        if let Err(e) = map.check_invariants() {
+            return Err(TestCaseError::fail(e.to_string()));
+        }
+    }
+
+    prop_assert!(map.iter().eq(oracle.map.iter()));
+    prop_assert!(map.iter().rev().eq(oracle.entries.iter().rev().map(|(k, v)| (k, v))));
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn edits_from_empty_match_the_oracle(ops in prop::collection::vec(op(), 0..2000)) {
+        apply(&mut IndexableSortedMap::new(), ops)?;
+    }
+
+    #[test]
+    fn edits_to_an_arbitrary_map_match_the_oracle(
+        mut map in any::<IndexableSortedMap<u16, u32>>(),
+        ops in prop::collection::vec(op(), 0..500),
+    ) {
+        apply(&mut map, ops)?;
+    }
+
+    #[test]
+    fn bulk_built_maps_match_the_oracle(
+        entries in prop::collection::vec((0..KEYS, any::<u32>()), 0..1000),
+        ops in prop::collection::vec(op(), 0..500),
+    ) {
+        let mut map: IndexableSortedMap<u16, u32> = entries.into_iter().collect();
+        apply(&mut map, ops)?;
+    }
+}
@@ -0,0 +1,95 @@
+// Checks union, intersection and difference against `BTreeSet` on pairs
+// of sets of very different sizes and of overlapping or disjoint ranges,
+// so that the recursion takes its single-element and whole-subtree
+// shortcuts as well as the general split and join. The results must also
+// be well formed trees.
+
+use std::collections::BTreeSet;
+
+use indexable_sorted_map::IndexableSortedSet;
+use proptest::prelude::*;
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Union,
+    Intersection,
+    Difference,
+}
+
+// Elements drawn from a window of the key space, so that two sets may
+// interleave, overlap at one end or not overlap at all.
+fn elements() -> impl Strategy<Value = Vec<u32>> {
+    (0..4u32, 0..300usize).prop_flat_map(|(window, len)| {
+        let start = window * 250;
+        prop::collection::vec(start..start + 500, 0..=len)
+    })
+}
+
+// Half the sets are built in bulk and half by insertion, which leaves
+// leaves of uneven sizes behind.
+fn set(elements: &[u32], bulk: bool) -> IndexableSortedSet<u32> {
+    if bulk {
+        elements.iter().copied().collect()
+    } else {
+        let mut set = IndexableSortedSet::new();
+        for &x in elements {
+            set.insert(x);
+        }
+        set
+    }
+}
+
+fn check(op: Op, a: Vec<u32>, b: Vec<u32>, bulk: (bool, bool)) -> Result<(), TestCaseError> {
+    let expected: BTreeSet<u32> = {
+        let a: BTreeSet<u32> = a.iter().copied().collect();
+        let b: BTreeSet<u32> = b.iter().copied().collect();
+        match op {
+            Op::Union => a.union(&b).copied().collect(),
+            Op::Intersection => a.intersection(&b).copied().collect(),
+            Op::Difference => a.difference(&b).copied().collect(),
+        }
+    };
+
+    let a = set(&a, bulk.0);
+    let b = set(&b, bulk.1);
+    let result = match op {
+        Op::Union => a.union(b),
+        Op::Intersection => a.intersection(b),
+        Op::Difference => a.difference(b),
+    };
+
+    if let Err(e) = result.as_map().check_invariants() {
+        return Err(TestCaseError::fail(e.to_string()));
+    }
+    prop_assert_eq!(result.len(), expected.len());
+    prop_assert!(result.iter().eq(expected.iter()));
+    Ok(())
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![Just(Op::Union), Just(Op::Intersection), Just(Op::Difference)]
+}
+
+proptest! {
+    #[test]
+    fn set_algebra_matches_btreeset(
+        op in op(),
+        a in elements(),
+        b in elements(),
+        bulk in any::<(bool, bool)>(),
+    ) {
+        check(op, a, b, bulk)?;
+    }
+
+    #[test]
+    fn set_algebra_with_a_tiny_side_matches_btreeset(
+        op in op(),
+        a in elements(),
+        b in prop::collection::vec(0..1500u32, 0..4),
+        swap in any::<bool>(),
+        bulk in any::<(bool, bool)>(),
+    ) {
+        let (a, b) = if swap { (b, a) } else { (a, b) };
+        check(op, a, b, bulk)?;
+    }
+}
@@ -0,0 +1,39 @@
+use indexable_sorted_map::StableIndexableSortedMap;
+
+#[test]
+fn value_addresses_survive_rebalancing() {
+    let mut map = StableIndexableSortedMap::new();
+    for i in 0..1000u32 {
+        map.insert(i * 2, i);
+    }
+
+    let addresses: Vec<*const u32> = (0..1000u32).map(|i| map.lookup(&(i * 2)).unwrap() as *const u32).collect();
+
+    // Interleaved inserts split nodes all over the tree; removals merge
+    // them; compacting rebuilds it from scratch.
+    for i in 0..1000u32 {
+        map.insert(i * 2 + 1, i);
+    }
+    for i in 0..500u32 {
+        map.remove(&(i * 4 + 1));
+    }
+    map.compact();
+
+    for (i, &address) in addresses.iter().enumerate() {
+        let key = i as u32 * 2;
+        assert_eq!(map.lookup(&key).unwrap() as *const u32, address);
+        // SAFETY: the test relies on exactly the guarantee under test.
+        assert_eq!(unsafe { *address }, i as u32);
+    }
+}
+
+#[test]
+fn replacing_a_value_keeps_its_address() {
+    let mut map = StableIndexableSortedMap::new();
+    map.insert("key", 1);
+    let address = map.lookup(&"key").unwrap() as *const i32;
+
+    assert_eq!(map.insert("key", 2), Some(1));
+    assert_eq!(map.lookup(&"key").unwrap() as *const i32, address);
+    assert_eq!(map.lookup(&"key"), Some(&2));
+}